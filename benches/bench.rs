@@ -30,7 +30,7 @@ fn spawn_static(b: &mut Bencher) {
     struct Bundle {
         pos: Position,
         vel: Velocity,
-    };
+    }
 
     let mut world = World::new();
     b.iter(|| {
@@ -46,7 +46,7 @@ fn spawn_batch(b: &mut Bencher) {
     struct Bundle {
         pos: Position,
         vel: Velocity,
-    };
+    }
 
     let mut world = World::new();
     b.iter(|| {
@@ -60,6 +60,18 @@ fn spawn_batch(b: &mut Bencher) {
     });
 }
 
+fn spawn_column_batch(b: &mut Bencher) {
+    let mut world = World::new();
+    b.iter(|| {
+        let mut batch = ColumnBatch::new();
+        batch
+            .add((0..1_000).map(|_| Position(0.0)).collect())
+            .add((0..1_000).map(|_| Velocity(0.0)).collect());
+        world.spawn_column_batch(batch);
+        world.clear();
+    });
+}
+
 fn iterate_100k(b: &mut Bencher) {
     let mut world = World::new();
     for i in 0..100_000 {
@@ -72,6 +84,57 @@ fn iterate_100k(b: &mut Bencher) {
     })
 }
 
+fn iterate_mut_100k(b: &mut Bencher) {
+    let mut world = World::new();
+    for i in 0..100_000 {
+        world.spawn((Position(-(i as f32)), Velocity(i as f32)));
+    }
+    b.iter(|| {
+        for (_, (pos, vel)) in world.query_mut::<(&mut Position, &Velocity)>() {
+            pos.0 += vel.0;
+        }
+    })
+}
+
+fn iterate_chunks_100k(b: &mut Bencher) {
+    let mut world = World::new();
+    for i in 0..100_000 {
+        world.spawn((Position(-(i as f32)), Velocity(i as f32)));
+    }
+    b.iter(|| {
+        for (_, (pos, vel)) in world.query::<(&mut Position, &Velocity)>().iter_chunks() {
+            for (pos, vel) in pos.iter_mut().zip(vel) {
+                pos.0 += vel.0;
+            }
+        }
+    })
+}
+
+fn iterate_chunked_100k(b: &mut Bencher) {
+    let mut world = World::with_chunk_size(16 * 1024);
+    for i in 0..100_000 {
+        world.spawn((Position(-(i as f32)), Velocity(i as f32)));
+    }
+    b.iter(|| {
+        for (_, (pos, vel)) in &mut world.query::<(&mut Position, &Velocity)>() {
+            pos.0 += vel.0;
+        }
+    })
+}
+
+fn insert_remove(b: &mut Bencher) {
+    let mut world = World::new();
+    let entities = (0..1_000)
+        .map(|_| world.spawn((Position(0.0),)))
+        .collect::<Vec<_>>();
+    let mut entities = entities.iter().cycle();
+    b.iter(|| {
+        let e = *entities.next().unwrap();
+        world.insert_one(e, Velocity(0.0)).unwrap();
+        world.remove_one::<Velocity>(e).unwrap();
+    });
+}
+
 fn build(b: &mut Bencher) {
     let mut world = World::new();
     let mut builder = EntityBuilder::new();
@@ -86,7 +149,12 @@ benchmark_group!(
     spawn_tuple,
     spawn_static,
     spawn_batch,
+    spawn_column_batch,
     iterate_100k,
+    iterate_mut_100k,
+    iterate_chunks_100k,
+    iterate_chunked_100k,
+    insert_remove,
     build
 );
 benchmark_main!(benches);
@@ -0,0 +1,221 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_quote, DeriveInput, Error, Ident, Member, Result, Type};
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let ident = input.ident;
+    let data = match input.data {
+        syn::Data::Struct(s) => s,
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "derive(Bundle) only supports structs",
+            ))
+        }
+    };
+    let fields = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            Ok(Field {
+                member: match field.ident {
+                    Some(ref x) => Member::Named(x.clone()),
+                    None => Member::Unnamed(i.into()),
+                },
+                binding: format_ident!("__hecs_field_{}", i),
+                ty: &field.ty,
+                kind: kind(&field.attrs)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut generics = input.generics.clone();
+    {
+        let predicates = &mut generics.make_where_clause().predicates;
+        for field in &fields {
+            let ty = field.ty;
+            match field.kind {
+                Kind::Component => predicates.push(parse_quote! { #ty: ::hecs::Component }),
+                Kind::Bundle => predicates.push(parse_quote! { #ty: ::hecs::Bundle }),
+                Kind::Skip => predicates.push(parse_quote! { #ty: ::core::default::Default }),
+            }
+        }
+        if input.generics.lifetimes().next().is_none() {
+            for param in input.generics.type_params() {
+                let ident = &param.ident;
+                predicates.push(parse_quote! { #ident: 'static });
+            }
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let components = fields
+        .iter()
+        .filter(|x| x.kind == Kind::Component)
+        .collect::<Vec<_>>();
+    let component_tys = components.iter().map(|x| x.ty).collect::<Vec<_>>();
+    let component_members = components.iter().map(|x| &x.member).collect::<Vec<_>>();
+    let component_bindings = components.iter().map(|x| &x.binding).collect::<Vec<_>>();
+    let bundles = fields
+        .iter()
+        .filter(|x| x.kind == Kind::Bundle)
+        .collect::<Vec<_>>();
+    let bundle_tys = bundles.iter().map(|x| x.ty).collect::<Vec<_>>();
+    let bundle_members = bundles.iter().map(|x| &x.member).collect::<Vec<_>>();
+    let bundle_bindings = bundles.iter().map(|x| &x.binding).collect::<Vec<_>>();
+    let skipped = fields
+        .iter()
+        .filter(|x| x.kind == Kind::Skip)
+        .collect::<Vec<_>>();
+    let skipped_members = skipped.iter().map(|x| &x.member).collect::<Vec<_>>();
+    let skipped_bindings = skipped.iter().map(|x| &x.binding).collect::<Vec<_>>();
+
+    // A static in a generic function is shared by every instantiation, so generic bundles look
+    // theirs up by type, which needs the bundle to be `'static`. Bundles with lifetime parameters
+    // can't be, so they compute their IDs on every call instead.
+    let ids = if input.generics.params.is_empty() {
+        quote! {
+            static IDS: ::hecs::__private::IdCache = ::hecs::__private::IdCache::new();
+            f(IDS.get(|| ::hecs::__private::sorted_ids(
+                ::core::stringify!(#ident),
+                &<Self as ::hecs::Bundle>::static_type_info(),
+            )))
+        }
+    } else if input.generics.lifetimes().next().is_none() {
+        quote! {
+            static IDS: ::hecs::__private::GenericIdCache =
+                ::hecs::__private::GenericIdCache::new();
+            f(IDS.get(::core::any::TypeId::of::<Self>(), || ::hecs::__private::sorted_ids(
+                ::core::stringify!(#ident),
+                &<Self as ::hecs::Bundle>::static_type_info(),
+            )))
+        }
+    } else {
+        quote! {
+            f(&::hecs::__private::sorted_ids(
+                ::core::stringify!(#ident),
+                &<Self as ::hecs::Bundle>::static_type_info(),
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::hecs::DynamicBundle for #ident #ty_generics #where_clause {
+            fn with_ids<__HecsT>(
+                &self,
+                f: impl ::core::ops::FnOnce(&[::hecs::ComponentId]) -> __HecsT,
+            ) -> __HecsT {
+                <Self as ::hecs::Bundle>::with_static_ids(f)
+            }
+
+            fn type_info(&self) -> ::hecs::__private::Vec<::hecs::TypeInfo> {
+                <Self as ::hecs::Bundle>::static_type_info()
+            }
+
+            #[allow(unused_variables, unused_mut, clippy::forget_non_drop)]
+            unsafe fn put(
+                mut self,
+                mut f: impl ::core::ops::FnMut(*mut u8, ::hecs::ComponentId, usize) -> bool,
+            ) {
+                #(
+                    if f(
+                        (&mut self.#component_members as *mut #component_tys).cast::<u8>(),
+                        ::hecs::ComponentId::of::<#component_tys>(),
+                        ::core::mem::size_of::<#component_tys>(),
+                    ) {
+                        ::core::mem::forget(self.#component_members);
+                    }
+                )*
+                #(
+                    ::hecs::DynamicBundle::put(self.#bundle_members, &mut f);
+                )*
+            }
+        }
+
+        impl #impl_generics ::hecs::Bundle for #ident #ty_generics #where_clause {
+            fn with_static_ids<__HecsT>(
+                f: impl ::core::ops::FnOnce(&[::hecs::ComponentId]) -> __HecsT,
+            ) -> __HecsT {
+                #ids
+            }
+
+            fn static_type_info() -> ::hecs::__private::Vec<::hecs::TypeInfo> {
+                let mut info = ::hecs::__private::Vec::new();
+                #(info.push(::hecs::TypeInfo::of::<#component_tys>());)*
+                #(info.extend(<#bundle_tys as ::hecs::Bundle>::static_type_info());)*
+                info.sort_unstable();
+                info
+            }
+
+            #[allow(unused_variables, unused_mut)]
+            unsafe fn get(
+                mut f: impl ::core::ops::FnMut(::hecs::ComponentId, usize) -> ::core::option::Option<::core::ptr::NonNull<u8>>,
+            ) -> ::core::result::Result<Self, ::hecs::MissingComponent> {
+                // User code runs before any component is read, so that if it panics, the world
+                // still owns every component
+                #(
+                    let #skipped_bindings = ::core::default::Default::default();
+                )*
+                #(
+                    let #component_bindings = f(
+                        ::hecs::ComponentId::of::<#component_tys>(),
+                        ::core::mem::size_of::<#component_tys>(),
+                    )
+                    .ok_or_else(::hecs::MissingComponent::new::<#component_tys>)?
+                    .cast::<#component_tys>()
+                    .as_ptr();
+                )*
+                // Nested bundles read their components as they go, so those copies must not be
+                // dropped if a later bundle turns out to be missing something
+                #(
+                    let #bundle_bindings = ::core::mem::ManuallyDrop::new(
+                        <#bundle_tys as ::hecs::Bundle>::get(&mut f)?,
+                    );
+                )*
+                ::core::result::Result::Ok(Self {
+                    #(#component_members: #component_bindings.read(),)*
+                    #(#bundle_members: ::core::mem::ManuallyDrop::into_inner(#bundle_bindings),)*
+                    #(#skipped_members: #skipped_bindings,)*
+                })
+            }
+        }
+    })
+}
+
+struct Field<'a> {
+    member: Member,
+    /// Name of a local variable holding this field
+    binding: Ident,
+    ty: &'a Type,
+    kind: Kind,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq)]
+enum Kind {
+    /// A single component
+    Component,
+    /// A nested bundle, marked `#[bundle]`
+    Bundle,
+    /// Not part of the bundle, marked `#[bundle(skip)]`
+    Skip,
+}
+
+fn kind(attrs: &[syn::Attribute]) -> Result<Kind> {
+    let mut kind = Kind::Component;
+    for attr in attrs.iter().filter(|x| x.path.is_ident("bundle")) {
+        if kind != Kind::Component {
+            return Err(Error::new_spanned(attr, "duplicate bundle attribute"));
+        }
+        kind = if attr.tokens.is_empty() {
+            Kind::Bundle
+        } else {
+            let arg = attr.parse_args::<Ident>()?;
+            if arg != "skip" {
+                return Err(Error::new_spanned(arg, "expected `skip`"));
+            }
+            Kind::Skip
+        };
+    }
+    Ok(kind)
+}
@@ -14,117 +14,69 @@
 
 extern crate proc_macro;
 
+mod bundle;
+mod query;
+
 use proc_macro::TokenStream;
-use proc_macro2::Span;
-use quote::quote;
 use syn::{parse_macro_input, DeriveInput};
 
-/// Implement `Bundle` for a monomorphic struct
+/// Implement `Bundle` for a struct
 ///
 /// Using derived `Bundle` impls improves spawn performance and can be convenient when combined with
 /// other derives like `serde::Deserialize`.
-#[allow(clippy::cognitive_complexity)]
-#[proc_macro_derive(Bundle)]
+///
+/// Each field is a component, except that fields marked `#[bundle]` are themselves bundles whose
+/// components are included, and fields marked `#[bundle(skip)]` are left out entirely. Skipped
+/// fields must implement `Default`, which supplies their value when a bundle is removed from an
+/// entity. Generic structs are supported, and their type parameters must be `'static`. A struct
+/// with lifetime parameters sorts its component IDs on every spawn or insert, where others do so
+/// only once.
+///
+/// ```ignore
+/// #[derive(Bundle)]
+/// struct Unit<T> {
+///     health: Health,
+///     faction: T,
+///     #[bundle]
+///     physics: PhysicsBundle,
+///     #[bundle(skip)]
+///     label: String,
+/// }
+/// ```
+#[proc_macro_derive(Bundle, attributes(bundle))]
 pub fn derive_bundle(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    if !input.generics.params.is_empty() {
-        return TokenStream::from(
-            quote! { compile_error!("derive(Bundle) does not support generics"); },
-        );
+    match bundle::derive(input) {
+        Ok(x) => x.into(),
+        Err(e) => e.to_compile_error().into(),
     }
-    let data = match input.data {
-        syn::Data::Struct(s) => s,
-        _ => {
-            return TokenStream::from(
-                quote! { compile_error!("derive(Bundle) only supports structs"); },
-            )
-        }
-    };
-    let ident = input.ident;
-    let (tys, fields) = struct_fields(&data.fields);
-
-    let n = tys.len();
-    let code = quote! {
-        impl ::hecs::DynamicBundle for #ident {
-            fn with_ids<T>(&self, f: impl FnOnce(&[std::any::TypeId]) -> T) -> T {
-                Self::with_static_ids(f)
-            }
-
-            fn type_info(&self) -> Vec<::hecs::TypeInfo> {
-                Self::static_type_info()
-            }
-
-            unsafe fn put(mut self, mut f: impl FnMut(*mut u8, std::any::TypeId, usize) -> bool) {
-                #(
-                    if f((&mut self.#fields as *mut #tys).cast::<u8>(), std::any::TypeId::of::<#tys>(), std::mem::size_of::<#tys>()) {
-                        std::mem::forget(self.#fields);
-                    }
-                )*
-            }
-        }
-
-        impl ::hecs::Bundle for #ident {
-            fn with_static_ids<T>(f: impl FnOnce(&[std::any::TypeId]) -> T) -> T {
-                use std::any::TypeId;
-                use std::mem;
-
-                ::hecs::lazy_static::lazy_static! {
-                    static ref ELEMENTS: [TypeId; #n] = {
-                        let mut dedup = std::collections::HashSet::new();
-                        for &(ty, name) in [#((std::any::TypeId::of::<#tys>(), std::any::type_name::<#tys>())),*].iter() {
-                            if !dedup.insert(ty) {
-                                panic!("{} has multiple {} fields; each type must occur at most once!", stringify!(#ident), name);
-                            }
-                        }
-
-                        let mut tys = [#((mem::align_of::<#tys>(), TypeId::of::<#tys>())),*];
-                        tys.sort_unstable_by(|x, y| x.0.cmp(&y.0).reverse().then(x.1.cmp(&y.1)));
-                        let mut ids = [TypeId::of::<()>(); #n];
-                        for (id, info) in ids.iter_mut().zip(tys.iter()) {
-                            *id = info.1;
-                        }
-                        ids
-                    };
-                }
-
-                f(&*ELEMENTS)
-            }
-
-            fn static_type_info() -> Vec<::hecs::TypeInfo> {
-                let mut info = vec![#(::hecs::TypeInfo::of::<#tys>()),*];
-                info.sort_unstable();
-                info
-            }
-
-            unsafe fn get(
-                mut f: impl FnMut(std::any::TypeId, usize) -> Option<std::ptr::NonNull<u8>>,
-            ) -> Result<Self, ::hecs::MissingComponent> {
-                #(
-                    let #fields = f(std::any::TypeId::of::<#tys>(), std::mem::size_of::<#tys>())
-                            .ok_or_else(::hecs::MissingComponent::new::<#tys>)?
-                            .cast::<#tys>()
-                        .as_ptr();
-                )*
-                Ok(Self { #( #fields: #fields.read(), )* })
-            }
-        }
-    };
-    TokenStream::from(code)
 }
 
-fn struct_fields(fields: &syn::Fields) -> (Vec<&syn::Type>, Vec<syn::Ident>) {
-    match fields {
-        syn::Fields::Named(ref fields) => fields
-            .named
-            .iter()
-            .map(|f| (&f.ty, f.ident.clone().unwrap()))
-            .unzip(),
-        syn::Fields::Unnamed(ref fields) => fields
-            .unnamed
-            .iter()
-            .enumerate()
-            .map(|(i, f)| (&f.ty, syn::Ident::new(&i.to_string(), Span::call_site())))
-            .unzip(),
-        syn::Fields::Unit => (Vec::new(), Vec::new()),
+/// Implement `Query` for a struct whose fields are queries
+///
+/// The struct may have at most one lifetime parameter, which is the lifetime of the borrows it
+/// holds. Each field must be a query whose results are of the field's type, such as `&'a T`,
+/// `&'a mut T`, `Option<&'a T>`, `Entity`, or another derived query. Entities can additionally be
+/// filtered with `#[query(with(A, B), without(C))]`.
+///
+/// ```ignore
+/// #[derive(Query)]
+/// #[query(without(Frozen))]
+/// struct PhysicsBody<'a> {
+///     position: &'a mut Position,
+///     velocity: &'a Velocity,
+///     mass: Option<&'a Mass>,
+/// }
+///
+/// for (_, body) in world.query_mut::<PhysicsBody>() {
+///     *body.position += *body.velocity;
+/// }
+/// ```
+#[proc_macro_derive(Query, attributes(query))]
+pub fn derive_query(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match query::derive(input) {
+        Ok(x) => x.into(),
+        Err(e) => e.to_compile_error().into(),
     }
 }
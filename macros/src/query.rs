@@ -0,0 +1,202 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::ParseStream;
+use syn::punctuated::Punctuated;
+use syn::visit_mut::VisitMut;
+use syn::{DeriveInput, Error, GenericParam, Ident, Lifetime, Result, Token, Type};
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream> {
+    let ident = input.ident;
+    let vis = input.vis;
+    let fields = match input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => fields.named,
+        _ => {
+            return Err(Error::new_spanned(
+                ident,
+                "derive(Query) only supports structs with named fields",
+            ))
+        }
+    };
+    let mut lifetime = None;
+    for param in &input.generics.params {
+        match *param {
+            GenericParam::Lifetime(ref x) if lifetime.is_none() => {
+                lifetime = Some(x.lifetime.clone());
+            }
+            GenericParam::Lifetime(ref x) => {
+                return Err(Error::new_spanned(
+                    x,
+                    "derive(Query) supports at most one lifetime parameter",
+                ))
+            }
+            _ => {
+                return Err(Error::new_spanned(
+                    param,
+                    "derive(Query) does not support type or const parameters",
+                ))
+            }
+        }
+    }
+    let (with, without) = filters(&input.attrs)?;
+
+    // The fetch of each field is that of its type with the struct's lifetime made `'static`, so
+    // that it doesn't depend on any particular borrow
+    let tys = fields
+        .iter()
+        .map(|x| {
+            let mut ty = x.ty.clone();
+            if let Some(ref lifetime) = lifetime {
+                MakeStatic(lifetime).visit_type_mut(&mut ty);
+            }
+            quote! { <#ty as ::hecs::Query>::Fetch }
+        })
+        .collect::<Vec<_>>();
+    let fields = fields
+        .iter()
+        .map(|x| x.ident.clone().unwrap())
+        .collect::<Vec<_>>();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let item = quote! { #ident #ty_generics };
+    let lifetime = lifetime.unwrap_or_else(|| Lifetime::new("'a", ident.span()));
+    let filters = with
+        .iter()
+        .map(|ty| quote! { <::hecs::With<#ty, ()> as ::hecs::Query>::Fetch })
+        .chain(
+            without
+                .iter()
+                .map(|ty| quote! { <::hecs::Without<#ty, ()> as ::hecs::Query>::Fetch }),
+        )
+        .collect::<Vec<_>>();
+    let fetch = format_ident!("__Hecs{}Fetch", ident);
+
+    Ok(quote! {
+        const _: () = {
+            #[doc(hidden)]
+            #vis struct #fetch {
+                #(#fields: #tys,)*
+                __hecs_filters: (#(#filters,)*),
+            }
+
+            impl<#lifetime> ::hecs::Query for #item {
+                type Fetch = #fetch;
+            }
+
+            impl<#lifetime> ::hecs::Fetch<#lifetime> for #fetch {
+                type Item = #item;
+
+                fn access(archetype: &::hecs::Archetype) -> ::core::option::Option<::hecs::Access> {
+                    #(<#filters as ::hecs::Fetch<#lifetime>>::access(archetype)?;)*
+                    let mut access = ::hecs::Access::Iterate;
+                    #(
+                        access = access.max(<#tys as ::hecs::Fetch<#lifetime>>::access(archetype)?);
+                    )*
+                    ::core::option::Option::Some(access)
+                }
+
+                #[allow(unused_mut, unused_assignments)]
+                fn try_borrow(
+                    archetype: &::hecs::Archetype,
+                ) -> ::core::result::Result<(), ::hecs::BorrowError> {
+                    // On failure, release whatever was borrowed before it
+                    let mut result = ::core::result::Result::Ok(());
+                    let mut borrowed = 0;
+                    #(
+                        if result.is_ok() {
+                            result = <#tys as ::hecs::Fetch<#lifetime>>::try_borrow(archetype);
+                            borrowed += result.is_ok() as usize;
+                        }
+                    )*
+                    if result.is_err() {
+                        #(
+                            if borrowed > 0 {
+                                <#tys as ::hecs::Fetch<#lifetime>>::release(archetype);
+                                borrowed -= 1;
+                            }
+                        )*
+                    }
+                    result
+                }
+                unsafe fn get(
+                    meta: &#lifetime [::hecs::EntityMeta],
+                    archetype: &#lifetime ::hecs::Archetype,
+                    offset: usize,
+                ) -> ::core::option::Option<Self> {
+                    ::core::option::Option::Some(Self {
+                        __hecs_filters: (
+                            #(<#filters as ::hecs::Fetch<#lifetime>>::get(meta, archetype, offset)?,)*
+                        ),
+                        #(
+                            #fields: <#tys as ::hecs::Fetch<#lifetime>>::get(meta, archetype, offset)?,
+                        )*
+                    })
+                }
+                fn release(archetype: &::hecs::Archetype) {
+                    #(<#tys as ::hecs::Fetch<#lifetime>>::release(archetype);)*
+                }
+
+                unsafe fn should_skip(&self, n: usize) -> bool {
+                    ::hecs::Fetch::should_skip(&self.__hecs_filters, n)
+                        #(|| self.#fields.should_skip(n))*
+                }
+
+                fn may_skip(&self) -> bool {
+                    ::hecs::Fetch::may_skip(&self.__hecs_filters)
+                        #(|| self.#fields.may_skip())*
+                }
+
+                unsafe fn is_missing(&self, n: usize) -> bool {
+                    ::hecs::Fetch::is_missing(&self.__hecs_filters, n)
+                        #(|| self.#fields.is_missing(n))*
+                }
+
+                unsafe fn fetch(&self, n: usize) -> Self::Item {
+                    #ident {
+                        #(#fields: self.#fields.fetch(n),)*
+                    }
+                }
+            }
+        };
+    })
+}
+
+/// Parse `#[query(with(A, B), without(C))]` attributes into the types to require and exclude
+fn filters(attrs: &[syn::Attribute]) -> Result<(Vec<Type>, Vec<Type>)> {
+    let mut with = Vec::new();
+    let mut without = Vec::new();
+    for attr in attrs.iter().filter(|x| x.path.is_ident("query")) {
+        attr.parse_args_with(|input: ParseStream| {
+            while !input.is_empty() {
+                let kind = input.parse::<Ident>()?;
+                let list = if kind == "with" {
+                    &mut with
+                } else if kind == "without" {
+                    &mut without
+                } else {
+                    return Err(Error::new_spanned(kind, "expected `with` or `without`"));
+                };
+                let content;
+                syn::parenthesized!(content in input);
+                list.extend(Punctuated::<Type, Token![,]>::parse_terminated(&content)?);
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok((with, without))
+}
+
+/// Replaces a lifetime with `'static`
+struct MakeStatic<'a>(&'a Lifetime);
+
+impl VisitMut for MakeStatic<'_> {
+    fn visit_lifetime_mut(&mut self, x: &mut Lifetime) {
+        if x.ident == self.0.ident {
+            *x = Lifetime::new("'static", x.span());
+        }
+    }
+}
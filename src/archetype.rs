@@ -12,18 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::alloc::alloc::{alloc, dealloc, Layout};
+use crate::alloc::alloc::{alloc, dealloc, GlobalAlloc, Layout};
 use crate::alloc::boxed::Box;
+use crate::alloc::sync::Arc;
 use crate::alloc::{vec, vec::Vec};
 use core::any::{type_name, TypeId};
 use core::cell::UnsafeCell;
 use core::mem;
+use core::ops::Range;
 use core::ptr::{self, NonNull};
 
 use hashbrown::HashMap;
 
-use crate::borrow::AtomicBorrow;
+use crate::borrow::{AtomicBorrow, BorrowError};
+use crate::clone::{CloneFn, Cloners, NotCloneable};
+use crate::dynamic::{Column, ColumnMut};
 use crate::query::Fetch;
+use crate::sparse::{SparseCell, SparseSet};
 use crate::{Access, Component, Query};
 
 /// A collection of entities having the same component types
@@ -32,28 +37,52 @@ use crate::{Access, Component, Query};
 /// go through the `World`.
 pub struct Archetype {
     types: Vec<TypeInfo>,
-    state: HashMap<TypeId, TypeState>,
+    state: HashMap<ComponentId, TypeState>,
     len: u32,
     entities: Box<[u32]>,
     // UnsafeCell allows unique references into `data` to be constructed while shared references
     // containing the `Archetype` exist
-    data: UnsafeCell<NonNull<u8>>,
+    /// Buffers of `data_size` bytes, each storing the components of `chunk_len` entities
+    data: UnsafeCell<Vec<NonNull<u8>>>,
     data_size: usize,
+    chunk_len: u32,
+    /// Whether storage grows by adding buffers, rather than by reallocating the only one
+    chunked: bool,
+    config: StorageConfig,
+    tick: u32,
+    /// Archetypes reached by inserting components with the given sorted type IDs
+    insert_edges: HashMap<Box<[ComponentId]>, u32>,
+    /// Archetypes reached by removing components with the given sorted type IDs
+    remove_edges: HashMap<Box<[ComponentId]>, u32>,
+    /// Sparse component types, which each entity here may or may not have
+    sparse: HashMap<ComponentId, SparseState>,
 }
 
 impl Archetype {
-    pub(crate) fn new(types: Vec<TypeInfo>) -> Self {
+    pub(crate) fn new(types: Vec<TypeInfo>, tick: u32, config: &StorageConfig) -> Self {
         debug_assert!(
             types.windows(2).all(|x| x[0] < x[1]),
             "type info unsorted or contains duplicates"
         );
+        // Without components, there's nothing to divide
+        let chunk_len = match config.chunk_size {
+            Some(size) if !types.is_empty() => chunk_len(&types, size, config.column_align),
+            _ => 0,
+        };
         Self {
             types,
             state: HashMap::default(),
             entities: Box::new([]),
             len: 0,
-            data: UnsafeCell::new(NonNull::dangling()),
+            data: UnsafeCell::new(Vec::new()),
             data_size: 0,
+            chunk_len,
+            chunked: chunk_len != 0,
+            config: config.clone(),
+            tick,
+            insert_edges: HashMap::default(),
+            remove_edges: HashMap::default(),
+            sparse: HashMap::default(),
         }
     }
 
@@ -73,58 +102,298 @@ impl Archetype {
     }
 
     pub(crate) fn has<T: Component>(&self) -> bool {
-        self.has_dynamic(TypeId::of::<T>())
+        self.has_dynamic(ComponentId::of::<T>())
     }
 
-    pub(crate) fn has_dynamic(&self, id: TypeId) -> bool {
-        self.state.contains_key(&id)
+    pub(crate) fn has_dynamic(&self, id: ComponentId) -> bool {
+        // Columns are only laid out in `state` while storage is allocated
+        self.types.iter().any(|x| x.id == id)
     }
 
-    pub(crate) fn get<T: Component>(&self) -> Option<NonNull<T>> {
-        let state = self.state.get(&TypeId::of::<T>())?;
+    /// Whether `T` is a sparse component type, which entities here may have
+    pub(crate) fn has_sparse<T: Component>(&self) -> bool {
+        self.sparse.contains_key(&ComponentId::of::<T>())
+    }
+
+    pub(crate) fn get_sparse<T: Component>(&self) -> Option<&SparseSet> {
+        Some(self.sparse.get(&ComponentId::of::<T>())?.set.get())
+    }
+
+    /// Every sparse component type registered with the world
+    pub(crate) fn sparse_sets(&self) -> impl Iterator<Item = &SparseSet> + '_ {
+        self.sparse.values().map(|x| x.set.get())
+    }
+
+    pub(crate) fn add_sparse(&mut self, id: ComponentId, set: Arc<SparseCell>) {
+        self.sparse.insert(
+            id,
+            SparseState {
+                set,
+                borrow: AtomicBorrow::new(),
+            },
+        );
+    }
+
+    /// Pointer to the `T` of the entity at `index`, followed by those of the rest of its chunk
+    pub(crate) fn get<T: Component>(&self, index: u32) -> Option<NonNull<T>> {
+        let state = self.state.get(&ComponentId::of::<T>())?;
         Some(unsafe {
-            NonNull::new_unchecked(
-                (*self.data.get()).as_ptr().add(state.offset).cast::<T>() as *mut T
-            )
+            self.entry(state.offset, mem::size_of::<T>(), index)
+                .cast::<T>()
         })
     }
 
-    pub(crate) fn borrow<T: Component>(&self) {
-        if self
-            .state
-            .get(&TypeId::of::<T>())
-            .map_or(false, |x| !x.borrow.borrow())
-        {
-            panic!("{} already borrowed uniquely", type_name::<T>());
+    /// Like `get`, for the ticks at which each `T` was added
+    pub(crate) fn get_added<T: Component>(&self, index: u32) -> Option<NonNull<u32>> {
+        let state = self.state.get(&ComponentId::of::<T>())?;
+        Some(unsafe { self.tick_ptr(state.added, index) })
+    }
+
+    /// Like `get`, for the ticks at which each `T` was last uniquely borrowed or written
+    pub(crate) fn get_mutated<T: Component>(&self, index: u32) -> Option<NonNull<u32>> {
+        let state = self.state.get(&ComponentId::of::<T>())?;
+        Some(unsafe { self.tick_ptr(state.mutated, index) })
+    }
+
+    unsafe fn tick_ptr(&self, offset: usize, index: u32) -> NonNull<u32> {
+        self.entry(offset, mem::size_of::<u32>(), index)
+            .cast::<u32>()
+    }
+
+    /// Address of element `index` of the array of `size`-byte elements found `offset` bytes into
+    /// each chunk
+    ///
+    /// `index` must be within capacity.
+    unsafe fn entry(&self, offset: usize, size: usize, index: u32) -> NonNull<u8> {
+        debug_assert!(index < self.capacity());
+        let chunk = self
+            .chunks()
+            .get_unchecked((index / self.chunk_len) as usize);
+        let row = (index % self.chunk_len) as usize;
+        NonNull::new_unchecked(chunk.as_ptr().add(offset + size * row))
+    }
+
+    /// Index just past the last entity stored in the same chunk as the entity at `index`
+    pub(crate) fn chunk_end(&self, index: u32) -> u32 {
+        match index.checked_rem(self.chunk_len) {
+            Some(row) => (index - row).saturating_add(self.chunk_len).min(self.len),
+            None => self.len,
         }
     }
 
+    /// Index of the first entity of each chunk storing any
+    pub(crate) fn chunk_starts(&self) -> impl Iterator<Item = u32> {
+        (0..self.len).step_by(self.chunk_len.max(1) as usize)
+    }
+
+    /// Number of entities each chunk has room for
+    pub(crate) fn chunk_len(&self) -> u32 {
+        self.chunk_len
+    }
+
+    /// The current change tracking tick, see `World::clear_trackers`
+    pub(crate) fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub(crate) fn set_tick(&mut self, tick: u32) {
+        self.tick = tick;
+    }
+
+    pub(crate) fn borrow<T: Component>(&self) {
+        self.borrow_dynamic(ComponentId::of::<T>(), type_name::<T>());
+    }
+
     pub(crate) fn borrow_mut<T: Component>(&self) {
-        if self
-            .state
-            .get(&TypeId::of::<T>())
-            .map_or(false, |x| !x.borrow.borrow_mut())
-        {
-            panic!("{} already borrowed", type_name::<T>());
-        }
+        self.borrow_mut_dynamic(ComponentId::of::<T>(), type_name::<T>());
     }
 
     pub(crate) fn release<T: Component>(&self) {
-        if let Some(x) = self.state.get(&TypeId::of::<T>()) {
-            x.borrow.release();
-        }
+        self.release_dynamic(ComponentId::of::<T>());
     }
 
     pub(crate) fn release_mut<T: Component>(&self) {
-        if let Some(x) = self.state.get(&TypeId::of::<T>()) {
-            x.borrow.release_mut();
+        self.release_mut_dynamic(ComponentId::of::<T>());
+    }
+
+    pub(crate) fn try_borrow<T: Component>(&self) -> Result<(), BorrowError> {
+        self.try_borrow_dynamic(ComponentId::of::<T>(), type_name::<T>())
+    }
+
+    pub(crate) fn try_borrow_mut<T: Component>(&self) -> Result<(), BorrowError> {
+        self.try_borrow_mut_dynamic(ComponentId::of::<T>(), type_name::<T>())
+    }
+
+    pub(crate) fn borrow_dynamic(&self, id: ComponentId, name: &'static str) {
+        if let Err(e) = self.try_borrow_dynamic(id, name) {
+            panic!("{}", e);
+        }
+    }
+
+    pub(crate) fn borrow_mut_dynamic(&self, id: ComponentId, name: &'static str) {
+        if let Err(e) = self.try_borrow_mut_dynamic(id, name) {
+            panic!("{}", e);
+        }
+    }
+
+    pub(crate) fn try_borrow_dynamic(
+        &self,
+        id: ComponentId,
+        name: &'static str,
+    ) -> Result<(), BorrowError> {
+        match self.borrow_state(id) {
+            Some(x) => x.borrow(name),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn try_borrow_mut_dynamic(
+        &self,
+        id: ComponentId,
+        name: &'static str,
+    ) -> Result<(), BorrowError> {
+        match self.borrow_state(id) {
+            Some(x) => x.borrow_mut(name),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn release_dynamic(&self, id: ComponentId) {
+        if let Some(x) = self.borrow_state(id) {
+            x.release();
+        }
+    }
+
+    pub(crate) fn release_mut_dynamic(&self, id: ComponentId) {
+        if let Some(x) = self.borrow_state(id) {
+            x.release_mut();
+        }
+    }
+
+    /// Borrow state of the components of type `id` belonging to entities in this archetype
+    fn borrow_state(&self, id: ComponentId) -> Option<&AtomicBorrow> {
+        match self.state.get(&id) {
+            Some(x) => Some(&x.borrow),
+            None => self.sparse.get(&id).map(|x| &x.borrow),
+        }
+    }
+
+    /// Borrow the column of the runtime-defined component type `id` as bytes
+    ///
+    /// Returns `None` if this archetype has no such component, or if `id` identifies a Rust type;
+    /// use queries to access those. Panics if the column is already borrowed uniquely, or if it's
+    /// split over several chunks of storage; see `World::with_chunk_size`. `World::query_dynamic`
+    /// visits each chunk separately.
+    pub fn get_column(&self, id: ComponentId) -> Option<Column<'_>> {
+        self.assert_contiguous();
+        self.get_column_range(id, 0..self.len)
+    }
+
+    /// Uniquely borrow the column of the runtime-defined component type `id` as bytes
+    ///
+    /// Every component in the column is marked mutated. Returns `None` if this archetype has no
+    /// such component, or if `id` identifies a Rust type. Panics if the column is already borrowed,
+    /// or if it's split over several chunks of storage, as with `get_column`.
+    pub fn get_column_mut(&self, id: ComponentId) -> Option<ColumnMut<'_>> {
+        self.assert_contiguous();
+        self.get_column_range_mut(id, 0..self.len)
+    }
+
+    fn assert_contiguous(&self) {
+        assert!(
+            self.chunk_end(0) == self.len,
+            "archetype storage is split into chunks; use World::query_dynamic to access them"
+        );
+    }
+
+    /// Like `get_column`, for the entities in `range`, which must lie within one chunk
+    pub(crate) fn get_column_range(
+        &self,
+        id: ComponentId,
+        range: Range<u32>,
+    ) -> Option<Column<'_>> {
+        let (ty, data) = self.column(id, range.start)?;
+        self.borrow_dynamic(id, ty.name());
+        let len = ty.layout().size() * (range.end - range.start) as usize;
+        Some(unsafe { Column::new(self, id, data, len) })
+    }
+
+    /// Like `get_column_mut`, for the entities in `range`, which must lie within one chunk
+    pub(crate) fn get_column_range_mut(
+        &self,
+        id: ComponentId,
+        range: Range<u32>,
+    ) -> Option<ColumnMut<'_>> {
+        let (ty, data) = self.column(id, range.start)?;
+        self.borrow_mut_dynamic(id, ty.name());
+        let len = ty.layout().size() * (range.end - range.start) as usize;
+        unsafe {
+            if let Some(state) = self.state.get(&id) {
+                for index in range {
+                    *self.tick_ptr(state.mutated, index).as_ptr() = self.tick;
+                }
+            }
+            Some(ColumnMut::new(self, id, data, len))
+        }
+    }
+
+    fn column(&self, id: ComponentId, index: u32) -> Option<(&TypeInfo, NonNull<u8>)> {
+        if !id.is_dynamic() {
+            return None;
+        }
+        let ty = self.types.iter().find(|x| x.id == id)?;
+        // Storage is only laid out once the archetype first grows
+        let data = self
+            .state
+            .get(&id)
+            .map_or(NonNull::dangling(), |state| unsafe {
+                self.entry(state.offset, ty.layout.size(), index)
+            });
+        Some((ty, data))
+    }
+
+    pub(crate) fn insert_edge(&self, ids: &[ComponentId]) -> Option<u32> {
+        self.insert_edges.get(ids).copied()
+    }
+
+    pub(crate) fn set_insert_edge(&mut self, ids: &[ComponentId], target: u32) {
+        self.insert_edges.insert(ids.into(), target);
+    }
+
+    pub(crate) fn remove_edge(&self, ids: &[ComponentId]) -> Option<u32> {
+        self.remove_edges.get(ids).copied()
+    }
+
+    pub(crate) fn set_remove_edge(&mut self, ids: &[ComponentId], target: u32) {
+        self.remove_edges.insert(ids.into(), target);
+    }
+
+    /// Replace the index of each archetype reachable from this one with `remap[index]`, forgetting
+    /// those mapped to `u32::MAX`
+    pub(crate) fn remap_edges(&mut self, remap: &[u32]) {
+        for edges in &mut [&mut self.insert_edges, &mut self.remove_edges] {
+            edges.retain(|_, target| {
+                *target = remap[*target as usize];
+                *target != u32::MAX
+            });
         }
     }
 
-    pub(crate) fn len(&self) -> u32 {
+    /// Number of entities in this archetype
+    pub fn len(&self) -> u32 {
         self.len
     }
 
+    /// Whether this archetype contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// IDs of the entities stored here, in storage order
+    pub(crate) fn ids(&self) -> &[u32] {
+        &self.entities[..self.len as usize]
+    }
+
     pub(crate) fn entities(&self) -> NonNull<u32> {
         unsafe { NonNull::new_unchecked(self.entities.as_ptr() as *mut _) }
     }
@@ -140,81 +409,223 @@ impl Archetype {
     /// `index` must be in-bounds
     pub(crate) unsafe fn get_dynamic(
         &self,
-        ty: TypeId,
+        ty: ComponentId,
         size: usize,
         index: u32,
     ) -> Option<NonNull<u8>> {
         debug_assert!(index < self.len);
-        Some(NonNull::new_unchecked(
-            (*self.data.get())
-                .as_ptr()
-                .add(self.state.get(&ty)?.offset + size * index as usize)
-                .cast::<u8>(),
-        ))
+        Some(self.entry(self.state.get(&ty)?.offset, size, index))
     }
 
-    /// Every type must be written immediately after this call
-    pub(crate) unsafe fn allocate(&mut self, id: u32) -> u32 {
-        if self.len as usize == self.entities.len() {
-            self.grow(self.len.max(64));
+    /// Locate the component of type `ty` and size `size` belonging to the entity `id` at `index`,
+    /// whether stored here or in a sparse set, and the tick at which it was last mutated
+    pub(crate) unsafe fn locate(
+        &self,
+        ty: ComponentId,
+        size: usize,
+        id: u32,
+        index: u32,
+    ) -> Option<(NonNull<u8>, NonNull<u32>)> {
+        match self.state.get(&ty) {
+            Some(state) => Some((
+                self.get_dynamic(ty, size, index)?,
+                self.tick_ptr(state.mutated, index),
+            )),
+            None => self.sparse.get(&ty)?.set.get().get(id),
         }
+    }
 
+    /// Every type must be written immediately after this call
+    pub(crate) unsafe fn allocate(&mut self, id: u32) -> u32 {
+        self.reserve_one();
         self.entities[self.len as usize] = id;
         self.len += 1;
         self.len - 1
     }
 
+    /// Make room for at least one more entity
+    fn reserve_one(&mut self) {
+        if self.len == self.capacity() {
+            // Chunks are never copied, so there's no need to add more than one at a time
+            let increment = if self.chunked { 1 } else { self.len.max(64) };
+            self.grow(self.len + increment);
+        }
+    }
+
+    /// Allocate consecutive entries for `ids`, returning the index of the first
+    ///
+    /// Every type must be written immediately after this call, e.g. with `put_column`.
+    pub(crate) unsafe fn allocate_many(&mut self, ids: Range<u32>) -> u32 {
+        let count = ids.end - ids.start;
+        self.reserve(count);
+        let first = self.len;
+        for (slot, id) in self.entities[first as usize..].iter_mut().zip(ids) {
+            *slot = id;
+        }
+        self.len += count;
+        first
+    }
+
     pub(crate) fn reserve(&mut self, additional: u32) {
         if additional > (self.capacity() - self.len()) {
-            self.grow(additional - (self.capacity() - self.len()));
+            self.grow(self.len + additional);
         }
     }
 
     pub(crate) fn capacity(&self) -> u32 {
-        self.entities.len() as u32
+        self.chunk_len * self.chunks().len() as u32
     }
 
-    fn grow(&mut self, increment: u32) {
+    /// Release storage not needed by the entities currently stored
+    pub(crate) fn shrink_to_fit(&mut self) {
         unsafe {
+            if self.len == 0 {
+                // Return to the state of a new archetype, whose columns aren't laid out until
+                // storage is first allocated
+                for chunk in mem::take(self.data.get_mut()) {
+                    self.dealloc_data(chunk);
+                }
+                self.state.clear();
+                self.data_size = 0;
+                if !self.chunked {
+                    self.chunk_len = 0;
+                }
+            } else if self.chunked {
+                // Entities are never spread over more chunks than necessary
+                let needed = self.len.div_ceil(self.chunk_len);
+                while self.chunks().len() > needed as usize {
+                    let chunk = self.data.get_mut().pop().unwrap();
+                    self.dealloc_data(chunk);
+                }
+            } else if self.capacity() != self.len {
+                self.reallocate(self.len);
+            }
+        }
+        self.data.get_mut().shrink_to_fit();
+        let capacity = self.capacity() as usize;
+        if self.entities.len() > capacity {
+            self.entities = self.entities[..capacity].into();
+        }
+    }
+
+    fn chunks(&self) -> &[NonNull<u8>] {
+        unsafe { &*self.data.get() }
+    }
+
+    /// Make room for at least `capacity` entities
+    fn grow(&mut self, capacity: u32) {
+        unsafe {
+            if self.chunked {
+                if self.chunks().is_empty() {
+                    let (state, size) = layout(
+                        &self.types,
+                        self.chunk_len as usize,
+                        self.config.column_align,
+                    );
+                    self.state = state;
+                    self.data_size = size;
+                }
+                while self.capacity() < capacity {
+                    let chunk = self.alloc_data(self.data_size);
+                    self.data.get_mut().push(chunk);
+                }
+            } else {
+                self.reallocate(capacity);
+            }
+        }
+
+        // IDs stay in one buffer regardless, grown geometrically so that adding chunks one at a
+        // time doesn't copy them each time
+        if self.capacity() as usize > self.entities.len() {
             let old_count = self.len as usize;
-            let count = old_count + increment as usize;
+            let count = (self.capacity() as usize).max(2 * self.entities.len());
             let mut new_entities = vec![!0; count].into_boxed_slice();
             new_entities[0..old_count].copy_from_slice(&self.entities[0..old_count]);
             self.entities = new_entities;
+        }
+    }
 
-            let old_data_size = mem::replace(&mut self.data_size, 0);
-            let mut state = HashMap::with_capacity(self.types.len());
-            for ty in &self.types {
-                self.data_size = align(self.data_size, ty.layout.align());
-                state.insert(ty.id, TypeState::new(self.data_size));
-                self.data_size += ty.layout.size() * count;
-            }
-            let new_data = if self.data_size == 0 {
-                NonNull::dangling()
-            } else {
-                NonNull::new(alloc(
-                    Layout::from_size_align(
-                        self.data_size,
-                        self.types.first().map_or(1, |x| x.layout.align()),
-                    )
-                    .unwrap(),
-                ))
-                .unwrap()
-            };
-            if old_data_size != 0 {
+    /// Move every component into a single new buffer with room for `capacity` entities
+    unsafe fn reallocate(&mut self, capacity: u32) {
+        let old_count = self.len as usize;
+        let (state, data_size) = layout(&self.types, capacity as usize, self.config.column_align);
+        let new_data = self.alloc_data(data_size);
+        if let Some(old_data) = self.data.get_mut().pop() {
+            if self.data_size != 0 {
                 for ty in &self.types {
-                    let old_off = self.state.get(&ty.id).unwrap().offset;
-                    let new_off = state.get(&ty.id).unwrap().offset;
+                    let old = self.state.get(&ty.id).unwrap();
+                    let new = state.get(&ty.id).unwrap();
                     ptr::copy_nonoverlapping(
-                        (*self.data.get()).as_ptr().add(old_off),
-                        new_data.as_ptr().add(new_off),
+                        old_data.as_ptr().add(old.offset),
+                        new_data.as_ptr().add(new.offset),
                         ty.layout.size() * old_count,
                     );
+                    for &(old_off, new_off) in &[(old.added, new.added), (old.mutated, new.mutated)]
+                    {
+                        ptr::copy_nonoverlapping(
+                            old_data.as_ptr().add(old_off),
+                            new_data.as_ptr().add(new_off),
+                            mem::size_of::<u32>() * old_count,
+                        );
+                    }
                 }
             }
+            self.dealloc_data(old_data);
+        }
+
+        self.data.get_mut().push(new_data);
+        self.data_size = data_size;
+        self.state = state;
+        self.chunk_len = capacity;
+    }
+
+    /// Alignment of `data`, suitable for every column and change tick
+    fn data_align(&self) -> usize {
+        self.types
+            .iter()
+            .map(|x| x.layout.align())
+            .fold(self.config.column_align, usize::max)
+            .max(mem::align_of::<u32>())
+    }
+
+    /// Allocate a buffer of `size` bytes from the configured allocator
+    unsafe fn alloc_data(&self, size: usize) -> NonNull<u8> {
+        if size == 0 {
+            // Never dereferenced, as there's nothing to store
+            return NonNull::dangling();
+        }
+        let layout = Layout::from_size_align(size, self.data_align()).unwrap();
+        NonNull::new(self.config.allocator.alloc(layout)).unwrap()
+    }
+
+    /// Free a buffer obtained from `alloc_data` with the current `data_size`
+    unsafe fn dealloc_data(&self, data: NonNull<u8>) {
+        if self.data_size != 0 {
+            self.config.allocator.dealloc(
+                data.as_ptr(),
+                Layout::from_size_align_unchecked(self.data_size, self.data_align()),
+            );
+        }
+    }
+
+    /// Copy the change ticks of `ty` at index `from` to index `to`
+    unsafe fn copy_ticks(&self, ty: ComponentId, from: u32, to: u32) {
+        let state = self.state.get(&ty).unwrap();
+        for &offset in &[state.added, state.mutated] {
+            *self.tick_ptr(offset, to).as_ptr() = *self.tick_ptr(offset, from).as_ptr();
+        }
+    }
 
-            self.data = UnsafeCell::new(new_data);
-            self.state = state;
+    /// Copy the change ticks of the components `source`'s entity at `source_index` shares with
+    /// this archetype to `index`
+    pub(crate) unsafe fn copy_ticks_from(&self, source: &Archetype, source_index: u32, index: u32) {
+        for (id, target) in &self.state {
+            if let Some(state) = source.state.get(id) {
+                for &(from, to) in &[(state.added, target.added), (state.mutated, target.mutated)] {
+                    *self.tick_ptr(to, index).as_ptr() =
+                        *source.tick_ptr(from, source_index).as_ptr();
+                }
+            }
         }
     }
 
@@ -235,6 +646,7 @@ impl Archetype {
                     removed,
                     ty.layout.size(),
                 );
+                self.copy_ticks(ty.id, last, index);
             }
         }
         self.len = last;
@@ -246,11 +658,51 @@ impl Archetype {
         }
     }
 
+    /// Drop the entities at each index flagged in `remove`, compacting the rest in order
+    ///
+    /// `moved` is invoked with the ID and new index of each entity that moved.
+    pub(crate) unsafe fn remove_many(&mut self, remove: &[bool], mut moved: impl FnMut(u32, u32)) {
+        debug_assert_eq!(remove.len(), self.len as usize);
+        for ty in &self.types {
+            let size = ty.layout.size();
+            let mut target = 0;
+            for (index, &remove) in (0..).zip(remove) {
+                let ptr = self.get_dynamic(ty.id, size, index).unwrap().as_ptr();
+                if remove {
+                    (ty.drop)(ptr);
+                    continue;
+                }
+                if target != index {
+                    ptr::copy_nonoverlapping(
+                        ptr,
+                        self.get_dynamic(ty.id, size, target).unwrap().as_ptr(),
+                        size,
+                    );
+                    self.copy_ticks(ty.id, index, target);
+                }
+                target += 1;
+            }
+        }
+        let mut target = 0;
+        for (index, &remove) in (0..).zip(remove) {
+            if remove {
+                continue;
+            }
+            if target != index {
+                let id = self.entities[index as usize];
+                self.entities[target as usize] = id;
+                moved(id, target);
+            }
+            target += 1;
+        }
+        self.len = target;
+    }
+
     /// Returns the ID of the entity moved into `index`, if any
     pub(crate) unsafe fn move_to(
         &mut self,
         index: u32,
-        mut f: impl FnMut(*mut u8, TypeId, usize),
+        mut f: impl FnMut(*mut u8, ComponentId, usize),
     ) -> Option<u32> {
         let last = self.len - 1;
         for ty in &self.types {
@@ -267,6 +719,7 @@ impl Archetype {
                     moved,
                     ty.layout.size(),
                 );
+                self.copy_ticks(ty.id, last, index);
             }
         }
         self.len -= 1;
@@ -278,10 +731,11 @@ impl Archetype {
         }
     }
 
+    /// Move a newly added component into `index`, marking it added and mutated
     pub(crate) unsafe fn put_dynamic(
         &mut self,
         component: *mut u8,
-        ty: TypeId,
+        ty: ComponentId,
         size: usize,
         index: u32,
     ) {
@@ -291,6 +745,137 @@ impl Archetype {
             .as_ptr()
             .cast::<u8>();
         ptr::copy_nonoverlapping(component, ptr, size);
+        let state = self.state.get(&ty).unwrap();
+        *self.tick_ptr(state.added, index).as_ptr() = self.tick;
+        *self.tick_ptr(state.mutated, index).as_ptr() = self.tick;
+    }
+
+    /// Move `count` components of type `ty` into consecutive entries starting at `index`, marking
+    /// them added and mutated
+    pub(crate) unsafe fn put_column(
+        &mut self,
+        ty: &TypeInfo,
+        components: *const u8,
+        index: u32,
+        count: u32,
+    ) {
+        let size = ty.layout.size();
+        let state = self.state.get(&ty.id).unwrap();
+        let end = index + count;
+        let mut start = index;
+        // The entries may span several chunks
+        while start < end {
+            let run = self.chunk_end(start).min(end) - start;
+            ptr::copy_nonoverlapping(
+                components.add(size * (start - index) as usize),
+                self.entry(state.offset, size, start).as_ptr(),
+                size * run as usize,
+            );
+            for &offset in &[state.added, state.mutated] {
+                let ticks = self.tick_ptr(offset, start).as_ptr();
+                for i in 0..run as usize {
+                    *ticks.add(i) = self.tick;
+                }
+            }
+            start += run;
+        }
+    }
+
+    /// Move a component over the already-dropped one at `index`, marking it mutated
+    pub(crate) unsafe fn replace_dynamic(
+        &mut self,
+        component: *mut u8,
+        ty: ComponentId,
+        size: usize,
+        index: u32,
+    ) {
+        let ptr = self
+            .get_dynamic(ty, size, index)
+            .unwrap()
+            .as_ptr()
+            .cast::<u8>();
+        ptr::copy_nonoverlapping(component, ptr, size);
+        let state = self.state.get(&ty).unwrap();
+        *self.tick_ptr(state.mutated, index).as_ptr() = self.tick;
+    }
+
+    /// Clone the entity at `index` into the entry after the last, marking its components added,
+    /// and return that entry's index
+    ///
+    /// The entry only becomes part of the archetype once `push_cloned` is called with its entity's
+    /// ID. If a clone panics, the components already cloned are dropped, leaving the archetype
+    /// unchanged. `fns` must be the clone functions of `types`, in order.
+    pub(crate) unsafe fn clone_row(&mut self, fns: &[CloneFn], index: u32) -> u32 {
+        self.reserve_one();
+        let target = self.len;
+        let this = &*self;
+        let mut guard = RowGuard {
+            archetype: this,
+            index: target,
+            count: 0,
+        };
+        for (ty, clone) in this.types.iter().zip(fns) {
+            let state = this.state.get(&ty.id).unwrap();
+            let size = ty.layout.size();
+            clone(
+                this.entry(state.offset, size, index).as_ptr(),
+                this.entry(state.offset, size, target).as_ptr(),
+                1,
+            );
+            guard.count += 1;
+            *this.tick_ptr(state.added, target).as_ptr() = this.tick;
+            *this.tick_ptr(state.mutated, target).as_ptr() = this.tick;
+        }
+        mem::forget(guard);
+        target
+    }
+
+    /// Add the entry filled in by `clone_row` for the entity `id`
+    pub(crate) unsafe fn push_cloned(&mut self, id: u32) {
+        debug_assert!(self.len < self.capacity());
+        self.entities[self.len as usize] = id;
+        self.len += 1;
+    }
+
+    /// Clone every entity, column by column, preserving change ticks
+    ///
+    /// Sparse component types are not carried over, as their storage belongs to the world.
+    pub(crate) fn try_clone(&self, cloners: &Cloners) -> Result<Self, NotCloneable> {
+        let fns = cloners.get_all(&self.types)?;
+        let mut new = Self::new(self.types.clone(), self.tick, &self.config);
+        new.insert_edges = self.insert_edges.clone();
+        new.remove_edges = self.remove_edges.clone();
+        if self.len == 0 {
+            return Ok(new);
+        }
+        new.reserve(self.len);
+        let len = self.len as usize;
+        unsafe {
+            for (ty, clone) in self.types.iter().zip(fns) {
+                let old = self.state.get(&ty.id).unwrap();
+                let state = new.state.get(&ty.id).unwrap();
+                let size = ty.layout.size();
+                // Chunks of the copy are at least as large as the original's
+                for start in self.chunk_starts() {
+                    let count = (self.chunk_end(start) - start) as usize;
+                    clone(
+                        self.entry(old.offset, size, start).as_ptr(),
+                        new.entry(state.offset, size, start).as_ptr(),
+                        count,
+                    );
+                    for &(from, to) in &[(old.added, state.added), (old.mutated, state.mutated)] {
+                        ptr::copy_nonoverlapping(
+                            self.tick_ptr(from, start).as_ptr(),
+                            new.tick_ptr(to, start).as_ptr(),
+                            count,
+                        );
+                    }
+                }
+            }
+        }
+        new.entities[..len].copy_from_slice(&self.entities[..len]);
+        new.len = self.len;
+        Ok(new)
     }
 
     /// How, if at all, `Q` will access entities in this archetype
@@ -302,40 +887,141 @@ impl Archetype {
 impl Drop for Archetype {
     fn drop(&mut self) {
         self.clear();
-        if self.data_size != 0 {
+        for &chunk in self.chunks() {
             unsafe {
-                dealloc(
-                    (*self.data.get()).as_ptr().cast(),
-                    Layout::from_size_align_unchecked(
-                        self.data_size,
-                        self.types.first().map_or(1, |x| x.layout.align()),
-                    ),
+                self.dealloc_data(chunk);
+            }
+        }
+    }
+}
+
+/// Drops the first `count` components of the entry at `index` of `archetype` unless forgotten,
+/// undoing a partial `Archetype::clone_row`
+struct RowGuard<'a> {
+    archetype: &'a Archetype,
+    index: u32,
+    count: usize,
+}
+
+impl Drop for RowGuard<'_> {
+    fn drop(&mut self) {
+        for ty in &self.archetype.types[..self.count] {
+            let state = self.archetype.state.get(&ty.id).unwrap();
+            unsafe {
+                ty.drop(
+                    self.archetype
+                        .entry(state.offset, ty.layout.size(), self.index)
+                        .as_ptr(),
                 );
             }
         }
     }
 }
 
+/// How archetypes lay out and allocate their storage, fixed when the `World` is created
+#[derive(Clone)]
+pub(crate) struct StorageConfig {
+    /// Size of the chunks storage is divided into, if any
+    pub(crate) chunk_size: Option<usize>,
+    /// Minimum alignment of the start of each column
+    pub(crate) column_align: usize,
+    pub(crate) allocator: Arc<dyn GlobalAlloc + Send + Sync>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: None,
+            column_align: 1,
+            allocator: Arc::new(Global),
+        }
+    }
+}
+
+/// The global allocator, as used by `alloc::alloc::alloc`
+struct Global;
+
+unsafe impl GlobalAlloc for Global {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        dealloc(ptr, layout)
+    }
+}
+
 struct TypeState {
     offset: usize,
+    added: usize,
+    mutated: usize,
+    borrow: AtomicBorrow,
+}
+
+/// A sparse component type, as seen from one archetype
+struct SparseState {
+    set: Arc<SparseCell>,
+    /// Borrow state of the components of entities in this archetype, independent of that of other
+    /// archetypes as with components stored in archetypes
     borrow: AtomicBorrow,
 }
 
 impl TypeState {
-    fn new(offset: usize) -> Self {
+    fn new(offset: usize, added: usize, mutated: usize) -> Self {
         Self {
             offset,
+            added,
+            mutated,
             borrow: AtomicBorrow::new(),
         }
     }
 }
 
+/// Identifier of a component type
+///
+/// Either the `TypeId` of a Rust type, or a number chosen for a type defined at runtime. The two
+/// never collide.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct ComponentId(Id);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+enum Id {
+    Static(TypeId),
+    Dynamic(u64),
+}
+
+impl ComponentId {
+    /// Identifier of the Rust type `T`
+    pub fn of<T: 'static>() -> Self {
+        Self(Id::Static(TypeId::of::<T>()))
+    }
+
+    /// Identifier of the runtime-defined type numbered `id`
+    ///
+    /// See `TypeInfo::dynamic`.
+    pub const fn dynamic(id: u64) -> Self {
+        Self(Id::Dynamic(id))
+    }
+
+    /// Whether this identifies a runtime-defined type
+    pub fn is_dynamic(self) -> bool {
+        matches!(self.0, Id::Dynamic(_))
+    }
+}
+
+impl From<TypeId> for ComponentId {
+    fn from(x: TypeId) -> Self {
+        Self(Id::Static(x))
+    }
+}
+
 /// Metadata required to store a component
 #[derive(Debug, Copy, Clone)]
 pub struct TypeInfo {
-    id: TypeId,
+    id: ComponentId,
     layout: Layout,
     drop: unsafe fn(*mut u8),
+    name: &'static str,
 }
 
 impl TypeInfo {
@@ -346,23 +1032,55 @@ impl TypeInfo {
         }
 
         Self {
-            id: TypeId::of::<T>(),
+            id: ComponentId::of::<T>(),
             layout: Layout::new::<T>(),
             drop: drop_ptr::<T>,
+            name: type_name::<T>(),
+        }
+    }
+
+    /// Metadata for a component type defined at runtime
+    ///
+    /// `drop` is invoked on each component of this type as it's destroyed, and `name` is used in
+    /// error messages.
+    ///
+    /// # Safety
+    ///
+    /// Every `TypeInfo` with the same `id` must have the same `layout` and `drop`. Components of
+    /// runtime-defined types are read and written as bytes, so `drop` must be sound to call on any
+    /// `layout.size()` initialized bytes stored for this type.
+    pub unsafe fn dynamic(
+        id: u64,
+        layout: Layout,
+        drop: unsafe fn(*mut u8),
+        name: &'static str,
+    ) -> Self {
+        Self {
+            id: ComponentId::dynamic(id),
+            layout,
+            drop,
+            name,
         }
     }
 
-    pub(crate) fn id(&self) -> TypeId {
+    /// Identifier of the described type
+    pub fn id(&self) -> ComponentId {
         self.id
     }
 
-    pub(crate) fn layout(&self) -> Layout {
+    /// Size and alignment of the described type
+    pub fn layout(&self) -> Layout {
         self.layout
     }
 
     pub(crate) unsafe fn drop(&self, data: *mut u8) {
         (self.drop)(data)
     }
+
+    /// Name of the described type, for diagnostics
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
 }
 
 impl PartialOrd for TypeInfo {
@@ -372,7 +1090,7 @@ impl PartialOrd for TypeInfo {
 }
 
 impl Ord for TypeInfo {
-    /// Order by alignment, descending. Ties broken with TypeId.
+    /// Order by alignment, descending. Ties broken with ComponentId.
     fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.layout
             .align()
@@ -390,7 +1108,46 @@ impl PartialEq for TypeInfo {
 
 impl Eq for TypeInfo {}
 
-fn align(x: usize, alignment: usize) -> usize {
+/// Offsets of the columns and change ticks of `types` in a buffer storing `len` entities, each
+/// column starting at a multiple of `column_align` bytes, and the size of that buffer
+fn layout(
+    types: &[TypeInfo],
+    len: usize,
+    column_align: usize,
+) -> (HashMap<ComponentId, TypeState>, usize) {
+    let mut size = 0;
+    let mut state = HashMap::with_capacity(types.len());
+    for ty in types {
+        size = align(size, ty.layout.align().max(column_align));
+        let offset = size;
+        size += ty.layout.size() * len;
+        // Change ticks follow each component's column
+        size = align(size, mem::align_of::<u32>());
+        let added = size;
+        size += mem::size_of::<u32>() * len;
+        let mutated = size;
+        size += mem::size_of::<u32>() * len;
+        state.insert(ty.id, TypeState::new(offset, added, mutated));
+    }
+    (state, size)
+}
+
+/// Number of entities having `types` whose components fit in a chunk of `size` bytes, or 1 if
+/// none do
+fn chunk_len(types: &[TypeInfo], size: usize, column_align: usize) -> u32 {
+    let row = types
+        .iter()
+        .map(|x| x.layout.size() + 2 * mem::size_of::<u32>())
+        .sum::<usize>();
+    // Padding between columns may make this an overestimate
+    let mut len = (size / row).clamp(1, u32::MAX as usize);
+    while len > 1 && layout(types, len, column_align).1 > size {
+        len -= 1;
+    }
+    len as u32
+}
+
+pub(crate) fn align(x: usize, alignment: usize) -> usize {
     debug_assert!(alignment.is_power_of_two());
     (x + alignment - 1) & (!alignment + 1)
 }
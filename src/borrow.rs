@@ -12,12 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::fmt;
+use core::mem;
 use core::ops::{Deref, DerefMut};
 use core::ptr::NonNull;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-use crate::archetype::Archetype;
-use crate::{Component, MissingComponent};
+use crate::archetype::{Archetype, ComponentId};
+use crate::{Component, ComponentError, MissingComponent};
 
 pub struct AtomicBorrow(AtomicUsize);
 
@@ -26,7 +28,8 @@ impl AtomicBorrow {
         Self(AtomicUsize::new(0))
     }
 
-    pub fn borrow(&self) -> bool {
+    /// Acquire a shared borrow of the component type `name`
+    pub fn borrow(&self, name: &'static str) -> Result<(), BorrowError> {
         let value = self.0.fetch_add(1, Ordering::Acquire).wrapping_add(1);
         if value == 0 {
             // Wrapped, this borrow is invalid!
@@ -34,16 +37,22 @@ impl AtomicBorrow {
         }
         if value & UNIQUE_BIT != 0 {
             self.0.fetch_sub(1, Ordering::Release);
-            false
+            Err(BorrowError::Unique(name))
         } else {
-            true
+            Ok(())
         }
     }
 
-    pub fn borrow_mut(&self) -> bool {
-        self.0
+    /// Acquire a unique borrow of the component type `name`
+    pub fn borrow_mut(&self, name: &'static str) -> Result<(), BorrowError> {
+        match self
+            .0
             .compare_exchange(0, UNIQUE_BIT, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+        {
+            Ok(_) => Ok(()),
+            Err(x) if x & UNIQUE_BIT != 0 => Err(BorrowError::Unique(name)),
+            Err(_) => Err(BorrowError::Shared(name)),
+        }
     }
 
     pub fn release(&self) {
@@ -58,7 +67,37 @@ impl AtomicBorrow {
     }
 }
 
-const UNIQUE_BIT: usize = !(usize::max_value() >> 1);
+const UNIQUE_BIT: usize = !(usize::MAX >> 1);
+
+/// Error indicating that a component could not be borrowed due to a conflicting borrow
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum BorrowError {
+    /// The named component type was already borrowed uniquely
+    Unique(&'static str),
+    /// The named component type was already borrowed, so it couldn't be borrowed uniquely
+    Shared(&'static str),
+}
+
+impl BorrowError {
+    /// Name of the component type that could not be borrowed
+    pub fn component(&self) -> &'static str {
+        match *self {
+            BorrowError::Unique(x) | BorrowError::Shared(x) => x,
+        }
+    }
+}
+
+impl fmt::Display for BorrowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BorrowError::Unique(x) => write!(f, "{} already borrowed uniquely", x),
+            BorrowError::Shared(x) => write!(f, "{} already borrowed", x),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BorrowError {}
 
 /// Shared borrow of an entity's component
 #[derive(Clone)]
@@ -70,18 +109,23 @@ pub struct Ref<'a, T: Component> {
 impl<'a, T: Component> Ref<'a, T> {
     pub(crate) unsafe fn new(
         archetype: &'a Archetype,
+        id: u32,
         index: u32,
     ) -> Result<Self, MissingComponent> {
-        let target = NonNull::new_unchecked(
-            archetype
-                .get::<T>()
-                .ok_or_else(MissingComponent::new::<T>)?
-                .as_ptr()
-                .add(index as usize),
-        );
+        let (target, _) = target::<T>(archetype, id, index)?;
         archetype.borrow::<T>();
         Ok(Self { archetype, target })
     }
+
+    pub(crate) unsafe fn try_new(
+        archetype: &'a Archetype,
+        id: u32,
+        index: u32,
+    ) -> Result<Self, ComponentError> {
+        let (target, _) = target::<T>(archetype, id, index)?;
+        archetype.try_borrow::<T>()?;
+        Ok(Self { archetype, target })
+    }
 }
 
 unsafe impl<T: Component> Send for Ref<'_, T> {}
@@ -109,17 +153,32 @@ pub struct RefMut<'a, T: Component> {
 impl<'a, T: Component> RefMut<'a, T> {
     pub(crate) unsafe fn new(
         archetype: &'a Archetype,
+        id: u32,
         index: u32,
     ) -> Result<Self, MissingComponent> {
-        let target = NonNull::new_unchecked(
-            archetype
-                .get::<T>()
-                .ok_or_else(MissingComponent::new::<T>)?
-                .as_ptr()
-                .add(index as usize),
-        );
+        let (target, mutated) = target::<T>(archetype, id, index)?;
         archetype.borrow_mut::<T>();
-        Ok(Self { archetype, target })
+        Ok(Self::mark_mutated(archetype, target, mutated))
+    }
+
+    pub(crate) unsafe fn try_new(
+        archetype: &'a Archetype,
+        id: u32,
+        index: u32,
+    ) -> Result<Self, ComponentError> {
+        let (target, mutated) = target::<T>(archetype, id, index)?;
+        archetype.try_borrow_mut::<T>()?;
+        Ok(Self::mark_mutated(archetype, target, mutated))
+    }
+
+    /// Finish constructing a `RefMut` once `T` has been uniquely borrowed
+    unsafe fn mark_mutated(
+        archetype: &'a Archetype,
+        target: NonNull<T>,
+        mutated: NonNull<u32>,
+    ) -> Self {
+        *mutated.as_ptr() = archetype.tick();
+        Self { archetype, target }
     }
 }
 
@@ -145,25 +204,32 @@ impl<'a, T: Component> DerefMut for RefMut<'a, T> {
     }
 }
 
+/// Locate the `T` component of the entity `id` at `index` in `archetype`, and the tick at which it
+/// was last mutated
+unsafe fn target<T: Component>(
+    archetype: &Archetype,
+    id: u32,
+    index: u32,
+) -> Result<(NonNull<T>, NonNull<u32>), MissingComponent> {
+    let (target, mutated) = archetype
+        .locate(ComponentId::of::<T>(), mem::size_of::<T>(), id, index)
+        .ok_or_else(MissingComponent::new::<T>)?;
+    Ok((target.cast::<T>(), mutated))
+}
+
 /// Handle to an entity with any component types
 #[derive(Copy, Clone)]
 pub struct EntityRef<'a> {
-    archetype: Option<&'a Archetype>,
+    archetype: &'a Archetype,
+    id: u32,
     index: u32,
 }
 
 impl<'a> EntityRef<'a> {
-    /// Construct a `Ref` for an entity with no components
-    pub(crate) fn empty() -> Self {
-        Self {
-            archetype: None,
-            index: 0,
-        }
-    }
-
-    pub(crate) unsafe fn new(archetype: &'a Archetype, index: u32) -> Self {
+    pub(crate) unsafe fn new(archetype: &'a Archetype, id: u32, index: u32) -> Self {
         Self {
-            archetype: Some(archetype),
+            archetype,
+            id,
             index,
         }
     }
@@ -173,14 +239,44 @@ impl<'a> EntityRef<'a> {
     /// Panics if the component is already uniquely borrowed from another entity with the same
     /// components.
     pub fn get<T: Component>(&self) -> Option<Ref<'a, T>> {
-        Some(unsafe { Ref::new(self.archetype?, self.index).ok()? })
+        unsafe { Ref::new(self.archetype, self.id, self.index).ok() }
     }
 
     /// Uniquely borrow the component of type `T`, if it exists
     ///
     /// Panics if the component is already borrowed from another entity with the same components.
     pub fn get_mut<T: Component>(&self) -> Option<RefMut<'a, T>> {
-        Some(unsafe { RefMut::new(self.archetype?, self.index).ok()? })
+        unsafe { RefMut::new(self.archetype, self.id, self.index).ok() }
+    }
+
+    /// Like `get`, but returns an error instead of panicking if `T` is already uniquely borrowed
+    pub fn try_get<T: Component>(&self) -> Result<Option<Ref<'a, T>>, BorrowError> {
+        match unsafe { Ref::try_new(self.archetype, self.id, self.index) } {
+            Ok(x) => Ok(Some(x)),
+            Err(ComponentError::Borrow(e)) => Err(e),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Like `get_mut`, but returns an error instead of panicking if `T` is already borrowed
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let entity = world.entity(a).unwrap();
+    /// let x = entity.try_get_mut::<i32>().unwrap().unwrap();
+    /// assert_eq!(entity.try_get_mut::<i32>().err(), Some(BorrowError::Unique("i32")));
+    /// drop(x);
+    /// assert!(entity.try_get_mut::<bool>().unwrap().is_none());
+    /// ```
+    pub fn try_get_mut<T: Component>(&self) -> Result<Option<RefMut<'a, T>>, BorrowError> {
+        match unsafe { RefMut::try_new(self.archetype, self.id, self.index) } {
+            Ok(x) => Ok(Some(x)),
+            Err(ComponentError::Borrow(e)) => Err(e),
+            Err(_) => Ok(None),
+        }
     }
 }
 
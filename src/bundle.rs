@@ -12,19 +12,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::alloc::{vec, vec::Vec};
+use crate::alloc::{boxed::Box, vec, vec::Vec};
 use core::any::{type_name, TypeId};
-use core::ptr::NonNull;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicPtr, Ordering};
 use core::{fmt, mem};
 
-use crate::archetype::TypeInfo;
+use crate::archetype::{ComponentId, TypeInfo};
 use crate::Component;
 
 /// A dynamically typed collection of components
 pub trait DynamicBundle {
     /// Invoke a callback on the fields' type IDs, sorted by descending alignment then id
     #[doc(hidden)]
-    fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T;
+    fn with_ids<T>(&self, f: impl FnOnce(&[ComponentId]) -> T) -> T;
     /// Obtain the fields' TypeInfos, sorted by descending alignment then id
     #[doc(hidden)]
     fn type_info(&self) -> Vec<TypeInfo>;
@@ -33,13 +34,13 @@ pub trait DynamicBundle {
     /// Must invoke `f` only with a valid pointer, its type, and the pointee's size. A `false`
     /// return value indicates that the value was not moved and should be dropped.
     #[doc(hidden)]
-    unsafe fn put(self, f: impl FnMut(*mut u8, TypeId, usize) -> bool);
+    unsafe fn put(self, f: impl FnMut(*mut u8, ComponentId, usize) -> bool);
 }
 
 /// A statically typed collection of components
 pub trait Bundle: DynamicBundle {
     #[doc(hidden)]
-    fn with_static_ids<T>(f: impl FnOnce(&[TypeId]) -> T) -> T;
+    fn with_static_ids<T>(f: impl FnOnce(&[ComponentId]) -> T) -> T;
 
     /// Obtain the fields' TypeInfos, sorted by descending alignment then id
     #[doc(hidden)]
@@ -53,7 +54,7 @@ pub trait Bundle: DynamicBundle {
     /// pointers if any call to `f` returns `None`.
     #[doc(hidden)]
     unsafe fn get(
-        f: impl FnMut(TypeId, usize) -> Option<NonNull<u8>>,
+        f: impl FnMut(ComponentId, usize) -> Option<NonNull<u8>>,
     ) -> Result<Self, MissingComponent>
     where
         Self: Sized;
@@ -79,10 +80,116 @@ impl fmt::Display for MissingComponent {
 #[cfg(feature = "std")]
 impl std::error::Error for MissingComponent {}
 
+/// The IDs of `types`, which must be sorted, panicking if any type occurs more than once
+///
+/// Used by `derive(Bundle)`, which can't rule out duplicates at compile time.
+#[doc(hidden)]
+pub fn sorted_ids(bundle: &str, types: &[TypeInfo]) -> Vec<ComponentId> {
+    // Sorting places equal IDs next to each other, since they have equal alignment
+    for pair in types.windows(2) {
+        if pair[0].id() == pair[1].id() {
+            panic!(
+                "{} has multiple {} fields; each type must occur at most once!",
+                bundle,
+                pair[0].name()
+            );
+        }
+    }
+    types.iter().map(|x| x.id()).collect()
+}
+
+/// Storage for the IDs of a non-generic derived bundle, computed on first use
+///
+/// Works without `std` or any locking: threads that race to initialize the cache each compute
+/// the IDs, and all but the first to finish discard theirs.
+#[doc(hidden)]
+pub struct IdCache(AtomicPtr<Vec<ComponentId>>);
+
+impl IdCache {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self(AtomicPtr::new(ptr::null_mut()))
+    }
+
+    pub fn get(&'static self, init: impl FnOnce() -> Vec<ComponentId>) -> &'static [ComponentId] {
+        let mut ids = self.0.load(Ordering::Acquire);
+        if ids.is_null() {
+            let new = Box::into_raw(Box::new(init()));
+            ids = match self.0.compare_exchange(
+                ptr::null_mut(),
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => new,
+                Err(existing) => {
+                    unsafe {
+                        drop(Box::from_raw(new));
+                    }
+                    existing
+                }
+            };
+        }
+        // Never freed once published
+        unsafe { &*ids }
+    }
+}
+
+/// Storage for the IDs of every instantiation of a generic derived bundle, computed on first use
+///
+/// A list that only ever grows, so that it works without `std` or any locking like `IdCache`.
+/// Threads that race to add the same instantiation may both succeed, which only wastes memory.
+#[doc(hidden)]
+pub struct GenericIdCache(AtomicPtr<GenericIds>);
+
+struct GenericIds {
+    ty: TypeId,
+    ids: Vec<ComponentId>,
+    next: *mut GenericIds,
+}
+
+impl GenericIdCache {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        Self(AtomicPtr::new(ptr::null_mut()))
+    }
+
+    pub fn get(
+        &'static self,
+        ty: TypeId,
+        init: impl FnOnce() -> Vec<ComponentId>,
+    ) -> &'static [ComponentId] {
+        let mut head = self.0.load(Ordering::Acquire);
+        // Nodes are never freed once published
+        let mut node = head as *const GenericIds;
+        while let Some(x) = unsafe { node.as_ref() } {
+            if x.ty == ty {
+                return &x.ids;
+            }
+            node = x.next;
+        }
+        let new = Box::into_raw(Box::new(GenericIds {
+            ty,
+            ids: init(),
+            next: head,
+        }));
+        while let Err(x) =
+            self.0
+                .compare_exchange_weak(head, new, Ordering::AcqRel, Ordering::Acquire)
+        {
+            head = x;
+            unsafe {
+                (*new).next = head;
+            }
+        }
+        unsafe { &(*new).ids }
+    }
+}
+
 macro_rules! tuple_impl {
     ($($name: ident),*) => {
         impl<$($name: Component),*> DynamicBundle for ($($name,)*) {
-            fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T {
+            fn with_ids<T>(&self, f: impl FnOnce(&[ComponentId]) -> T) -> T {
                 Self::with_static_ids(f)
             }
 
@@ -91,13 +198,13 @@ macro_rules! tuple_impl {
             }
 
             #[allow(unused_variables, unused_mut)]
-            unsafe fn put(self, mut f: impl FnMut(*mut u8, TypeId, usize) -> bool) {
+            unsafe fn put(self, mut f: impl FnMut(*mut u8, ComponentId, usize) -> bool) {
                 #[allow(non_snake_case)]
                 let ($(mut $name,)*) = self;
                 $(
                     if f(
                         (&mut $name as *mut $name).cast::<u8>(),
-                        TypeId::of::<$name>(),
+                        ComponentId::of::<$name>(),
                         mem::size_of::<$name>()
                     ) {
                         mem::forget($name)
@@ -107,11 +214,11 @@ macro_rules! tuple_impl {
         }
 
         impl<$($name: Component),*> Bundle for ($($name,)*) {
-            fn with_static_ids<T>(f: impl FnOnce(&[TypeId]) -> T) -> T {
+            fn with_static_ids<T>(f: impl FnOnce(&[ComponentId]) -> T) -> T {
                 const N: usize = count!($($name),*);
-                let mut xs: [(usize, TypeId); N] = [$((mem::align_of::<$name>(), TypeId::of::<$name>())),*];
+                let mut xs: [(usize, ComponentId); N] = [$((mem::align_of::<$name>(), ComponentId::of::<$name>())),*];
                 xs.sort_unstable_by(|x, y| x.0.cmp(&y.0).reverse().then(x.1.cmp(&y.1)));
-                let mut ids = [TypeId::of::<()>(); N];
+                let mut ids = [ComponentId::of::<()>(); N];
                 for (slot, &(_, id)) in ids.iter_mut().zip(xs.iter()) {
                     *slot = id;
                 }
@@ -125,10 +232,10 @@ macro_rules! tuple_impl {
             }
 
             #[allow(unused_variables, unused_mut)]
-            unsafe fn get(mut f: impl FnMut(TypeId, usize) -> Option<NonNull<u8>>) -> Result<Self, MissingComponent> {
+            unsafe fn get(mut f: impl FnMut(ComponentId, usize) -> Option<NonNull<u8>>) -> Result<Self, MissingComponent> {
                 #[allow(non_snake_case)]
                 let ($(mut $name,)*) = ($(
-                    f(TypeId::of::<$name>(), mem::size_of::<$name>()).ok_or_else(MissingComponent::new::<$name>)?
+                    f(ComponentId::of::<$name>(), mem::size_of::<$name>()).ok_or_else(MissingComponent::new::<$name>)?
                         .as_ptr()
                         .cast::<$name>(),)*
                 );
@@ -0,0 +1,93 @@
+use crate::alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use hashbrown::HashMap;
+
+use crate::archetype::{ComponentId, TypeInfo};
+use crate::{Component, NoSuchEntity};
+
+/// Clones `n` contiguous components starting at the first pointer into uninitialized memory at the
+/// second
+pub(crate) type CloneFn = unsafe fn(*const u8, *mut u8, usize);
+
+/// Component types registered through `World::register_clone`
+#[derive(Default, Clone)]
+pub(crate) struct Cloners {
+    fns: HashMap<ComponentId, CloneFn>,
+}
+
+impl Cloners {
+    pub fn register<T: Component + Clone>(&mut self) {
+        self.fns.insert(ComponentId::of::<T>(), clone_slice::<T>);
+    }
+
+    /// Look up the clone function of `ty`
+    pub fn get(&self, ty: &TypeInfo) -> Result<CloneFn, NotCloneable> {
+        self.fns
+            .get(&ty.id())
+            .copied()
+            .ok_or(NotCloneable(ty.name()))
+    }
+
+    /// Look up the clone function of every type in `types`, in order
+    pub fn get_all(&self, types: &[TypeInfo]) -> Result<Vec<CloneFn>, NotCloneable> {
+        types.iter().map(|ty| self.get(ty)).collect()
+    }
+}
+
+unsafe fn clone_slice<T: Clone>(src: *const u8, dst: *mut u8, n: usize) {
+    let src = src.cast::<T>();
+    let dst = dst.cast::<T>();
+    for i in 0..n {
+        dst.add(i).write((*src.add(i)).clone());
+    }
+}
+
+/// Error indicating that a component type was not registered with `World::register_clone`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct NotCloneable(&'static str);
+
+impl fmt::Display for NotCloneable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} component is not registered as cloneable", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for NotCloneable {}
+
+/// Errors that arise when cloning an entity
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum CloneError {
+    /// The entity was already despawned
+    NoSuchEntity,
+    /// The entity has a component that cannot be cloned
+    NotCloneable(NotCloneable),
+}
+
+#[cfg(feature = "std")]
+impl Error for CloneError {}
+
+impl fmt::Display for CloneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use CloneError::*;
+        match *self {
+            NoSuchEntity => f.write_str("no such entity"),
+            NotCloneable(ref x) => x.fmt(f),
+        }
+    }
+}
+
+impl From<NoSuchEntity> for CloneError {
+    fn from(NoSuchEntity: NoSuchEntity) -> Self {
+        CloneError::NoSuchEntity
+    }
+}
+
+impl From<NotCloneable> for CloneError {
+    fn from(x: NotCloneable) -> Self {
+        CloneError::NotCloneable(x)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use crate::archetype::TypeInfo;
+use crate::Component;
+
+/// Components for many entities, one `Vec` per component type, for `World::spawn_column_batch`
+///
+/// Every column must have the same length. Entity `i` receives element `i` of each column.
+///
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let mut batch = ColumnBatch::new();
+/// batch.add(vec![1, 2, 3]).add_slice(&["a", "b", "c"]);
+/// let entities = world.spawn_column_batch(batch).collect::<Vec<_>>();
+/// assert_eq!(*world.get::<i32>(entities[1]).unwrap(), 2);
+/// assert_eq!(*world.get::<&str>(entities[2]).unwrap(), "c");
+/// ```
+#[derive(Default)]
+pub struct ColumnBatch {
+    columns: Vec<(TypeInfo, Box<dyn ErasedVec>)>,
+    len: u32,
+}
+
+impl ColumnBatch {
+    /// Create a batch with no columns
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a column of `T` components
+    ///
+    /// Panics if a column of `T` was already added, or if `components` has a different length
+    /// than the columns added before it.
+    pub fn add<T: Component>(&mut self, components: Vec<T>) -> &mut Self {
+        let ty = TypeInfo::of::<T>();
+        assert!(
+            self.columns.iter().all(|x| x.0 != ty),
+            "{} column added twice",
+            ty.name()
+        );
+        let len = u32::try_from(components.len()).expect("too many entities");
+        if self.columns.is_empty() {
+            self.len = len;
+        } else {
+            assert_eq!(len, self.len, "{} column has the wrong length", ty.name());
+        }
+        self.columns.push((ty, Box::new(components)));
+        self
+    }
+
+    /// Add a column of `T` components cloned from `components`
+    ///
+    /// See `add`.
+    pub fn add_slice<T: Component + Clone>(&mut self, components: &[T]) -> &mut Self {
+        self.add(components.to_vec())
+    }
+
+    /// Number of entities in the batch
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Whether the batch contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Sort the columns into archetype order
+    pub(crate) fn into_columns(mut self) -> Vec<(TypeInfo, Box<dyn ErasedVec>)> {
+        self.columns.sort_unstable_by_key(|x| x.0);
+        self.columns
+    }
+}
+
+/// A `Vec` of some component type
+pub(crate) trait ErasedVec: Send + Sync {
+    fn as_ptr(&self) -> *const u8;
+
+    /// Forget every element without dropping it, after they've all been moved out
+    unsafe fn forget_elements(&mut self);
+}
+
+impl<T: Component> ErasedVec for Vec<T> {
+    fn as_ptr(&self) -> *const u8 {
+        <[T]>::as_ptr(self).cast()
+    }
+
+    unsafe fn forget_elements(&mut self) {
+        self.set_len(0);
+    }
+}
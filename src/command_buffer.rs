@@ -0,0 +1,282 @@
+use crate::alloc::alloc::{alloc, dealloc, Layout};
+use crate::alloc::vec::Vec;
+use core::mem;
+use core::ops::Range;
+use core::ptr::{self, NonNull};
+
+use crate::archetype::{align, ComponentId, TypeInfo};
+use crate::{Bundle, Component, DynamicBundle, Entity, World};
+
+/// Records operations for future application to a `World`
+///
+/// Useful when operations cannot be applied directly due to ordering concerns or borrow checking,
+/// e.g. when spawning or despawning entities while iterating a query.
+///
+/// Entities reserved with `World::reserve_entity` can be freely referenced by recorded commands,
+/// allowing components to be attached to entities that do not yet exist.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456, false));
+/// let mut cmd = CommandBuffer::new();
+/// for (id, &flag) in world.query::<&bool>().iter() {
+///     if flag {
+///         cmd.despawn(id);
+///     } else {
+///         cmd.insert_one(id, "abc");
+///     }
+/// }
+/// let c = world.reserve_entity();
+/// cmd.insert(c, (789, true));
+/// cmd.run_on(&mut world);
+/// assert!(!world.contains(a));
+/// assert_eq!(*world.get::<&str>(b).unwrap(), "abc");
+/// assert_eq!(*world.get::<i32>(c).unwrap(), 789);
+/// ```
+pub struct CommandBuffer {
+    cmds: Vec<Cmd>,
+    storage: NonNull<u8>,
+    layout: Layout,
+    cursor: usize,
+    components: Vec<ComponentInfo>,
+    ids: Vec<ComponentId>,
+}
+
+impl CommandBuffer {
+    /// Create an empty command buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `components` to `entity`, if it exists
+    ///
+    /// See `World::insert`.
+    pub fn insert(&mut self, entity: Entity, components: impl DynamicBundle) {
+        let components = self.record(components);
+        self.cmds.push(Cmd::SpawnOrInsert {
+            entity: Some(entity),
+            components,
+        });
+    }
+
+    /// Add `component` to `entity`, if it exists
+    ///
+    /// See `World::insert_one`.
+    pub fn insert_one(&mut self, entity: Entity, component: impl Component) {
+        self.insert(entity, (component,));
+    }
+
+    /// Remove components from `entity`, if they exist
+    ///
+    /// The removed components are dropped. See `World::remove`.
+    pub fn remove<T: Bundle>(&mut self, entity: Entity) {
+        fn remove_and_drop<T: Bundle>(world: &mut World, entity: Entity) {
+            let _ = world.remove::<T>(entity);
+        }
+        self.cmds.push(Cmd::Remove {
+            entity,
+            remove: remove_and_drop::<T>,
+        });
+    }
+
+    /// Remove the `T` component from `entity`, if it exists
+    ///
+    /// See `remove`.
+    pub fn remove_one<T: Component>(&mut self, entity: Entity) {
+        self.remove::<(T,)>(entity);
+    }
+
+    /// Destroy `entity`, if it exists
+    ///
+    /// See `World::despawn`.
+    pub fn despawn(&mut self, entity: Entity) {
+        self.cmds.push(Cmd::Despawn(entity));
+    }
+
+    /// Create an entity with certain components
+    ///
+    /// See `World::spawn`. To refer to the new entity from other commands, reserve its ID with
+    /// `World::reserve_entity` and record an `insert` instead.
+    pub fn spawn(&mut self, components: impl DynamicBundle) {
+        let components = self.record(components);
+        self.cmds.push(Cmd::SpawnOrInsert {
+            entity: None,
+            components,
+        });
+    }
+
+    /// Apply all recorded commands to `world` in the order they were recorded
+    ///
+    /// Commands referring to entities or components that no longer exist are skipped. The buffer
+    /// is left empty, retaining its storage for reuse.
+    pub fn run_on(&mut self, world: &mut World) {
+        let mut cmds = mem::take(&mut self.cmds);
+        for cmd in cmds.drain(..) {
+            match cmd {
+                Cmd::SpawnOrInsert { entity, components } => {
+                    self.ids.clear();
+                    self.ids.extend(
+                        self.components[components.clone()]
+                            .iter()
+                            .map(|x| x.ty.id()),
+                    );
+                    let bundle = RecordedEntity {
+                        buffer: self,
+                        components,
+                    };
+                    match entity {
+                        Some(entity) => {
+                            // If `entity` no longer exists, `bundle` drops the components
+                            let _ = world.insert(entity, bundle);
+                        }
+                        None => {
+                            world.spawn(bundle);
+                        }
+                    }
+                }
+                Cmd::Remove { entity, remove } => remove(world, entity),
+                Cmd::Despawn(entity) => {
+                    let _ = world.despawn(entity);
+                }
+            }
+        }
+        self.cmds = cmds;
+        // Every component has been moved out or dropped by now
+        self.components.clear();
+        self.cursor = 0;
+    }
+
+    /// Drop all recorded commands without applying them
+    pub fn clear(&mut self) {
+        self.cmds.clear();
+        for info in self.components.drain(..) {
+            unsafe {
+                info.ty.drop(self.storage.as_ptr().add(info.offset));
+            }
+        }
+        self.cursor = 0;
+    }
+
+    /// Move `components` into storage, returning the range of `self.components` they occupy
+    fn record(&mut self, components: impl DynamicBundle) -> Range<usize> {
+        let first = self.components.len();
+        let info = components.type_info();
+        unsafe {
+            components.put(|ptr, id, _| {
+                let ty = *info.iter().find(|x| x.id() == id).unwrap();
+                self.add_inner(ptr, ty);
+                true
+            });
+        }
+        // Sort by descending alignment then id, as required by `DynamicBundle`
+        self.components[first..].sort_unstable_by_key(|x| x.ty);
+        first..self.components.len()
+    }
+
+    unsafe fn add_inner(&mut self, ptr: *mut u8, ty: TypeInfo) {
+        let offset = align(self.cursor, ty.layout().align());
+        let end = offset + ty.layout().size();
+        if end > self.layout.size() || ty.layout().align() > self.layout.align() {
+            let new_align = self.layout.align().max(ty.layout().align());
+            let new_size = end.next_power_of_two().max(self.layout.size()).max(64);
+            let new_layout = Layout::from_size_align(new_size, new_align).unwrap();
+            let new_storage = NonNull::new(alloc(new_layout)).unwrap();
+            ptr::copy_nonoverlapping(self.storage.as_ptr(), new_storage.as_ptr(), self.cursor);
+            if self.layout.size() != 0 {
+                dealloc(self.storage.as_ptr(), self.layout);
+            }
+            self.storage = new_storage;
+            self.layout = new_layout;
+        }
+        ptr::copy_nonoverlapping(ptr, self.storage.as_ptr().add(offset), ty.layout().size());
+        self.components.push(ComponentInfo { ty, offset });
+        self.cursor = end;
+    }
+}
+
+unsafe impl Send for CommandBuffer {}
+unsafe impl Sync for CommandBuffer {}
+
+impl Drop for CommandBuffer {
+    fn drop(&mut self) {
+        self.clear();
+        if self.layout.size() != 0 {
+            unsafe {
+                dealloc(self.storage.as_ptr(), self.layout);
+            }
+        }
+    }
+}
+
+impl Default for CommandBuffer {
+    fn default() -> Self {
+        Self {
+            cmds: Vec::new(),
+            storage: NonNull::dangling(),
+            layout: Layout::from_size_align(0, 1).unwrap(),
+            cursor: 0,
+            components: Vec::new(),
+            ids: Vec::new(),
+        }
+    }
+}
+
+/// The components of a single recorded spawn or insert, moved out of a `CommandBuffer`
+struct RecordedEntity<'a> {
+    buffer: &'a mut CommandBuffer,
+    components: Range<usize>,
+}
+
+impl DynamicBundle for RecordedEntity<'_> {
+    fn with_ids<T>(&self, f: impl FnOnce(&[ComponentId]) -> T) -> T {
+        f(&self.buffer.ids)
+    }
+
+    fn type_info(&self) -> Vec<TypeInfo> {
+        self.buffer.components[self.components.clone()]
+            .iter()
+            .map(|x| x.ty)
+            .collect()
+    }
+
+    unsafe fn put(mut self, mut f: impl FnMut(*mut u8, ComponentId, usize) -> bool) {
+        for info in &self.buffer.components[self.components.clone()] {
+            let ptr = self.buffer.storage.as_ptr().add(info.offset);
+            if !f(ptr, info.ty.id(), info.ty.layout().size()) {
+                info.ty.drop(ptr);
+            }
+        }
+        // Ownership has been transferred, so `Drop` must not touch the components again
+        self.components = 0..0;
+    }
+}
+
+impl Drop for RecordedEntity<'_> {
+    fn drop(&mut self) {
+        for info in &self.buffer.components[self.components.clone()] {
+            unsafe {
+                info.ty.drop(self.buffer.storage.as_ptr().add(info.offset));
+            }
+        }
+    }
+}
+
+enum Cmd {
+    SpawnOrInsert {
+        entity: Option<Entity>,
+        components: Range<usize>,
+    },
+    Remove {
+        entity: Entity,
+        remove: fn(&mut World, Entity),
+    },
+    Despawn(Entity),
+}
+
+struct ComponentInfo {
+    ty: TypeInfo,
+    offset: usize,
+}
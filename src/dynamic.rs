@@ -0,0 +1,196 @@
+use core::ops::{Deref, DerefMut, Range};
+use core::ptr::NonNull;
+use core::slice;
+
+use crate::archetype::{Archetype, ComponentId};
+use crate::entities::EntityMeta;
+use crate::Entity;
+
+/// Shared borrow of a column of runtime-defined components, as bytes
+///
+/// Component `i` occupies bytes `i * size..(i + 1) * size`, where `size` comes from the type's
+/// `TypeInfo`. Obtained from `Archetype::get_column` or `DynamicChunk::get`.
+pub struct Column<'a> {
+    archetype: &'a Archetype,
+    id: ComponentId,
+    bytes: &'a [u8],
+}
+
+impl<'a> Column<'a> {
+    /// `id` must already be borrowed from `archetype`, and `data` must point to `len` bytes
+    pub(crate) unsafe fn new(
+        archetype: &'a Archetype,
+        id: ComponentId,
+        data: NonNull<u8>,
+        len: usize,
+    ) -> Self {
+        Self {
+            archetype,
+            id,
+            bytes: slice::from_raw_parts(data.as_ptr(), len),
+        }
+    }
+}
+
+impl Drop for Column<'_> {
+    fn drop(&mut self) {
+        self.archetype.release_dynamic(self.id);
+    }
+}
+
+impl Deref for Column<'_> {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.bytes
+    }
+}
+
+/// Unique borrow of a column of runtime-defined components, as bytes
+///
+/// See `Column`. Obtained from `Archetype::get_column_mut` or `DynamicChunk::get_mut`.
+pub struct ColumnMut<'a> {
+    archetype: &'a Archetype,
+    id: ComponentId,
+    bytes: &'a mut [u8],
+}
+
+impl<'a> ColumnMut<'a> {
+    /// `id` must already be uniquely borrowed from `archetype`, and `data` must point to `len`
+    /// bytes
+    pub(crate) unsafe fn new(
+        archetype: &'a Archetype,
+        id: ComponentId,
+        data: NonNull<u8>,
+        len: usize,
+    ) -> Self {
+        Self {
+            archetype,
+            id,
+            bytes: slice::from_raw_parts_mut(data.as_ptr(), len),
+        }
+    }
+}
+
+impl Drop for ColumnMut<'_> {
+    fn drop(&mut self) {
+        self.archetype.release_mut_dynamic(self.id);
+    }
+}
+
+impl Deref for ColumnMut<'_> {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.bytes
+    }
+}
+
+impl DerefMut for ColumnMut<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.bytes
+    }
+}
+
+/// Iterator over the chunks of the archetypes having every component type in a set, from
+/// `World::query_dynamic`
+pub struct DynamicQueryIter<'a, 'q> {
+    meta: &'a [EntityMeta],
+    archetypes: slice::Iter<'a, Archetype>,
+    ids: &'q [ComponentId],
+    /// Archetype being visited, and the index of the first entity of its next chunk
+    current: Option<(&'a Archetype, u32)>,
+}
+
+impl<'a, 'q> DynamicQueryIter<'a, 'q> {
+    pub(crate) fn new(
+        meta: &'a [EntityMeta],
+        archetypes: &'a [Archetype],
+        ids: &'q [ComponentId],
+    ) -> Self {
+        Self {
+            meta,
+            archetypes: archetypes.iter(),
+            ids,
+            current: None,
+        }
+    }
+}
+
+impl<'a> Iterator for DynamicQueryIter<'a, '_> {
+    type Item = DynamicChunk<'a>;
+
+    fn next(&mut self) -> Option<DynamicChunk<'a>> {
+        loop {
+            if let Some((archetype, start)) = self.current {
+                if start < archetype.len() {
+                    let end = archetype.chunk_end(start);
+                    self.current = Some((archetype, end));
+                    return Some(DynamicChunk {
+                        meta: self.meta,
+                        archetype,
+                        range: start..end,
+                    });
+                }
+            }
+            let ids = self.ids;
+            let archetype = self
+                .archetypes
+                .find(|x| !x.is_empty() && ids.iter().all(|&id| x.has_dynamic(id)))?;
+            self.current = Some((archetype, 0));
+        }
+    }
+}
+
+/// A set of entities sharing the same component types and stored together, yielded by
+/// `DynamicQueryIter`
+///
+/// Covers a whole archetype, unless its storage is divided into chunks as with
+/// `World::with_chunk_size`.
+pub struct DynamicChunk<'a> {
+    meta: &'a [EntityMeta],
+    archetype: &'a Archetype,
+    range: Range<u32>,
+}
+
+impl<'a> DynamicChunk<'a> {
+    /// Number of entities in this chunk
+    pub fn len(&self) -> u32 {
+        self.range.end - self.range.start
+    }
+
+    /// Whether this chunk contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    /// The entities in this chunk, in the same order as their components in each column
+    pub fn entities(&self) -> impl ExactSizeIterator<Item = Entity> + 'a {
+        let meta = self.meta;
+        let archetype = self.archetype;
+        self.range.clone().map(move |index| {
+            let id = archetype.entity_id(index);
+            Entity {
+                id,
+                generation: meta[id as usize].generation,
+            }
+        })
+    }
+
+    /// The archetype storing this chunk
+    pub fn archetype(&self) -> &'a Archetype {
+        self.archetype
+    }
+
+    /// Borrow the column of the runtime-defined component type `id`
+    ///
+    /// See `Archetype::get_column`.
+    pub fn get(&self, id: ComponentId) -> Option<Column<'a>> {
+        self.archetype.get_column_range(id, self.range.clone())
+    }
+
+    /// Uniquely borrow the column of the runtime-defined component type `id`
+    ///
+    /// See `Archetype::get_column_mut`.
+    pub fn get_mut(&self, id: ComponentId) -> Option<ColumnMut<'a>> {
+        self.archetype.get_column_range_mut(id, self.range.clone())
+    }
+}
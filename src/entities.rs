@@ -1,6 +1,7 @@
 use alloc::boxed::Box;
 use alloc::vec::Vec;
 use core::convert::TryFrom;
+use core::ops::Range;
 use core::sync::atomic::{AtomicU32, Ordering};
 use core::{fmt, mem};
 #[cfg(feature = "std")]
@@ -52,6 +53,68 @@ impl fmt::Debug for Entity {
     }
 }
 
+/// Serialized as the `u64` returned by `to_bits`
+#[cfg(feature = "serde")]
+impl serde::Serialize for Entity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.to_bits())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Entity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(Self::from_bits)
+    }
+}
+
+/// Consecutively numbered entities, from `World::spawn_column_batch`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EntityRange {
+    ids: Range<u32>,
+    generation: u32,
+}
+
+impl EntityRange {
+    /// Constructed from the results of `Entities::alloc_many`
+    pub(crate) fn new(ids: Range<u32>, generation: u32) -> Self {
+        Self { ids, generation }
+    }
+
+    /// Whether `entity` is in this range
+    pub fn contains(&self, entity: Entity) -> bool {
+        entity.generation == self.generation && self.ids.contains(&entity.id)
+    }
+}
+
+impl Iterator for EntityRange {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let id = self.ids.next()?;
+        Some(Entity {
+            generation: self.generation,
+            id,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for EntityRange {
+    fn next_back(&mut self) -> Option<Entity> {
+        let id = self.ids.next_back()?;
+        Some(Entity {
+            generation: self.generation,
+            id,
+        })
+    }
+}
+
+impl ExactSizeIterator for EntityRange {}
+
 #[derive(Default)]
 pub(crate) struct Entities {
     pub meta: Box<[EntityMeta]>,
@@ -62,12 +125,33 @@ pub(crate) struct Entities {
     // Unused entity IDs below `meta.len()`
     free: Box<[u32]>,
     free_cursor: AtomicU32,
+    // Index in `free` of each ID, which is meaningful for IDs found at that index below
+    // `free_cursor`
+    free_index: Box<[u32]>,
     // Reserved IDs within `meta.len()` with implicit archetype 0 and undefined index. Should be
     // consumed and used to initialize locations to produce real entities after calling `flush`.
     reserved: Box<[AtomicU32]>,
     reserved_cursor: AtomicU32,
 }
 
+impl Clone for Entities {
+    fn clone(&self) -> Self {
+        Self {
+            meta: self.meta.clone(),
+            pending: AtomicU32::new(self.pending.load(Ordering::Relaxed)),
+            free: self.free.clone(),
+            free_cursor: AtomicU32::new(self.free_cursor.load(Ordering::Relaxed)),
+            free_index: self.free_index.clone(),
+            reserved: self
+                .reserved
+                .iter()
+                .map(|x| AtomicU32::new(x.load(Ordering::Relaxed)))
+                .collect(),
+            reserved_cursor: AtomicU32::new(self.reserved_cursor.load(Ordering::Relaxed)),
+        }
+    }
+}
+
 impl Entities {
     /// Reserve an entity ID concurrently
     ///
@@ -146,6 +230,95 @@ impl Entities {
         }
     }
 
+    /// Allocate `n` entities with consecutive IDs, all of the returned generation
+    ///
+    /// Locations should be written immediately.
+    pub fn alloc_many(&mut self, n: u32) -> (Range<u32>, u32) {
+        debug_assert_eq!(
+            self.pending.load(Ordering::Relaxed),
+            0,
+            "allocator must be flushed before potentially growing"
+        );
+        if n == 0 {
+            return (0..0, 0);
+        }
+        let run = match self.free_run(n) {
+            Some(x) => x,
+            None => {
+                // Fresh IDs are pushed onto the free list in order, so they form a run on top
+                self.grow(n);
+                self.free_run(n).unwrap()
+            }
+        };
+        // Not racey due to &mut self
+        self.free_cursor.fetch_sub(n, Ordering::Relaxed);
+        run
+    }
+
+    /// The IDs and generation of the last `n` entries of the free list, if they're consecutive
+    /// and share a generation
+    fn free_run(&self, n: u32) -> Option<(Range<u32>, u32)> {
+        let cursor = self.free_cursor.load(Ordering::Relaxed) as usize;
+        let tail = &self.free[cursor.checked_sub(n as usize)?..cursor];
+        let start = tail[0].min(tail[tail.len() - 1]);
+        let generation = self.meta[start as usize].generation;
+        let ascending = tail.windows(2).all(|x| x[1] == x[0].wrapping_add(1));
+        let descending = tail.windows(2).all(|x| x[0] == x[1].wrapping_add(1));
+        let run = (ascending || descending)
+            && tail
+                .iter()
+                .all(|&id| self.meta[id as usize].generation == generation);
+        if run {
+            Some((start..start + n, generation))
+        } else {
+            None
+        }
+    }
+
+    /// The live entity using the ID `id`, if any
+    ///
+    /// Must not be called while entities are pending.
+    pub fn occupant(&self, id: u32) -> Option<Entity> {
+        let meta = self.meta.get(id as usize)?;
+        if self.is_free(id) {
+            return None;
+        }
+        Some(Entity {
+            generation: meta.generation,
+            id,
+        })
+    }
+
+    fn is_free(&self, id: u32) -> bool {
+        let free_cursor = self.free_cursor.load(Ordering::Relaxed) as usize;
+        let index = self.free_index[id as usize] as usize;
+        index < free_cursor && self.free[index] == id
+    }
+
+    /// Allocate a specific entity ID, which must not be in use
+    ///
+    /// Location should be written immediately.
+    pub fn alloc_at(&mut self, entity: Entity) {
+        debug_assert_eq!(
+            self.pending.load(Ordering::Relaxed),
+            0,
+            "allocator must be flushed before potentially growing"
+        );
+        if entity.id as usize >= self.meta.len() {
+            let len = entity.id.checked_add(1).expect("too many entities");
+            self.grow(len - self.meta.len() as u32);
+        }
+        debug_assert!(self.is_free(entity.id), "ID is in use");
+        // Fill the gap with the ID that would have been allocated next
+        let free_cursor = self.free_cursor.load(Ordering::Relaxed) - 1; // Not racey due to &mut self
+        let index = self.free_index[entity.id as usize];
+        let last = self.free[free_cursor as usize];
+        self.free[index as usize] = last;
+        self.free_index[last as usize] = index;
+        self.free_cursor.store(free_cursor, Ordering::Relaxed);
+        self.meta[entity.id as usize].generation = entity.generation;
+    }
+
     /// Destroy an entity, allowing it to be reused
     ///
     /// Must not be called on reserved entities prior to `flush`.
@@ -160,13 +333,14 @@ impl Entities {
             Location {
                 archetype: 0,
                 // Guard against bugs in reservation handling
-                index: u32::max_value(),
+                index: u32::MAX,
             },
         );
         let index = self.free_cursor.fetch_add(1, Ordering::Relaxed); // Not racey due to &mut self
         self.free[index as usize] = entity.id;
+        self.free_index[entity.id as usize] = index;
         debug_assert!(
-            loc.index != u32::max_value(),
+            loc.index != u32::MAX,
             "free called on reserved entity without flush"
         );
         Ok(loc)
@@ -199,6 +373,9 @@ impl Entities {
         for (i, x) in self.free.iter_mut().enumerate() {
             *x = i as u32;
         }
+        for (i, x) in self.free_index.iter_mut().enumerate() {
+            *x = i as u32;
+        }
         self.pending.store(0, Ordering::Relaxed);
         self.reserved_cursor.store(0, Ordering::Relaxed);
     }
@@ -215,24 +392,18 @@ impl Entities {
         }
     }
 
-    /// Returns `Ok(Location { archetype: 0, index: undefined })` for pending entities
+    /// Returns `Ok(Location { archetype: 0, index: u32::MAX })` for pending entities
     pub fn get(&self, entity: Entity) -> Result<Location, NoSuchEntity> {
         if self.meta.len() <= entity.id as usize {
             return Ok(Location {
                 archetype: 0,
-                index: u32::max_value(),
+                index: u32::MAX,
             });
         }
         let meta = &self.meta[entity.id as usize];
         if meta.generation != entity.generation {
             return Err(NoSuchEntity);
         }
-        if meta.location.archetype == 0 {
-            return Ok(Location {
-                archetype: 0,
-                index: u32::max_value(),
-            });
-        }
         Ok(meta.location)
     }
 
@@ -271,7 +442,9 @@ impl Entities {
         let pending = self.pending.swap(0, Ordering::Relaxed);
         let new_len = (self.meta.len() + pending as usize + increment as usize)
             .max(self.meta.len() * 2)
-            .max(1024);
+            .max(1024)
+            // IDs must fit in a `u32`
+            .min(u32::MAX as usize);
         let mut new_meta = Vec::with_capacity(new_len);
         new_meta.extend_from_slice(&self.meta);
         new_meta.resize(
@@ -280,7 +453,7 @@ impl Entities {
                 generation: 0,
                 location: Location {
                     archetype: 0,
-                    index: u32::max_value(), // dummy value, to be filled in
+                    index: u32::MAX, // dummy value, to be filled in
                 },
             },
         );
@@ -294,11 +467,19 @@ impl Entities {
         self.free_cursor
             .store(new_free.len() as u32, Ordering::Relaxed); // Not racey due to &mut self
 
+        let mut new_free_index = Vec::with_capacity(new_len);
+        new_free_index.extend_from_slice(&self.free_index);
+        new_free_index.resize(new_len, 0);
+        for (index, &id) in new_free.iter().enumerate().skip(free_cursor as usize) {
+            new_free_index[id as usize] = index as u32;
+        }
+
         // Zero-fill
         new_free.resize(new_len, 0);
 
         self.meta = new_meta.into();
         self.free = new_free.into();
+        self.free_index = new_free_index.into();
         let mut new_reserved = Vec::with_capacity(new_len);
         // Not racey due to &mut self
         let reserved_cursor = self.reserved_cursor.load(Ordering::Relaxed);
@@ -310,10 +491,11 @@ impl Entities {
     }
 }
 
+#[doc(hidden)]
 #[derive(Copy, Clone)]
-pub(crate) struct EntityMeta {
-    pub generation: u32,
-    pub location: Location,
+pub struct EntityMeta {
+    pub(crate) generation: u32,
+    pub(crate) location: Location,
 }
 
 #[derive(Copy, Clone)]
@@ -347,4 +529,28 @@ mod tests {
         };
         assert_eq!(Entity::from_bits(e.to_bits()), e);
     }
+
+    #[test]
+    fn alloc_at_free_ids() {
+        let mut entities = Entities::default();
+        let mut taken = Vec::new();
+        for &id in &[7, 2000, 0, 1023, 3] {
+            let entity = Entity { generation: 1, id };
+            entities.alloc_at(entity);
+            entities.meta[id as usize].location.index = 0;
+            taken.push(entity);
+        }
+        let e = taken.pop().unwrap();
+        entities.free(e).unwrap();
+        assert_eq!(entities.occupant(e.id), None);
+        entities.alloc_at(e);
+        assert_eq!(entities.occupant(e.id), Some(e));
+        taken.push(e);
+        let len = entities.meta.len() - taken.len();
+        let mut ids = (0..len).map(|_| entities.alloc().id).collect::<Vec<_>>();
+        ids.extend(taken.iter().map(|e| e.id));
+        ids.sort_unstable();
+        assert!(ids.iter().copied().eq(0..entities.meta.len() as u32));
+        assert_eq!(entities.free_cursor.load(Ordering::Relaxed), 0);
+    }
 }
@@ -15,13 +15,12 @@
 use crate::alloc::alloc::{alloc, dealloc, Layout};
 use crate::alloc::boxed::Box;
 use crate::alloc::{vec, vec::Vec};
-use core::any::TypeId;
 use core::mem::{self, MaybeUninit};
 use core::ptr;
 
 use hashbrown::HashSet;
 
-use crate::archetype::TypeInfo;
+use crate::archetype::{ComponentId, TypeInfo};
 use crate::{Component, DynamicBundle};
 
 /// Helper for incrementally constructing a bundle of components with dynamic component types
@@ -41,8 +40,8 @@ pub struct EntityBuilder {
     storage: Box<[MaybeUninit<u8>]>,
     cursor: usize,
     info: Vec<(TypeInfo, usize)>,
-    ids: Vec<TypeId>,
-    id_set: HashSet<TypeId>,
+    ids: Vec<ComponentId>,
+    id_set: HashSet<ComponentId>,
 }
 
 impl EntityBuilder {
@@ -59,24 +58,59 @@ impl EntityBuilder {
 
     /// Add `component` to the entity
     pub fn add<T: Component>(&mut self, component: T) -> &mut Self {
-        if !self.id_set.insert(TypeId::of::<T>()) {
+        let component = mem::ManuallyDrop::new(component);
+        unsafe { self.add_dynamic((&*component as *const T).cast(), TypeInfo::of::<T>()) }
+    }
+
+    /// Add a component of the runtime-defined type `ty` with the given bytes
+    ///
+    /// Panics if `ty` describes a Rust type, which must be added with `add` instead, or if
+    /// `component` is not exactly the size of `ty`. See `TypeInfo::dynamic`.
+    pub fn add_bytes(&mut self, ty: TypeInfo, component: &[u8]) -> &mut Self {
+        assert!(
+            ty.id().is_dynamic(),
+            "{} is not a runtime-defined type",
+            ty.name()
+        );
+        assert_eq!(
+            component.len(),
+            ty.layout().size(),
+            "wrong size for {}",
+            ty.name()
+        );
+        if self.id_set.contains(&ty.id()) {
+            // `add_dynamic` would drop the borrowed bytes
             return self;
         }
-        let end = self.cursor + mem::size_of::<T>();
+        // Sound by the contract of `TypeInfo::dynamic`
+        unsafe { self.add_dynamic(component.as_ptr(), ty) }
+    }
+
+    /// Add a component of type `ty` by moving it out of `component`
+    ///
+    /// # Safety
+    ///
+    /// `component` must point to a valid value of type `ty`, which must not be used afterwards. If
+    /// a component of the same type was already added, the new one is dropped.
+    pub(crate) unsafe fn add_dynamic(&mut self, component: *const u8, ty: TypeInfo) -> &mut Self {
+        if !self.id_set.insert(ty.id()) {
+            ty.drop(component as *mut u8);
+            return self;
+        }
+        let size = ty.layout().size();
+        let end = self.cursor + size;
         if end > self.storage.len() {
             self.grow(end);
         }
-        if mem::size_of::<T>() != 0 {
-            unsafe {
-                self.storage
-                    .as_mut_ptr()
-                    .add(self.cursor)
-                    .cast::<T>()
-                    .write_unaligned(component);
-            }
+        if size != 0 {
+            ptr::copy_nonoverlapping(
+                component,
+                self.storage.as_mut_ptr().add(self.cursor).cast(),
+                size,
+            );
         }
-        self.info.push((TypeInfo::of::<T>(), self.cursor));
-        self.cursor += mem::size_of::<T>();
+        self.info.push((ty, self.cursor));
+        self.cursor += size;
         self
     }
 
@@ -89,9 +123,22 @@ impl EntityBuilder {
 
     /// Construct a `Bundle` suitable for spawning
     pub fn build(&mut self) -> BuiltEntity<'_> {
+        self.sort();
+        BuiltEntity { builder: self }
+    }
+
+    fn sort(&mut self) {
         self.info.sort_unstable_by_key(|x| x.0);
         self.ids.extend(self.info.iter().map(|x| x.0.id()));
-        BuiltEntity { builder: self }
+    }
+
+    unsafe fn put(&mut self, mut f: impl FnMut(*mut u8, ComponentId, usize) -> bool) {
+        for (ty, offset) in self.info.drain(..) {
+            let ptr = self.storage.as_mut_ptr().add(offset).cast();
+            if !f(ptr, ty.id(), ty.layout().size()) {
+                ty.drop(ptr);
+            }
+        }
     }
 
     /// Drop previously `add`ed components
@@ -160,7 +207,7 @@ pub struct BuiltEntity<'a> {
 }
 
 impl DynamicBundle for BuiltEntity<'_> {
-    fn with_ids<T>(&self, f: impl FnOnce(&[TypeId]) -> T) -> T {
+    fn with_ids<T>(&self, f: impl FnOnce(&[ComponentId]) -> T) -> T {
         f(&self.builder.ids)
     }
 
@@ -169,13 +216,8 @@ impl DynamicBundle for BuiltEntity<'_> {
         self.builder.info.iter().map(|x| x.0).collect()
     }
 
-    unsafe fn put(self, mut f: impl FnMut(*mut u8, TypeId, usize) -> bool) {
-        for (ty, offset) in self.builder.info.drain(..) {
-            let ptr = self.builder.storage.as_mut_ptr().add(offset).cast();
-            if !f(ptr, ty.id(), ty.layout().size()) {
-                ty.drop(ptr);
-            }
-        }
+    unsafe fn put(self, f: impl FnMut(*mut u8, ComponentId, usize) -> bool) {
+        self.builder.put(f);
     }
 }
 
@@ -186,3 +228,33 @@ impl Drop for BuiltEntity<'_> {
         self.builder.clear();
     }
 }
+
+/// The components of an entity removed from a `World` by `World::take`
+///
+/// Can be passed to `World::spawn` or `World::insert` on any world. Components are dropped with
+/// this value if it's never spawned.
+pub struct TakenEntity {
+    builder: EntityBuilder,
+}
+
+impl TakenEntity {
+    pub(crate) fn new(mut builder: EntityBuilder) -> Self {
+        builder.sort();
+        Self { builder }
+    }
+}
+
+impl DynamicBundle for TakenEntity {
+    fn with_ids<T>(&self, f: impl FnOnce(&[ComponentId]) -> T) -> T {
+        f(&self.builder.ids)
+    }
+
+    #[doc(hidden)]
+    fn type_info(&self) -> Vec<TypeInfo> {
+        self.builder.info.iter().map(|x| x.0).collect()
+    }
+
+    unsafe fn put(mut self, f: impl FnMut(*mut u8, ComponentId, usize) -> bool) {
+        self.builder.put(f);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::alloc::collections::VecDeque;
+use crate::alloc::{vec, vec::Vec};
+use core::{fmt, slice};
+#[cfg(feature = "std")]
+use std::error::Error;
+
+use hashbrown::HashMap;
+
+use crate::{Entity, NoSuchEntity};
+
+/// Parent-child relationships between the entities of a `World`, indexed by entity ID
+#[derive(Default, Clone)]
+pub(crate) struct Hierarchy {
+    nodes: HashMap<u32, Node>,
+}
+
+#[derive(Default, Clone)]
+struct Node {
+    parent: Option<Entity>,
+    /// In the order they were attached
+    children: Vec<Entity>,
+}
+
+impl Node {
+    fn is_empty(&self) -> bool {
+        self.parent.is_none() && self.children.is_empty()
+    }
+}
+
+impl Hierarchy {
+    pub fn parent(&self, id: u32) -> Option<Entity> {
+        self.nodes.get(&id)?.parent
+    }
+
+    pub fn children(&self, id: u32) -> &[Entity] {
+        self.nodes.get(&id).map_or(&[], |x| &x.children)
+    }
+
+    /// Whether `ancestor` is `entity` or one of its ancestors
+    fn is_ancestor(&self, ancestor: Entity, entity: Entity) -> bool {
+        entity == ancestor || self.ancestors(entity.id).any(|x| x == ancestor)
+    }
+
+    /// Both entities must be live
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), HierarchyError> {
+        if self.is_ancestor(child, parent) {
+            return Err(HierarchyError::Cycle);
+        }
+        self.remove_parent(child.id);
+        self.nodes.entry(child.id).or_default().parent = Some(parent);
+        self.nodes
+            .entry(parent.id)
+            .or_default()
+            .children
+            .push(child);
+        Ok(())
+    }
+
+    pub fn remove_parent(&mut self, id: u32) -> Option<Entity> {
+        let node = self.nodes.get_mut(&id)?;
+        let parent = node.parent.take()?;
+        if node.is_empty() {
+            self.nodes.remove(&id);
+        }
+        self.detach(parent.id, id);
+        Some(parent)
+    }
+
+    /// Remove the child with ID `id` from `parent`'s children
+    fn detach(&mut self, parent: u32, id: u32) {
+        let node = self.nodes.get_mut(&parent).unwrap();
+        let index = node.children.iter().position(|x| x.id == id).unwrap();
+        node.children.remove(index);
+        if node.is_empty() {
+            self.nodes.remove(&parent);
+        }
+    }
+
+    /// Forget a despawned entity, orphaning its children
+    pub fn remove(&mut self, id: u32) {
+        let node = match self.nodes.remove(&id) {
+            Some(x) => x,
+            None => return,
+        };
+        if let Some(parent) = node.parent {
+            self.detach(parent.id, id);
+        }
+        for child in node.children {
+            let child_node = self.nodes.get_mut(&child.id).unwrap();
+            child_node.parent = None;
+            if child_node.is_empty() {
+                self.nodes.remove(&child.id);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        for node in self.nodes.values_mut() {
+            node.children.shrink_to_fit();
+        }
+        self.nodes.shrink_to_fit();
+    }
+
+    pub fn ancestors(&self, id: u32) -> Ancestors<'_> {
+        Ancestors {
+            hierarchy: self,
+            next: self.parent(id),
+        }
+    }
+
+    pub fn depth_first(&self, id: u32) -> DepthFirst<'_> {
+        DepthFirst {
+            hierarchy: self,
+            stack: vec![self.children(id).iter()],
+        }
+    }
+
+    pub fn breadth_first(&self, id: u32) -> BreadthFirst<'_> {
+        BreadthFirst {
+            hierarchy: self,
+            queue: self.children(id).iter().copied().collect(),
+        }
+    }
+}
+
+/// Iterator over the parent, grandparent, etc. of an entity
+///
+/// Obtained from `World::ancestors`.
+pub struct Ancestors<'a> {
+    hierarchy: &'a Hierarchy,
+    next: Option<Entity>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let entity = self.next?;
+        self.next = self.hierarchy.parent(entity.id);
+        Some(entity)
+    }
+}
+
+/// Iterator over the descendants of an entity, each immediately followed by its own descendants
+///
+/// Obtained from `World::descendants_depth_first`.
+pub struct DepthFirst<'a> {
+    hierarchy: &'a Hierarchy,
+    stack: Vec<slice::Iter<'a, Entity>>,
+}
+
+impl Iterator for DepthFirst<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        loop {
+            let children = self.stack.last_mut()?;
+            match children.next() {
+                Some(&entity) => {
+                    self.stack.push(self.hierarchy.children(entity.id).iter());
+                    return Some(entity);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Iterator over the descendants of an entity, in order of increasing depth
+///
+/// Obtained from `World::descendants_breadth_first`.
+pub struct BreadthFirst<'a> {
+    hierarchy: &'a Hierarchy,
+    queue: VecDeque<Entity>,
+}
+
+impl Iterator for BreadthFirst<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Entity> {
+        let entity = self.queue.pop_front()?;
+        self.queue
+            .extend(self.hierarchy.children(entity.id).iter().copied());
+        Some(entity)
+    }
+}
+
+/// Errors that arise when modifying the hierarchy
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum HierarchyError {
+    /// The child or parent was already despawned
+    NoSuchEntity,
+    /// The parent is the child itself or one of its descendants
+    Cycle,
+}
+
+#[cfg(feature = "std")]
+impl Error for HierarchyError {}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use HierarchyError::*;
+        match *self {
+            NoSuchEntity => f.write_str("no such entity"),
+            Cycle => f.write_str("entity cannot be its own ancestor"),
+        }
+    }
+}
+
+impl From<NoSuchEntity> for HierarchyError {
+    fn from(NoSuchEntity: NoSuchEntity) -> Self {
+        HierarchyError::NoSuchEntity
+    }
+}
@@ -65,29 +65,57 @@ macro_rules! smaller_tuples_too {
 mod archetype;
 mod borrow;
 mod bundle;
+mod clone;
+mod column_batch;
+mod command_buffer;
+mod dynamic;
 mod entities;
 mod entity_builder;
+mod hierarchy;
+mod observer;
+mod prepared_query;
 mod query;
 mod query_one;
+#[cfg(feature = "serde")]
+mod serialize;
+mod sparse;
 mod world;
 
-pub use archetype::Archetype;
-pub use borrow::{EntityRef, Ref, RefMut};
+pub use archetype::{Archetype, ComponentId, TypeInfo};
+pub use borrow::{BorrowError, EntityRef, Ref, RefMut};
 pub use bundle::{Bundle, DynamicBundle, MissingComponent};
-pub use entities::{Entity, NoSuchEntity};
-pub use entity_builder::{BuiltEntity, EntityBuilder};
-pub use query::{Access, BatchedIter, Query, QueryBorrow, QueryIter, With, Without};
+pub use clone::{CloneError, NotCloneable};
+pub use column_batch::ColumnBatch;
+pub use command_buffer::CommandBuffer;
+pub use dynamic::{Column, ColumnMut, DynamicChunk, DynamicQueryIter};
+pub use entities::{Entity, EntityRange, NoSuchEntity};
+pub use entity_builder::{BuiltEntity, EntityBuilder, TakenEntity};
+pub use hierarchy::{Ancestors, BreadthFirst, DepthFirst, HierarchyError};
+pub use prepared_query::{PreparedQuery, PreparedQueryBorrow, PreparedQueryIter};
+pub use query::{
+    Access, Added, BatchedIter, Changed, Mutated, Or, Query, QueryBorrow, QueryChunks, QueryItems,
+    QueryIter, QueryMut, QueryMutItems, QueryShared, Satisfies, View, With, Without,
+};
+#[cfg(feature = "rayon")]
+pub use query::{ParIter, PAR_BATCH_SIZE};
 pub use query_one::QueryOne;
-pub use world::{ArchetypesGeneration, Component, ComponentError, Iter, SpawnBatchIter, World};
+#[cfg(feature = "serde")]
+pub use serialize::{Registry, SerializeColumns, SerializeRows};
+pub use world::{
+    ArchetypesGeneration, Component, ComponentError, Iter, QueryOneError, SpawnBatchIter, World,
+    WorldBuilder,
+};
 
 // Unstable implementation details needed by the macros
 #[doc(hidden)]
-pub use archetype::TypeInfo;
-#[cfg(feature = "macros")]
+pub use entities::EntityMeta;
 #[doc(hidden)]
-pub use lazy_static;
+pub use query::{Fetch, FetchChunk};
 #[doc(hidden)]
-pub use query::Fetch;
+pub mod __private {
+    pub use crate::alloc::vec::Vec;
+    pub use crate::bundle::{sorted_ids, GenericIdCache, IdCache};
+}
 
 #[cfg(feature = "macros")]
-pub use hecs_macros::Bundle;
+pub use hecs_macros::{Bundle, Query};
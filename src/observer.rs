@@ -0,0 +1,142 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::Vec;
+
+use hashbrown::HashMap;
+
+use crate::archetype::{Archetype, ComponentId, TypeInfo};
+use crate::{Component, Entity};
+
+/// Callback invoked with a pointer to a component of the type it was registered for
+type ComponentObserver = Box<dyn FnMut(Entity, *const u8) + Send + Sync>;
+
+/// Callbacks registered through `World::on_add`, `World::on_remove`, and `World::on_despawn`
+#[derive(Default)]
+pub(crate) struct Observers {
+    add: HashMap<ComponentId, Vec<ComponentObserver>>,
+    remove: HashMap<ComponentId, Vec<ComponentObserver>>,
+    despawn: Vec<Box<dyn FnMut(Entity) + Send + Sync>>,
+}
+
+impl Observers {
+    pub fn on_add<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.add
+            .entry(ComponentId::of::<T>())
+            .or_default()
+            .push(erase(f));
+    }
+
+    pub fn on_remove<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.remove
+            .entry(ComponentId::of::<T>())
+            .or_default()
+            .push(erase(f));
+    }
+
+    pub fn on_despawn(&mut self, f: impl FnMut(Entity) + Send + Sync + 'static) {
+        self.despawn.push(Box::new(f));
+    }
+
+    /// Whether no observers would be invoked by `despawned`
+    pub fn ignores_despawn(&self) -> bool {
+        self.remove.is_empty() && self.despawn.is_empty()
+    }
+
+    /// Notify observers that a component of type `ty` and size `size` was added to `entity`
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in bounds, and the component must have been written
+    pub unsafe fn added(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        ty: ComponentId,
+        size: usize,
+    ) {
+        notify(&mut self.add, entity, archetype, index, ty, size);
+    }
+
+    /// Notify observers that every component of `entity` was added
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in bounds, and every component must have been written
+    pub unsafe fn spawned(&mut self, entity: Entity, archetype: &Archetype, index: u32) {
+        if self.add.is_empty() {
+            return;
+        }
+        for ty in archetype.types() {
+            self.added(entity, archetype, index, ty.id(), ty.layout().size());
+        }
+        for set in archetype.sparse_sets().filter(|x| x.contains(entity.id)) {
+            let ty = set.ty();
+            self.added(entity, archetype, index, ty.id(), ty.layout().size());
+        }
+    }
+
+    /// Notify observers that a component of type `ty` is about to be removed from `entity`
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in bounds, and the component must not yet have been dropped
+    pub unsafe fn removed(
+        &mut self,
+        entity: Entity,
+        archetype: &Archetype,
+        index: u32,
+        ty: &TypeInfo,
+    ) {
+        notify(
+            &mut self.remove,
+            entity,
+            archetype,
+            index,
+            ty.id(),
+            ty.layout().size(),
+        );
+    }
+
+    /// Notify observers that `entity` and all its components are about to be destroyed
+    ///
+    /// Observers may panic, so this must be called before the world is modified to reflect the
+    /// despawn.
+    ///
+    /// # Safety
+    ///
+    /// `index` must be in bounds, and no component may have been dropped yet
+    pub unsafe fn despawned(&mut self, entity: Entity, archetype: &Archetype, index: u32) {
+        if !self.remove.is_empty() {
+            for ty in archetype.types() {
+                self.removed(entity, archetype, index, ty);
+            }
+            for set in archetype.sparse_sets().filter(|x| x.contains(entity.id)) {
+                self.removed(entity, archetype, index, set.ty());
+            }
+        }
+        for f in &mut self.despawn {
+            f(entity);
+        }
+    }
+}
+
+fn erase<T: Component>(mut f: impl FnMut(Entity, &T) + Send + Sync + 'static) -> ComponentObserver {
+    // Only ever invoked by `notify`, with a pointer to a live `T`
+    Box::new(move |entity, ptr| f(entity, unsafe { &*ptr.cast::<T>() }))
+}
+
+unsafe fn notify(
+    observers: &mut HashMap<ComponentId, Vec<ComponentObserver>>,
+    entity: Entity,
+    archetype: &Archetype,
+    index: u32,
+    ty: ComponentId,
+    size: usize,
+) {
+    if let Some(observers) = observers.get_mut(&ty) {
+        let (ptr, _) = archetype.locate(ty, size, entity.id, index).unwrap();
+        for f in observers {
+            f(entity, ptr.as_ptr());
+        }
+    }
+}
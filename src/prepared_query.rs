@@ -0,0 +1,257 @@
+use crate::alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::archetype::Archetype;
+use crate::entities::EntityMeta;
+use crate::query::{assert_borrow, count, ChunkIter, Fetch};
+use crate::{Access, ArchetypesGeneration, Entity, Query, World};
+
+/// A query that caches the archetypes it matches for reuse across executions
+///
+/// `QueryBorrow` must inspect every archetype in the `World` each time it's run. A
+/// `PreparedQuery` instead remembers which archetypes `Q` matches, only inspecting archetypes
+/// created since its previous execution, or every archetype again after
+/// `World::remove_empty_archetypes`. This is a win when a query is executed repeatedly against a
+/// world with many archetypes, e.g. once per frame.
+///
+/// A `PreparedQuery` is bound to the first `World` it's executed on, and panics if used with any
+/// other.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let mut query = PreparedQuery::<&mut i32>::new();
+/// assert_eq!(query.query(&world).iter().map(|(_, &mut i)| i).collect::<Vec<_>>(), &[123]);
+/// let b = world.spawn((456,));
+/// for (_, x) in query.query_mut(&mut world) {
+///     *x += 1;
+/// }
+/// assert_eq!(*world.get::<i32>(b).unwrap(), 457);
+/// ```
+pub struct PreparedQuery<Q: Query> {
+    world_id: Option<u64>,
+    generation: Option<ArchetypesGeneration>,
+    /// Number of archetypes inspected so far
+    seen: usize,
+    /// Number of sparse component types when archetypes were last inspected
+    sparse_types: usize,
+    /// `World::archetype_removals` when archetypes were last inspected
+    removals: u64,
+    /// Indices of matching archetypes
+    archetypes: Vec<u32>,
+    _marker: PhantomData<fn(Q)>,
+}
+
+impl<Q: Query> PreparedQuery<Q> {
+    /// Create a prepared query that hasn't yet inspected any archetypes
+    pub fn new() -> Self {
+        Self {
+            world_id: None,
+            generation: None,
+            seen: 0,
+            sparse_types: 0,
+            removals: 0,
+            archetypes: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Borrow `world` sufficiently to execute the query
+    ///
+    /// See `World::query`. Only the cached matching archetypes are borrowed.
+    pub fn query<'q>(&'q mut self, world: &'q World) -> PreparedQueryBorrow<'q, Q> {
+        self.refresh(world);
+        PreparedQueryBorrow::new(
+            world.entities_meta(),
+            world.archetypes_inner(),
+            &self.archetypes,
+        )
+    }
+
+    /// Execute the query on a uniquely borrowed `world`
+    ///
+    /// Faster than `query`, as exclusive access to the world makes dynamic borrow tracking
+    /// unnecessary. Panics if `Q` would alias a component, like `(&mut T, &T)`.
+    pub fn query_mut<'q>(&'q mut self, world: &'q mut World) -> PreparedQueryIter<'q, Q> {
+        self.refresh(world);
+        let archetypes = world.archetypes_inner();
+        for &index in &self.archetypes {
+            assert_borrow::<Q>(&archetypes[index as usize]);
+        }
+        unsafe { PreparedQueryIter::new(world.entities_meta(), archetypes, &self.archetypes) }
+    }
+
+    /// Inspect archetypes created since the previous execution
+    fn refresh(&mut self, world: &World) {
+        match self.world_id {
+            None => self.world_id = Some(world.id()),
+            Some(id) => assert_eq!(
+                id,
+                world.id(),
+                "prepared query executed on a different world than it was prepared for"
+            ),
+        }
+        if self.generation == Some(world.archetypes_generation()) {
+            return;
+        }
+        if self.sparse_types != world.sparse_types() || self.removals != world.archetype_removals()
+        {
+            // Existing archetypes may now match, or be found at different indices
+            self.sparse_types = world.sparse_types();
+            self.removals = world.archetype_removals();
+            self.seen = 0;
+            self.archetypes.clear();
+        }
+        let archetypes = world.archetypes_inner();
+        for (index, archetype) in archetypes.iter().enumerate().skip(self.seen) {
+            if Q::Fetch::access(archetype).is_some() {
+                self.archetypes.push(index as u32);
+            }
+        }
+        self.seen = archetypes.len();
+        self.generation = Some(world.archetypes_generation());
+    }
+}
+
+impl<Q: Query> Default for PreparedQuery<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A borrow of a `World` sufficient to execute a `PreparedQuery`
+///
+/// Note that borrows are not released until this object is dropped.
+pub struct PreparedQueryBorrow<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetypes: &'q [Archetype],
+    matching: &'q [u32],
+    _marker: PhantomData<Q>,
+}
+
+impl<'q, Q: Query> PreparedQueryBorrow<'q, Q> {
+    fn new(meta: &'q [EntityMeta], archetypes: &'q [Archetype], matching: &'q [u32]) -> Self {
+        for &index in matching {
+            let archetype = &archetypes[index as usize];
+            if Q::Fetch::access(archetype) >= Some(Access::Read) {
+                Q::Fetch::borrow(archetype);
+            }
+        }
+        Self {
+            meta,
+            archetypes,
+            matching,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Execute the query
+    pub fn iter<'i>(&'i mut self) -> PreparedQueryIter<'i, Q> {
+        unsafe { PreparedQueryIter::new(self.meta, self.archetypes, self.matching) }
+    }
+}
+
+unsafe impl<'q, Q: Query> Send for PreparedQueryBorrow<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for PreparedQueryBorrow<'q, Q> {}
+
+impl<'q, Q: Query> Drop for PreparedQueryBorrow<'q, Q> {
+    fn drop(&mut self) {
+        for &index in self.matching {
+            let archetype = &self.archetypes[index as usize];
+            if Q::Fetch::access(archetype) >= Some(Access::Read) {
+                Q::Fetch::release(archetype);
+            }
+        }
+    }
+}
+
+impl<'i, 'q, Q: Query> IntoIterator for &'i mut PreparedQueryBorrow<'q, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'i>>::Item);
+    type IntoIter = PreparedQueryIter<'i, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the set of entities matched by a `PreparedQuery`
+pub struct PreparedQueryIter<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetypes: &'q [Archetype],
+    matching: core::slice::Iter<'q, u32>,
+    iter: Option<ChunkIter<Q>>,
+}
+
+impl<'q, Q: Query> PreparedQueryIter<'q, Q> {
+    /// # Safety
+    ///
+    /// `Q` must be borrowed from every archetype in `matching` for `'q`
+    unsafe fn new(
+        meta: &'q [EntityMeta],
+        archetypes: &'q [Archetype],
+        matching: &'q [u32],
+    ) -> Self {
+        Self {
+            meta,
+            archetypes,
+            matching: matching.iter(),
+            iter: None,
+        }
+    }
+}
+
+unsafe impl<'q, Q: Query> Send for PreparedQueryIter<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for PreparedQueryIter<'q, Q> {}
+
+impl<'q, Q: Query> Iterator for PreparedQueryIter<'q, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.iter {
+                None => {
+                    let archetype = &self.archetypes[*self.matching.next()? as usize];
+                    unsafe {
+                        self.iter = ChunkIter::new(self.meta, archetype, 0..archetype.len());
+                    }
+                }
+                Some(ref mut iter) => match unsafe { iter.next() } {
+                    None => {
+                        if !unsafe { iter.next_chunk() } {
+                            self.iter = None;
+                        }
+                        continue;
+                    }
+                    Some((id, components)) => {
+                        return Some((
+                            Entity {
+                                id,
+                                generation: self.meta[id as usize].generation,
+                            },
+                            components,
+                        ));
+                    }
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for PreparedQueryIter<'q, Q> {
+    fn len(&self) -> usize {
+        self.iter.as_ref().map_or(0, |x| x.remaining())
+            + self
+                .matching
+                .clone()
+                .map(|&index| count::<Q>(self.meta, &self.archetypes[index as usize]))
+                .sum::<usize>()
+    }
+}
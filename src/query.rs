@@ -12,11 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::alloc::vec::Vec;
 use core::marker::PhantomData;
+use core::ops::Range;
 use core::ptr::NonNull;
+use core::slice;
 
 use crate::archetype::Archetype;
+use crate::borrow::BorrowError;
 use crate::entities::EntityMeta;
+use crate::sparse::SparseSet;
 use crate::{Component, Entity};
 
 /// A collection of component types to fetch from a `World`
@@ -25,6 +30,16 @@ pub trait Query {
     type Fetch: for<'a> Fetch<'a>;
 }
 
+/// A `Query` that never uniquely borrows components
+///
+/// Allows a single entity to be accessed by several results of the query at once, e.g. through
+/// `View::get`.
+///
+/// # Safety
+///
+/// The `Fetch` of implementing types must never produce unique references.
+pub unsafe trait QueryShared: Query {}
+
 /// Streaming iterators over contiguous homogeneous ranges of components
 pub trait Fetch<'a>: Sized {
     /// Type of value to be fetched
@@ -33,24 +48,75 @@ pub trait Fetch<'a>: Sized {
     /// How this query will access `archetype`, if at all
     fn access(archetype: &Archetype) -> Option<Access>;
 
-    /// Acquire dynamic borrows from `archetype`
-    fn borrow(archetype: &Archetype);
+    /// Acquire dynamic borrows from `archetype`, panicking on conflict
+    fn borrow(archetype: &Archetype) {
+        if let Err(e) = Self::try_borrow(archetype) {
+            panic!("{}", e);
+        }
+    }
+    /// Acquire dynamic borrows from `archetype`, or none at all if any would conflict
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError>;
     /// Construct a `Fetch` for `archetype` if it should be traversed
     ///
+    /// The result only covers the entities from `offset` to the end of the chunk of storage
+    /// containing it; see `World::with_chunk_size`.
+    ///
     /// # Safety
-    /// `offset` must be in bounds of `archetype`
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self>;
+    /// `offset` must be in bounds of `archetype`, and `meta` must describe every entity in it
+    unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self>;
     /// Release dynamic borrows acquired by `borrow`
     fn release(archetype: &Archetype);
 
-    /// Access the next item in this archetype without bounds checking
+    /// Whether the `n`th item after `offset` should be excluded from the results
+    ///
+    /// # Safety
+    /// Bounds-checking must be performed externally
+    unsafe fn should_skip(&self, n: usize) -> bool {
+        let _ = n;
+        false
+    }
+
+    /// Whether `should_skip` may be true for any item after `offset`
+    ///
+    /// If not, results can be counted without visiting each entity. Conservatively defaults to
+    /// `true`.
+    fn may_skip(&self) -> bool {
+        true
+    }
+
+    /// Whether the `n`th item after `offset` lacks a component the query requires
+    ///
+    /// Unlike `should_skip`, ignores filters like `Added` that depend on more than which components
+    /// an entity has. Only ever true for entities missing a sparse component, as archetypes
+    /// otherwise decide which components are present.
+    ///
+    /// # Safety
+    /// Bounds-checking must be performed externally
+    unsafe fn is_missing(&self, n: usize) -> bool {
+        let _ = n;
+        false
+    }
+
+    /// Access the `n`th item after `offset` without bounds checking
     ///
     /// # Safety
     /// - Must only be called after `borrow`
     /// - `release` must not be called while `'a` is still live
     /// - Bounds-checking must be performed externally
     /// - Any resulting borrows must be legal (e.g. no &mut to something another iterator might access)
-    unsafe fn next(&mut self) -> Self::Item;
+    unsafe fn fetch(&self, n: usize) -> Self::Item;
+}
+
+/// A `Fetch` that can produce the results for many consecutive entities at once, as slices
+pub trait FetchChunk<'a>: Fetch<'a> {
+    /// Type of the results for a run of entities
+    type Chunk;
+
+    /// Access the items of the first `len` entities from the offset passed to `Fetch::get`
+    ///
+    /// # Safety
+    /// Subject to the same requirements as `Fetch::fetch`, for every item in the run
+    unsafe fn fetch_chunk(&self, len: usize) -> Self::Chunk;
 }
 
 /// Type of access a `Query` may have to an `Archetype`
@@ -64,77 +130,278 @@ pub enum Access {
     Write,
 }
 
-impl<'a, T: Component> Query for &'a T {
+impl<T: Component> Query for &T {
     type Fetch = FetchRead<T>;
 }
 
+unsafe impl<T: Component> QueryShared for &T {}
+
 #[doc(hidden)]
-pub struct FetchRead<T>(NonNull<T>);
+pub struct FetchRead<T>(Storage<T>);
 
 impl<'a, T: Component> Fetch<'a> for FetchRead<T> {
     type Item = &'a T;
 
     fn access(archetype: &Archetype) -> Option<Access> {
-        if archetype.has::<T>() {
+        if Storage::<T>::exists(archetype) {
             Some(Access::Read)
         } else {
             None
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        archetype.borrow::<T>();
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-        archetype
-            .get::<T>()
-            .map(|x| Self(NonNull::new_unchecked(x.as_ptr().add(offset))))
+    unsafe fn get(
+        _meta: &'a [EntityMeta],
+        archetype: &'a Archetype,
+        offset: usize,
+    ) -> Option<Self> {
+        Storage::get(archetype, offset).map(Self)
     }
     fn release(archetype: &Archetype) {
         archetype.release::<T>();
     }
 
-    unsafe fn next(&mut self) -> &'a T {
-        let x = self.0.as_ptr();
-        self.0 = NonNull::new_unchecked(x.add(1));
-        &*x
+    unsafe fn should_skip(&self, n: usize) -> bool {
+        self.0.index(n).is_none()
+    }
+
+    fn may_skip(&self) -> bool {
+        self.0.sparse.is_some()
+    }
+
+    unsafe fn is_missing(&self, n: usize) -> bool {
+        self.0.index(n).is_none()
+    }
+
+    unsafe fn fetch(&self, n: usize) -> &'a T {
+        &*self.0.data.as_ptr().add(self.0.index_unchecked(n))
+    }
+}
+
+impl<'a, T: Component> FetchChunk<'a> for FetchRead<T> {
+    type Chunk = &'a [T];
+
+    unsafe fn fetch_chunk(&self, len: usize) -> &'a [T] {
+        self.0.assert_dense();
+        slice::from_raw_parts(self.0.data.as_ptr(), len)
     }
 }
 
-impl<'a, T: Component> Query for &'a mut T {
+impl<T: Component> Query for &mut T {
     type Fetch = FetchWrite<T>;
 }
 
 #[doc(hidden)]
-pub struct FetchWrite<T>(NonNull<T>);
+pub struct FetchWrite<T> {
+    storage: Storage<T>,
+    tick: u32,
+}
 
 impl<'a, T: Component> Fetch<'a> for FetchWrite<T> {
     type Item = &'a mut T;
 
     fn access(archetype: &Archetype) -> Option<Access> {
-        if archetype.has::<T>() {
+        if Storage::<T>::exists(archetype) {
             Some(Access::Write)
         } else {
             None
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        archetype.borrow_mut::<T>();
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow_mut::<T>()
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-        archetype
-            .get::<T>()
-            .map(|x| Self(NonNull::new_unchecked(x.as_ptr().add(offset))))
+    unsafe fn get(
+        _meta: &'a [EntityMeta],
+        archetype: &'a Archetype,
+        offset: usize,
+    ) -> Option<Self> {
+        Some(Self {
+            storage: Storage::get(archetype, offset)?,
+            tick: archetype.tick(),
+        })
     }
     fn release(archetype: &Archetype) {
         archetype.release_mut::<T>();
     }
 
-    unsafe fn next(&mut self) -> &'a mut T {
-        let x = self.0.as_ptr();
-        self.0 = NonNull::new_unchecked(x.add(1));
-        &mut *x
+    unsafe fn should_skip(&self, n: usize) -> bool {
+        self.storage.index(n).is_none()
+    }
+
+    fn may_skip(&self) -> bool {
+        self.storage.sparse.is_some()
+    }
+
+    unsafe fn is_missing(&self, n: usize) -> bool {
+        self.storage.index(n).is_none()
+    }
+
+    unsafe fn fetch(&self, n: usize) -> &'a mut T {
+        let index = self.storage.index_unchecked(n);
+        *self.storage.mutated.as_ptr().add(index) = self.tick;
+        &mut *self.storage.data.as_ptr().add(index)
+    }
+}
+
+impl<'a, T: Component> FetchChunk<'a> for FetchWrite<T> {
+    type Chunk = &'a mut [T];
+
+    unsafe fn fetch_chunk(&self, len: usize) -> &'a mut [T] {
+        self.storage.assert_dense();
+        slice::from_raw_parts_mut(self.storage.mutated.as_ptr(), len).fill(self.tick);
+        slice::from_raw_parts_mut(self.storage.data.as_ptr(), len)
+    }
+}
+
+/// Components of type `T`, and their change ticks, belonging to the entities of an archetype from
+/// some offset to the end of its chunk
+struct Storage<T> {
+    data: NonNull<T>,
+    added: NonNull<u32>,
+    mutated: NonNull<u32>,
+    /// Set if `T` is sparse, in which case the above point to the start of the sparse set, rather
+    /// than to the component of the entity at the offset
+    sparse: Option<Sparse>,
+}
+
+impl<T: Component> Storage<T> {
+    /// Whether entities in `archetype` may have a `T`
+    fn exists(archetype: &Archetype) -> bool {
+        archetype.has::<T>() || archetype.has_sparse::<T>()
+    }
+
+    unsafe fn get(archetype: &Archetype, offset: usize) -> Option<Self> {
+        if let Some(data) = archetype.get::<T>(offset as u32) {
+            return Some(Self {
+                data,
+                added: archetype.get_added::<T>(offset as u32)?,
+                mutated: archetype.get_mutated::<T>(offset as u32)?,
+                sparse: None,
+            });
+        }
+        let set = archetype.get_sparse::<T>()?;
+        Some(Self {
+            data: set.data().cast::<T>(),
+            added: set.added(),
+            mutated: set.mutated(),
+            sparse: Some(Sparse::new(archetype, set, offset)),
+        })
+    }
+
+    /// Index relative to the pointers of the component of the `n`th entity, if it has one
+    #[inline]
+    unsafe fn index(&self, n: usize) -> Option<usize> {
+        match self.sparse {
+            None => Some(n),
+            Some(ref sparse) => sparse.slot(n),
+        }
+    }
+
+    /// Like `index`, for an entity known to have a component
+    #[inline]
+    unsafe fn index_unchecked(&self, n: usize) -> usize {
+        match self.sparse {
+            None => n,
+            Some(ref sparse) => sparse.slot(n).unwrap_unchecked(),
+        }
+    }
+
+    fn assert_dense(&self) {
+        Sparse::assert_dense::<T>(self.sparse);
+    }
+}
+
+/// Membership of the entities of an archetype from some offset onwards in a sparse set
+#[derive(Copy, Clone)]
+struct Sparse {
+    ids: NonNull<u32>,
+    set: NonNull<SparseSet>,
+}
+
+impl Sparse {
+    unsafe fn new(archetype: &Archetype, set: &SparseSet, offset: usize) -> Self {
+        Self {
+            ids: NonNull::new_unchecked(archetype.entities().as_ptr().add(offset)),
+            set: NonNull::from(set),
+        }
+    }
+
+    /// Slot in the set of the component of the `n`th entity, if it has one
+    #[inline]
+    unsafe fn slot(&self, n: usize) -> Option<usize> {
+        let id = *self.ids.as_ptr().add(n);
+        self.set.as_ref().slot(id).map(|x| x as usize)
+    }
+
+    fn assert_dense<T>(sparse: Option<Self>) {
+        assert!(
+            sparse.is_none(),
+            "{} is sparse, so can't be fetched in chunks",
+            core::any::type_name::<T>()
+        );
+    }
+}
+
+/// Query that yields the `Entity` being visited, for use alongside other queries
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456, true));
+/// let entities = world.query::<(Entity, &i32)>()
+///     .iter()
+///     .map(|(_, (e, &i))| (e, i))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities.len(), 2);
+/// assert!(entities.contains(&(a, 123)));
+/// assert!(entities.contains(&(b, 456)));
+/// ```
+impl Query for Entity {
+    type Fetch = FetchEntity;
+}
+
+unsafe impl QueryShared for Entity {}
+
+#[doc(hidden)]
+pub struct FetchEntity {
+    entities: NonNull<u32>,
+    meta: NonNull<EntityMeta>,
+}
+
+impl<'a> Fetch<'a> for FetchEntity {
+    type Item = Entity;
+
+    fn access(_archetype: &Archetype) -> Option<Access> {
+        Some(Access::Iterate)
+    }
+
+    fn try_borrow(_archetype: &Archetype) -> Result<(), BorrowError> {
+        Ok(())
+    }
+    unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self> {
+        Some(Self {
+            entities: NonNull::new_unchecked(archetype.entities().as_ptr().add(offset)),
+            meta: NonNull::new_unchecked(meta.as_ptr() as *mut EntityMeta),
+        })
+    }
+    fn release(_archetype: &Archetype) {}
+
+    fn may_skip(&self) -> bool {
+        false
+    }
+
+    unsafe fn fetch(&self, n: usize) -> Entity {
+        let id = *self.entities.as_ptr().add(n);
+        Entity {
+            id,
+            generation: (*self.meta.as_ptr().add(id as usize)).generation,
+        }
     }
 }
 
@@ -142,6 +409,8 @@ impl<T: Query> Query for Option<T> {
     type Fetch = TryFetch<T::Fetch>;
 }
 
+unsafe impl<T: QueryShared> QueryShared for Option<T> {}
+
 #[doc(hidden)]
 pub struct TryFetch<T>(Option<T>);
 
@@ -152,18 +421,34 @@ impl<'a, T: Fetch<'a>> Fetch<'a> for TryFetch<T> {
         Some(T::access(archetype).unwrap_or(Access::Iterate))
     }
 
-    fn borrow(archetype: &Archetype) {
-        T::borrow(archetype)
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        T::try_borrow(archetype)
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-        Some(Self(T::get(archetype, offset)))
+    unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self> {
+        Some(Self(T::get(meta, archetype, offset)))
     }
     fn release(archetype: &Archetype) {
         T::release(archetype)
     }
 
-    unsafe fn next(&mut self) -> Option<T::Item> {
-        Some(self.0.as_mut()?.next())
+    fn may_skip(&self) -> bool {
+        false
+    }
+
+    unsafe fn fetch(&self, n: usize) -> Option<T::Item> {
+        let fetch = self.0.as_ref()?;
+        if fetch.should_skip(n) {
+            return None;
+        }
+        Some(fetch.fetch(n))
+    }
+}
+
+impl<'a, T: FetchChunk<'a>> FetchChunk<'a> for TryFetch<T> {
+    type Chunk = Option<T::Chunk>;
+
+    unsafe fn fetch_chunk(&self, len: usize) -> Option<T::Chunk> {
+        Some(self.0.as_ref()?.fetch_chunk(len))
     }
 }
 
@@ -190,8 +475,10 @@ impl<T: Component, Q: Query> Query for Without<T, Q> {
     type Fetch = FetchWithout<T, Q::Fetch>;
 }
 
+unsafe impl<T: Component, Q: QueryShared> QueryShared for Without<T, Q> {}
+
 #[doc(hidden)]
-pub struct FetchWithout<T, F>(F, PhantomData<fn(T)>);
+pub struct FetchWithout<T, F>(F, Option<Sparse>, PhantomData<fn(T)>);
 
 impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWithout<T, F> {
     type Item = F::Item;
@@ -204,21 +491,45 @@ impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWithout<T, F> {
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        F::borrow(archetype)
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        F::try_borrow(archetype)
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
+    unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self> {
         if archetype.has::<T>() {
             return None;
         }
-        Some(Self(F::get(archetype, offset)?, PhantomData))
+        let sparse = archetype
+            .get_sparse::<T>()
+            .map(|set| Sparse::new(archetype, set, offset));
+        Some(Self(F::get(meta, archetype, offset)?, sparse, PhantomData))
     }
     fn release(archetype: &Archetype) {
         F::release(archetype)
     }
 
-    unsafe fn next(&mut self) -> F::Item {
-        self.0.next()
+    unsafe fn should_skip(&self, n: usize) -> bool {
+        self.is_missing(n) || self.0.should_skip(n)
+    }
+
+    fn may_skip(&self) -> bool {
+        self.1.is_some() || self.0.may_skip()
+    }
+
+    unsafe fn is_missing(&self, n: usize) -> bool {
+        self.1.is_some_and(|x| x.slot(n).is_some()) || self.0.is_missing(n)
+    }
+
+    unsafe fn fetch(&self, n: usize) -> F::Item {
+        self.0.fetch(n)
+    }
+}
+
+impl<'a, T: Component, F: FetchChunk<'a>> FetchChunk<'a> for FetchWithout<T, F> {
+    type Chunk = F::Chunk;
+
+    unsafe fn fetch_chunk(&self, len: usize) -> F::Chunk {
+        Sparse::assert_dense::<T>(self.1);
+        self.0.fetch_chunk(len)
     }
 }
 
@@ -247,134 +558,627 @@ impl<T: Component, Q: Query> Query for With<T, Q> {
     type Fetch = FetchWith<T, Q::Fetch>;
 }
 
+unsafe impl<T: Component, Q: QueryShared> QueryShared for With<T, Q> {}
+
 #[doc(hidden)]
-pub struct FetchWith<T, F>(F, PhantomData<fn(T)>);
+pub struct FetchWith<T, F>(F, Option<Sparse>, PhantomData<fn(T)>);
 
 impl<'a, T: Component, F: Fetch<'a>> Fetch<'a> for FetchWith<T, F> {
     type Item = F::Item;
 
     fn access(archetype: &Archetype) -> Option<Access> {
-        if archetype.has::<T>() {
+        if Storage::<T>::exists(archetype) {
             F::access(archetype)
         } else {
             None
         }
     }
 
-    fn borrow(archetype: &Archetype) {
-        F::borrow(archetype)
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        F::try_borrow(archetype)
     }
-    unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-        if !archetype.has::<T>() {
-            return None;
-        }
-        Some(Self(F::get(archetype, offset)?, PhantomData))
+    unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self> {
+        let sparse = if archetype.has::<T>() {
+            None
+        } else {
+            Some(Sparse::new(archetype, archetype.get_sparse::<T>()?, offset))
+        };
+        Some(Self(F::get(meta, archetype, offset)?, sparse, PhantomData))
     }
     fn release(archetype: &Archetype) {
         F::release(archetype)
     }
 
-    unsafe fn next(&mut self) -> F::Item {
-        self.0.next()
+    unsafe fn should_skip(&self, n: usize) -> bool {
+        self.is_missing(n) || self.0.should_skip(n)
+    }
+
+    fn may_skip(&self) -> bool {
+        self.1.is_some() || self.0.may_skip()
+    }
+
+    unsafe fn is_missing(&self, n: usize) -> bool {
+        self.1.is_some_and(|x| x.slot(n).is_none()) || self.0.is_missing(n)
+    }
+
+    unsafe fn fetch(&self, n: usize) -> F::Item {
+        self.0.fetch(n)
     }
 }
 
-/// A borrow of a `World` sufficient to execute the query `Q`
+impl<'a, T: Component, F: FetchChunk<'a>> FetchChunk<'a> for FetchWith<T, F> {
+    type Chunk = F::Chunk;
+
+    unsafe fn fetch_chunk(&self, len: usize) -> F::Chunk {
+        Sparse::assert_dense::<T>(self.1);
+        self.0.fetch_chunk(len)
+    }
+}
+
+/// Query that matches entities satisfying at least one of the queries in the tuple `T`
 ///
-/// Note that borrows are not released until this object is dropped.
-pub struct QueryBorrow<'w, Q: Query> {
-    meta: &'w [EntityMeta],
-    archetypes: &'w [Archetype],
-    borrowed: bool,
-    _marker: PhantomData<Q>,
+/// Yields a tuple with an `Option` for each member query, which is `Some` for exactly those
+/// members the entity satisfies. Only components of members matching a given archetype are
+/// borrowed.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456,));
+/// let c = world.spawn((false,));
+/// let d = world.spawn(("abc",));
+/// let mut entities = world.query::<Or<(&i32, &bool)>>()
+///     .iter()
+///     .map(|(e, (i, b))| (e, i.copied(), b.copied()))
+///     .collect::<Vec<_>>();
+/// entities.sort();
+/// assert_eq!(entities, &[(a, Some(123), Some(true)), (b, Some(456), None), (c, None, Some(false))]);
+/// ```
+pub struct Or<T>(PhantomData<T>);
+
+#[doc(hidden)]
+pub struct FetchOr<T>(T);
+
+/// Query that yields whether an entity satisfies `Q`, without borrowing any components
+///
+/// Matches every entity. Only the component types `Q` requires are considered, so per-entity
+/// filters like `Added` evaluate to `true` for every entity having the filtered component.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456,));
+/// let mut entities = world.query::<(&i32, Satisfies<&bool>)>()
+///     .iter()
+///     .map(|(e, (&i, b))| (e, i, b))
+///     .collect::<Vec<_>>();
+/// entities.sort();
+/// assert_eq!(entities, &[(a, 123, true), (b, 456, false)]);
+/// ```
+pub struct Satisfies<Q>(PhantomData<Q>);
+
+impl<Q: Query> Query for Satisfies<Q> {
+    type Fetch = FetchSatisfies<Q::Fetch>;
 }
 
-impl<'w, Q: Query> QueryBorrow<'w, Q> {
-    pub(crate) fn new(meta: &'w [EntityMeta], archetypes: &'w [Archetype]) -> Self {
-        Self {
-            meta,
-            archetypes,
-            borrowed: false,
-            _marker: PhantomData,
-        }
+unsafe impl<Q: Query> QueryShared for Satisfies<Q> {}
+
+#[doc(hidden)]
+pub struct FetchSatisfies<F>(Option<F>);
+
+impl<'a, F: Fetch<'a>> Fetch<'a> for FetchSatisfies<F> {
+    type Item = bool;
+
+    fn access(_archetype: &Archetype) -> Option<Access> {
+        Some(Access::Iterate)
     }
 
-    /// Execute the query
-    ///
-    /// Must be called only once per query.
-    pub fn iter<'q>(&'q mut self) -> QueryIter<'q, 'w, Q> {
-        self.borrow();
-        QueryIter {
-            borrow: self,
-            archetype_index: 0,
-            iter: None,
-        }
+    fn try_borrow(_archetype: &Archetype) -> Result<(), BorrowError> {
+        Ok(())
+    }
+    unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self> {
+        Some(Self(F::get(meta, archetype, offset)))
     }
+    fn release(_archetype: &Archetype) {}
 
-    /// Like `iter`, but returns child iterators of at most `batch_size` elements
-    ///
-    /// Useful for distributing work over a threadpool.
-    pub fn iter_batched<'q>(&'q mut self, batch_size: u32) -> BatchedIter<'q, 'w, Q> {
-        self.borrow();
-        BatchedIter {
-            borrow: self,
-            archetype_index: 0,
-            batch_size,
-            batch: 0,
-        }
+    fn may_skip(&self) -> bool {
+        false
     }
 
-    fn borrow(&mut self) {
-        if self.borrowed {
-            panic!(
-                "called QueryBorrow::iter twice on the same borrow; construct a new query instead"
-            );
-        }
-        for x in self.archetypes {
-            // TODO: Release prior borrows on failure?
-            if Q::Fetch::access(x) >= Some(Access::Read) {
-                Q::Fetch::borrow(x);
-            }
-        }
-        self.borrowed = true;
+    unsafe fn fetch(&self, n: usize) -> bool {
+        self.0.as_ref().is_some_and(|x| !x.is_missing(n))
     }
+}
 
-    /// Transform the query into one that requires a certain component without borrowing it
-    ///
-    /// This can be useful when the component needs to be borrowed elsewhere and it isn't necessary
-    /// for the iterator to expose its data directly.
-    ///
-    /// Equivalent to using a query type wrapped in `With`.
-    ///
-    /// # Example
-    /// ```
-    /// # use hecs::*;
-    /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
-    /// let c = world.spawn((42, "def"));
-    /// let entities = world.query::<&i32>()
-    ///     .with::<bool>()
-    ///     .iter()
-    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
-    ///     .collect::<Vec<_>>();
-    /// assert!(entities.contains(&(a, 123)));
-    /// assert!(entities.contains(&(b, 456)));
-    /// ```
-    pub fn with<T: Component>(self) -> QueryBorrow<'w, With<T, Q>> {
-        self.transform()
+impl<'a, F: Fetch<'a>> FetchChunk<'a> for FetchSatisfies<F> {
+    type Chunk = bool;
+
+    unsafe fn fetch_chunk(&self, len: usize) -> bool {
+        let result = self.0.is_some() && (len == 0 || self.fetch(0));
+        assert!(
+            (1..len).all(|n| self.fetch(n) == result),
+            "Satisfies is not uniform across an archetype with sparse components, so can't be \
+             fetched in chunks"
+        );
+        result
     }
+}
 
-    /// Transform the query into one that skips entities having a certain component
-    ///
-    /// Equivalent to using a query type wrapped in `Without`.
-    ///
-    /// # Example
-    /// ```
-    /// # use hecs::*;
-    /// let mut world = World::new();
-    /// let a = world.spawn((123, true, "abc"));
-    /// let b = world.spawn((456, false));
+/// Query that yields `&T` only for components added since the last `World::clear_trackers` call
+///
+/// Components are considered added when their entity is spawned or when they are inserted into an
+/// entity that did not already have a component of the same type.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// world.clear_trackers();
+/// let b = world.spawn((456,));
+/// let entities = world.query::<Added<i32>>()
+///     .iter()
+///     .map(|(e, &i)| (e, i))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities, &[(b, 456)]);
+/// ```
+pub struct Added<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Added<T> {
+    type Fetch = FetchAdded<T>;
+}
+
+unsafe impl<T: Component> QueryShared for Added<T> {}
+
+#[doc(hidden)]
+pub struct FetchAdded<T> {
+    storage: Storage<T>,
+    tick: u32,
+}
+
+impl<'a, T: Component> Fetch<'a> for FetchAdded<T> {
+    type Item = &'a T;
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if Storage::<T>::exists(archetype) {
+            Some(Access::Read)
+        } else {
+            None
+        }
+    }
+
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
+    }
+    unsafe fn get(
+        _meta: &'a [EntityMeta],
+        archetype: &'a Archetype,
+        offset: usize,
+    ) -> Option<Self> {
+        Some(Self {
+            storage: Storage::get(archetype, offset)?,
+            tick: archetype.tick(),
+        })
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<T>();
+    }
+
+    unsafe fn should_skip(&self, n: usize) -> bool {
+        match self.storage.index(n) {
+            Some(index) => *self.storage.added.as_ptr().add(index) != self.tick,
+            None => true,
+        }
+    }
+
+    unsafe fn is_missing(&self, n: usize) -> bool {
+        self.storage.index(n).is_none()
+    }
+
+    unsafe fn fetch(&self, n: usize) -> &'a T {
+        &*self
+            .storage
+            .data
+            .as_ptr()
+            .add(self.storage.index_unchecked(n))
+    }
+}
+
+/// Query that yields `&T` only for components uniquely borrowed since the last
+/// `World::clear_trackers` call
+///
+/// Components are considered mutated whenever they're accessed through a `&mut T` query, a
+/// `RefMut`, or replaced by `World::insert`, regardless of whether their value actually changed.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// world.clear_trackers();
+/// *world.get_mut::<i32>(b).unwrap() += 1;
+/// let entities = world.query::<Mutated<i32>>()
+///     .iter()
+///     .map(|(e, &i)| (e, i))
+///     .collect::<Vec<_>>();
+/// assert_eq!(entities, &[(b, 457)]);
+/// ```
+pub struct Mutated<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Mutated<T> {
+    type Fetch = FetchMutated<T>;
+}
+
+unsafe impl<T: Component> QueryShared for Mutated<T> {}
+
+#[doc(hidden)]
+pub struct FetchMutated<T> {
+    storage: Storage<T>,
+    tick: u32,
+}
+
+impl<'a, T: Component> Fetch<'a> for FetchMutated<T> {
+    type Item = &'a T;
+
+    fn access(archetype: &Archetype) -> Option<Access> {
+        if Storage::<T>::exists(archetype) {
+            Some(Access::Read)
+        } else {
+            None
+        }
+    }
+
+    fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+        archetype.try_borrow::<T>()
+    }
+    unsafe fn get(
+        _meta: &'a [EntityMeta],
+        archetype: &'a Archetype,
+        offset: usize,
+    ) -> Option<Self> {
+        Some(Self {
+            storage: Storage::get(archetype, offset)?,
+            tick: archetype.tick(),
+        })
+    }
+    fn release(archetype: &Archetype) {
+        archetype.release::<T>();
+    }
+
+    unsafe fn should_skip(&self, n: usize) -> bool {
+        match self.storage.index(n) {
+            Some(index) => *self.storage.mutated.as_ptr().add(index) != self.tick,
+            None => true,
+        }
+    }
+
+    unsafe fn is_missing(&self, n: usize) -> bool {
+        self.storage.index(n).is_none()
+    }
+
+    unsafe fn fetch(&self, n: usize) -> &'a T {
+        &*self
+            .storage
+            .data
+            .as_ptr()
+            .add(self.storage.index_unchecked(n))
+    }
+}
+
+/// Query that yields `&T` only for components that are `Added` or `Mutated`
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut world = World::new();
+/// let a = world.spawn((123,));
+/// let b = world.spawn((456,));
+/// world.clear_trackers();
+/// let c = world.spawn((789,));
+/// *world.get_mut::<i32>(a).unwrap() += 1;
+/// let mut entities = world.query::<Changed<i32>>()
+///     .iter()
+///     .map(|(e, &i)| (e, i))
+///     .collect::<Vec<_>>();
+/// entities.sort();
+/// assert_eq!(entities, &[(a, 124), (c, 789)]);
+/// ```
+pub struct Changed<T>(PhantomData<fn(T)>);
+
+impl<T: Component> Query for Changed<T> {
+    // Adding a component also marks it mutated
+    type Fetch = FetchMutated<T>;
+}
+
+unsafe impl<T: Component> QueryShared for Changed<T> {}
+
+/// A borrow of a `World` sufficient to execute the query `Q`
+///
+/// Note that borrows are not released until this object is dropped.
+pub struct QueryBorrow<'w, Q: Query> {
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+    borrowed: bool,
+    _marker: PhantomData<Q>,
+}
+
+impl<'w, Q: Query> QueryBorrow<'w, Q> {
+    pub(crate) fn new(meta: &'w [EntityMeta], archetypes: &'w [Archetype]) -> Self {
+        Self {
+            meta,
+            archetypes,
+            borrowed: false,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Execute the query
+    ///
+    /// Must be called only once per query.
+    pub fn iter<'q>(&'q mut self) -> QueryIter<'q, 'w, Q> {
+        self.borrow();
+        QueryIter {
+            borrow: self,
+            archetype_index: 0,
+            iter: None,
+        }
+    }
+
+    /// Like `iter`, but returns an error instead of panicking if a component is already borrowed
+    /// incompatibly
+    ///
+    /// No borrows are held if an error is returned. Unlike `iter`, may be called again on the same
+    /// query, which reuses the borrows acquired by the first successful call.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let mut numbers = world.query::<&mut i32>();
+    /// let _numbers = numbers.iter();
+    /// let mut query = world.query::<(&i32, &bool)>();
+    /// assert_eq!(query.try_iter().err(), Some(BorrowError::Unique("i32")));
+    /// // `bool` was released when borrowing `i32` failed
+    /// assert!(world.try_get_mut::<bool>(a).is_ok());
+    /// ```
+    pub fn try_iter<'q>(&'q mut self) -> Result<QueryIter<'q, 'w, Q>, BorrowError> {
+        self.try_borrow()?;
+        Ok(QueryIter {
+            borrow: self,
+            archetype_index: 0,
+            iter: None,
+        })
+    }
+
+    /// Like `iter`, but yields only the query results, without the `Entity` of each
+    ///
+    /// Must be called only once per query.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1,));
+    /// world.spawn((2, true));
+    /// let sum: i32 = world.query::<&i32>().iter_items().sum();
+    /// assert_eq!(sum, 3);
+    /// ```
+    pub fn iter_items<'q>(&'q mut self) -> QueryItems<'q, 'w, Q> {
+        QueryItems(self.iter())
+    }
+
+    /// Like `iter`, but yields the results for each archetype at once, as slices
+    ///
+    /// Each item pairs the IDs of a run of entities, as returned by `Entity::id`, with their
+    /// components, in the same order. This exposes the contiguous storage of each component type,
+    /// for use with SIMD or bulk copies. Only queries whose results can be expressed as slices,
+    /// such as those made of `&T`, `&mut T`, `Option`, `With`, and `Without`, are supported. Every
+    /// `&mut T` component yielded is considered mutated. Must be called only once per query. Panics
+    /// on reaching an archetype where the query involves a sparse component type, as those aren't
+    /// stored contiguously; see `World::register_sparse`. In a world whose storage is divided into
+    /// chunks, as with `World::with_chunk_size`, each chunk is yielded separately.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Position(f32);
+    /// struct Velocity(f32);
+    ///
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(|i| (Position(i as f32), Velocity(1.0))));
+    /// world.spawn((Position(10.0), Velocity(2.0), true));
+    /// let mut query = world.query::<(&mut Position, &Velocity)>();
+    /// for (ids, (positions, velocities)) in query.iter_chunks() {
+    ///     assert_eq!(ids.len(), positions.len());
+    ///     for (p, v) in positions.iter_mut().zip(velocities) {
+    ///         p.0 += v.0;
+    ///     }
+    /// }
+    /// # drop(query);
+    /// # assert_eq!(world.query::<&Position>().iter().map(|(_, p)| p.0).sum::<f32>(), 67.0);
+    /// ```
+    pub fn iter_chunks<'q>(&'q mut self) -> QueryChunks<'q, 'w, Q>
+    where
+        Q::Fetch: FetchChunk<'q>,
+    {
+        self.borrow();
+        QueryChunks {
+            borrow: self,
+            archetype_index: 0,
+            offset: 0,
+        }
+    }
+
+    /// Like `iter`, but returns child iterators of at most `batch_size` elements
+    ///
+    /// Useful for distributing work over a threadpool. Each batch draws from a single archetype, and
+    /// from a single chunk of its storage if divided as with `World::with_chunk_size`.
+    pub fn iter_batched<'q>(&'q mut self, batch_size: u32) -> BatchedIter<'q, 'w, Q> {
+        self.borrow();
+        BatchedIter {
+            borrow: self,
+            archetype_index: 0,
+            batch_size,
+            offset: 0,
+        }
+    }
+
+    /// Like `iter`, but distributes work over the rayon global threadpool
+    ///
+    /// Entities are split into batches of at most `PAR_BATCH_SIZE` elements from the same
+    /// archetype, each of which may be processed on a different thread. Use `par_for_each` to
+    /// control the batch size. Must be called only once per query.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use rayon::iter::ParallelIterator;
+    ///
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..1000).map(|i| (i,)));
+    /// let sum: i32 = world.query::<&i32>().par_iter().map(|(_, &i)| i).sum();
+    /// assert_eq!(sum, 499500);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_iter<'q>(&'q mut self) -> ParIter<'q, 'w, Q> {
+        ParIter {
+            borrow: self,
+            batch_size: PAR_BATCH_SIZE,
+        }
+    }
+
+    /// Call `f` on every entity satisfying the query, distributing batches of at most
+    /// `batch_size` entities over the rayon global threadpool
+    ///
+    /// Must be called only once per query.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..1000).map(|i| (i,)));
+    /// world.query::<&mut i32>().par_for_each(64, |_, i| *i *= 2);
+    /// assert_eq!(world.query::<&i32>().iter().map(|(_, &i)| i).sum::<i32>(), 999000);
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn par_for_each<'q, F>(&'q mut self, batch_size: u32, f: F)
+    where
+        F: Fn(Entity, <Q::Fetch as Fetch<'q>>::Item) + Send + Sync,
+        <Q::Fetch as Fetch<'q>>::Item: Send,
+    {
+        use rayon::iter::ParallelIterator;
+        ParIter {
+            borrow: self,
+            batch_size,
+        }
+        .for_each(|(entity, item)| f(entity, item));
+    }
+
+    /// Provide random access to any entity satisfying the query
+    ///
+    /// Borrows every matching archetype once, rather than on every lookup as `World::get` does.
+    /// Like `iter`, must be called only once per query.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Target(Entity);
+    ///
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let b = world.spawn((456, Target(a)));
+    /// let mut targets = world.query::<&Target>();
+    /// let mut query = world.query::<&mut i32>();
+    /// let mut view = query.view();
+    /// for (_, target) in targets.iter() {
+    ///     if let Some(x) = view.get_mut(target.0) {
+    ///         *x += 1;
+    ///     }
+    /// }
+    /// assert_eq!(view.get_mut(a), Some(&mut 124));
+    /// let [x, y] = view.get_many_mut([a, b]);
+    /// assert_eq!((x, y), (Some(&mut 124), Some(&mut 456)));
+    /// ```
+    pub fn view(&mut self) -> View<'_, Q> {
+        self.borrow();
+        View::new(self.meta, self.archetypes)
+    }
+
+    fn borrow(&mut self) {
+        if self.borrowed {
+            panic!(
+                "called QueryBorrow::iter twice on the same borrow; construct a new query instead"
+            );
+        }
+        if let Err(e) = self.try_borrow() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Acquire borrows for the query, if not already held, or none at all if any would conflict
+    fn try_borrow(&mut self) -> Result<(), BorrowError> {
+        if self.borrowed {
+            return Ok(());
+        }
+        for (i, x) in self.archetypes.iter().enumerate() {
+            if Q::Fetch::access(x) < Some(Access::Read) {
+                continue;
+            }
+            if let Err(e) = Q::Fetch::try_borrow(x) {
+                for x in &self.archetypes[..i] {
+                    if Q::Fetch::access(x) >= Some(Access::Read) {
+                        Q::Fetch::release(x);
+                    }
+                }
+                return Err(e);
+            }
+        }
+        self.borrowed = true;
+        Ok(())
+    }
+
+    /// Transform the query into one that requires a certain component without borrowing it
+    ///
+    /// This can be useful when the component needs to be borrowed elsewhere and it isn't necessary
+    /// for the iterator to expose its data directly.
+    ///
+    /// Equivalent to using a query type wrapped in `With`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
+    /// let c = world.spawn((42, "def"));
+    /// let entities = world.query::<&i32>()
+    ///     .with::<bool>()
+    ///     .iter()
+    ///     .map(|(e, &i)| (e, i)) // Copy out of the world
+    ///     .collect::<Vec<_>>();
+    /// assert!(entities.contains(&(a, 123)));
+    /// assert!(entities.contains(&(b, 456)));
+    /// ```
+    pub fn with<T: Component>(self) -> QueryBorrow<'w, With<T, Q>> {
+        self.transform()
+    }
+
+    /// Transform the query into one that skips entities having a certain component
+    ///
+    /// Equivalent to using a query type wrapped in `Without`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true, "abc"));
+    /// let b = world.spawn((456, false));
     /// let c = world.spawn((42, "def"));
     /// let entities = world.query::<&i32>()
     ///     .without::<bool>()
@@ -387,90 +1191,461 @@ impl<'w, Q: Query> QueryBorrow<'w, Q> {
         self.transform()
     }
 
-    /// Helper to change the type of the query
-    fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
-        let x = QueryBorrow {
-            meta: self.meta,
-            archetypes: self.archetypes,
-            borrowed: self.borrowed,
-            _marker: PhantomData,
-        };
-        // Ensure `Drop` won't fire redundantly
-        self.borrowed = false;
-        x
-    }
+    /// Helper to change the type of the query
+    fn transform<R: Query>(mut self) -> QueryBorrow<'w, R> {
+        let x = QueryBorrow {
+            meta: self.meta,
+            archetypes: self.archetypes,
+            borrowed: self.borrowed,
+            _marker: PhantomData,
+        };
+        // Ensure `Drop` won't fire redundantly
+        self.borrowed = false;
+        x
+    }
+}
+
+unsafe impl<'w, Q: Query> Send for QueryBorrow<'w, Q> {}
+unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> {}
+
+impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
+    fn drop(&mut self) {
+        if self.borrowed {
+            for x in self.archetypes {
+                if Q::Fetch::access(x) >= Some(Access::Read) {
+                    Q::Fetch::release(x);
+                }
+            }
+        }
+    }
+}
+
+impl<'q, 'w, Q: Query> IntoIterator for &'q mut QueryBorrow<'w, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+    type IntoIter = QueryIter<'q, 'w, Q>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the set of entities with the components in `Q`
+pub struct QueryIter<'q, 'w, Q: Query> {
+    borrow: &'q mut QueryBorrow<'w, Q>,
+    archetype_index: u32,
+    iter: Option<ChunkIter<Q>>,
+}
+
+unsafe impl<'q, 'w, Q: Query> Send for QueryIter<'q, 'w, Q> {}
+unsafe impl<'q, 'w, Q: Query> Sync for QueryIter<'q, 'w, Q> {}
+
+impl<'q, 'w, Q: Query> QueryIter<'q, 'w, Q> {
+    /// Advance to the next result, yielding its entity ID
+    #[inline]
+    fn next_raw(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'q>>::Item)> {
+        loop {
+            match self.iter {
+                None => {
+                    let archetype = self.borrow.archetypes.get(self.archetype_index as usize)?;
+                    self.archetype_index += 1;
+                    unsafe {
+                        self.iter = ChunkIter::new(self.borrow.meta, archetype, 0..archetype.len());
+                    }
+                }
+                Some(ref mut iter) => match unsafe { iter.next() } {
+                    None => {
+                        if !unsafe { iter.next_chunk() } {
+                            self.iter = None;
+                        }
+                        continue;
+                    }
+                    x => return x,
+                },
+            }
+        }
+    }
+}
+
+impl<'q, 'w, Q: Query> Iterator for QueryIter<'q, 'w, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, components) = self.next_raw()?;
+        Some((
+            Entity {
+                id,
+                generation: self.borrow.meta[id as usize].generation,
+            },
+            components,
+        ))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        (n, Some(n))
+    }
+}
+
+impl<'q, 'w, Q: Query> ExactSizeIterator for QueryIter<'q, 'w, Q> {
+    fn len(&self) -> usize {
+        self.borrow
+            .archetypes
+            .iter()
+            .map(|x| count::<Q>(self.borrow.meta, x))
+            .sum()
+    }
+}
+
+/// Iterator over the results of `Q`, without the entities they belong to
+///
+/// Obtained from `QueryBorrow::iter_items`.
+pub struct QueryItems<'q, 'w, Q: Query>(QueryIter<'q, 'w, Q>);
+
+impl<'q, 'w, Q: Query> Iterator for QueryItems<'q, 'w, Q> {
+    type Item = <Q::Fetch as Fetch<'q>>::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.0.next_raw()?.1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'q, 'w, Q: Query> ExactSizeIterator for QueryItems<'q, 'w, Q> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Iterator over the results of `Q` for each archetype or chunk of storage, as slices
+///
+/// Obtained from `QueryBorrow::iter_chunks`.
+pub struct QueryChunks<'q, 'w, Q: Query> {
+    borrow: &'q mut QueryBorrow<'w, Q>,
+    archetype_index: usize,
+    /// Index of the first entity of the next chunk in the current archetype
+    offset: u32,
+}
+
+unsafe impl<'q, 'w, Q: Query> Send for QueryChunks<'q, 'w, Q> {}
+unsafe impl<'q, 'w, Q: Query> Sync for QueryChunks<'q, 'w, Q> {}
+
+impl<'q, 'w, Q: Query> Iterator for QueryChunks<'q, 'w, Q>
+where
+    Q::Fetch: FetchChunk<'q>,
+{
+    type Item = (&'q [u32], <Q::Fetch as FetchChunk<'q>>::Chunk);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let archetype: &'w Archetype = self.borrow.archetypes.get(self.archetype_index)?;
+            if self.offset >= archetype.len() {
+                self.archetype_index += 1;
+                self.offset = 0;
+                continue;
+            }
+            let fetch =
+                match unsafe { Q::Fetch::get(self.borrow.meta, archetype, self.offset as usize) } {
+                    Some(x) => x,
+                    None => {
+                        self.archetype_index += 1;
+                        self.offset = 0;
+                        continue;
+                    }
+                };
+            let end = archetype.chunk_end(self.offset);
+            let ids = &archetype.ids()[self.offset as usize..end as usize];
+            self.offset = end;
+            return Some((ids, unsafe { fetch.fetch_chunk(ids.len()) }));
+        }
+    }
+}
+
+/// Number of entities in `archetype` matched by `Q`
+pub(crate) fn count<Q: Query>(meta: &[EntityMeta], archetype: &Archetype) -> usize {
+    unsafe { ChunkIter::<Q>::new(meta, archetype, 0..archetype.len()) }.map_or(0, |x| x.remaining())
 }
 
-unsafe impl<'w, Q: Query> Send for QueryBorrow<'w, Q> {}
-unsafe impl<'w, Q: Query> Sync for QueryBorrow<'w, Q> {}
+/// Iterator over the entities in a range of an archetype, one chunk of storage at a time
+pub(crate) struct ChunkIter<Q: Query> {
+    meta: NonNull<[EntityMeta]>,
+    archetype: NonNull<Archetype>,
+    entities: NonNull<u32>,
+    fetch: Q::Fetch,
+    position: usize,
+    len: usize,
+    /// Index in the archetype of the entity after the current chunk
+    next: u32,
+    /// Index in the archetype of the entity after the range
+    end: u32,
+}
 
-impl<'w, Q: Query> Drop for QueryBorrow<'w, Q> {
-    fn drop(&mut self) {
-        if self.borrowed {
-            for x in self.archetypes {
-                if Q::Fetch::access(x) >= Some(Access::Read) {
-                    Q::Fetch::release(x);
-                }
+impl<Q: Query> ChunkIter<Q> {
+    /// Iterate over the entities in `range` of `archetype`, if `Q` matches it
+    ///
+    /// # Safety
+    ///
+    /// `range` must be in bounds, and `meta` and `archetype` must outlive the iterator
+    pub(crate) unsafe fn new(
+        meta: &[EntityMeta],
+        archetype: &Archetype,
+        range: Range<u32>,
+    ) -> Option<Self> {
+        let fetch = Q::Fetch::get(meta, archetype, range.start as usize)?;
+        let chunk_end = archetype.chunk_end(range.start).min(range.end);
+        Some(Self {
+            meta: NonNull::from(meta),
+            archetype: NonNull::from(archetype),
+            entities: NonNull::new_unchecked(
+                archetype.entities().as_ptr().add(range.start as usize),
+            ),
+            fetch,
+            position: 0,
+            len: (chunk_end - range.start) as usize,
+            next: chunk_end,
+            end: range.end,
+        })
+    }
+
+    /// Number of entities yet to be yielded
+    pub(crate) fn remaining(&self) -> usize {
+        if !self.fetch.may_skip() {
+            // Every chunk of an archetype is fetched alike
+            return self.len - self.position + (self.end - self.next) as usize;
+        }
+        let mut n = (self.position..self.len)
+            .filter(|&n| unsafe { !self.fetch.should_skip(n) })
+            .count();
+        let archetype = unsafe { self.archetype.as_ref() };
+        let mut start = self.next;
+        while start < self.end {
+            let end = archetype.chunk_end(start).min(self.end);
+            if let Some(fetch) =
+                unsafe { Q::Fetch::get(self.meta.as_ref(), archetype, start as usize) }
+            {
+                n += (0..(end - start) as usize)
+                    .filter(|&i| unsafe { !fetch.should_skip(i) })
+                    .count();
+            }
+            start = end;
+        }
+        n
+    }
+
+    /// Yield the next entity in the current chunk, see `next_chunk`
+    #[inline]
+    pub(crate) unsafe fn next<'a>(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'a>>::Item)> {
+        loop {
+            if self.position == self.len {
+                return None;
             }
+            let n = self.position;
+            self.position += 1;
+            if self.fetch.should_skip(n) {
+                continue;
+            }
+            return Some((*self.entities.as_ptr().add(n), self.fetch.fetch(n)));
+        }
+    }
+
+    /// Move on to the chunk after the current one, if it's in range
+    ///
+    /// Kept separate from `next` so as not to burden the common case of an archetype stored in one
+    /// chunk.
+    #[cold]
+    pub(crate) unsafe fn next_chunk(&mut self) -> bool {
+        if self.next == self.end {
+            return false;
         }
+        let archetype = self.archetype.as_ref();
+        self.fetch = match Q::Fetch::get(self.meta.as_ref(), archetype, self.next as usize) {
+            Some(x) => x,
+            None => return false,
+        };
+        let chunk_end = archetype.chunk_end(self.next).min(self.end);
+        self.entities =
+            NonNull::new_unchecked(archetype.entities().as_ptr().add(self.next as usize));
+        self.position = 0;
+        self.len = (chunk_end - self.next) as usize;
+        self.next = chunk_end;
+        true
     }
 }
 
-impl<'q, 'w, Q: Query> IntoIterator for &'q mut QueryBorrow<'w, Q> {
-    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
-    type IntoIter = QueryIter<'q, 'w, Q>;
+/// Random access to the entities satisfying a query
+///
+/// Obtained from `QueryBorrow::view`.
+pub struct View<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetypes: &'q [Archetype],
+    /// Fetches for each chunk of each archetype, empty for archetypes `Q` doesn't match
+    fetch: Vec<Vec<Q::Fetch>>,
+}
 
-    fn into_iter(self) -> Self::IntoIter {
-        self.iter()
+impl<'q, Q: Query> View<'q, Q> {
+    /// `Q` must be borrowed from every archetype for `'q`
+    fn new(meta: &'q [EntityMeta], archetypes: &'q [Archetype]) -> Self {
+        Self {
+            meta,
+            archetypes,
+            fetch: archetypes
+                .iter()
+                .map(|x| {
+                    x.chunk_starts()
+                        .map_while(|start| unsafe { Q::Fetch::get(meta, x, start as usize) })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Retrieve the query results for `entity`, or `None` if it doesn't exist or satisfy the query
+    ///
+    /// Only available for queries that never uniquely borrow, as those can't alias.
+    pub fn get(&self, entity: Entity) -> Option<<Q::Fetch as Fetch<'_>>::Item>
+    where
+        Q: QueryShared,
+    {
+        unsafe { self.get_unchecked(entity) }
+    }
+
+    /// Retrieve the query results for `entity`, or `None` if it doesn't exist or satisfy the query
+    pub fn get_mut(&mut self, entity: Entity) -> Option<<Q::Fetch as Fetch<'_>>::Item> {
+        unsafe { self.get_unchecked(entity) }
+    }
+
+    /// Retrieve the query results for several distinct entities at once
+    ///
+    /// Each result is `None` if the corresponding entity doesn't exist or satisfy the query. Panics
+    /// if any entity occurs more than once.
+    pub fn get_many_mut<const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> [Option<<Q::Fetch as Fetch<'_>>::Item>; N] {
+        for (i, a) in entities.iter().enumerate() {
+            for b in &entities[i + 1..] {
+                assert!(a != b, "{:?} occurs more than once", a);
+            }
+        }
+        let this = &*self;
+        core::array::from_fn(move |i| unsafe { this.get_unchecked(entities[i]) })
+    }
+
+    /// # Safety
+    ///
+    /// No unique reference to any component of `entity` produced by `Q` may be live
+    unsafe fn get_unchecked(&self, entity: Entity) -> Option<<Q::Fetch as Fetch<'_>>::Item> {
+        let meta = self.meta.get(entity.id as usize)?;
+        if meta.generation != entity.generation {
+            return None;
+        }
+        let archetype = &self.archetypes[meta.location.archetype as usize];
+        let index = meta.location.index;
+        // Guards against reserved entities, which have no defined index
+        if index >= archetype.len() {
+            return None;
+        }
+        let chunk_len = archetype.chunk_len();
+        let fetch =
+            self.fetch[meta.location.archetype as usize].get((index / chunk_len) as usize)?;
+        let n = (index % chunk_len) as usize;
+        if fetch.should_skip(n) {
+            return None;
+        }
+        Some(fetch.fetch(n))
     }
 }
 
-/// Iterator over the set of entities with the components in `Q`
-pub struct QueryIter<'q, 'w, Q: Query> {
-    borrow: &'q mut QueryBorrow<'w, Q>,
-    archetype_index: u32,
+unsafe impl<'q, Q: Query> Send for View<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for View<'q, Q> {}
+
+/// Iterator over the set of entities with the components in `Q`, from a uniquely borrowed `World`
+///
+/// Obtained from `World::query_mut`. Unlike `QueryBorrow`, no dynamic borrows are held, since
+/// exclusive access to the world rules out conflicts with any other borrow.
+pub struct QueryMut<'q, Q: Query> {
+    meta: &'q [EntityMeta],
+    archetypes: core::slice::Iter<'q, Archetype>,
     iter: Option<ChunkIter<Q>>,
 }
 
-unsafe impl<'q, 'w, Q: Query> Send for QueryIter<'q, 'w, Q> {}
-unsafe impl<'q, 'w, Q: Query> Sync for QueryIter<'q, 'w, Q> {}
+impl<'q, Q: Query> QueryMut<'q, Q> {
+    pub(crate) fn new(meta: &'q [EntityMeta], archetypes: &'q mut [Archetype]) -> Self {
+        for x in archetypes.iter() {
+            assert_borrow::<Q>(x);
+        }
+        Self {
+            meta,
+            archetypes: archetypes.iter(),
+            iter: None,
+        }
+    }
+}
 
-impl<'q, 'w, Q: Query> Iterator for QueryIter<'q, 'w, Q> {
-    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+unsafe impl<'q, Q: Query> Send for QueryMut<'q, Q> {}
+unsafe impl<'q, Q: Query> Sync for QueryMut<'q, Q> {}
+
+impl<'q, Q: Query> QueryMut<'q, Q> {
+    /// Yield only the query results, without the `Entity` of each
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((1,));
+    /// world.spawn((2, true));
+    /// for x in world.query_mut::<&mut i32>().into_items() {
+    ///     *x *= 10;
+    /// }
+    /// assert_eq!(world.query_mut::<&i32>().into_items().sum::<i32>(), 30);
+    /// ```
+    pub fn into_items(self) -> QueryMutItems<'q, Q> {
+        QueryMutItems(self)
+    }
 
+    /// Advance to the next result, yielding its entity ID
     #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_raw(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'q>>::Item)> {
         loop {
             match self.iter {
                 None => {
-                    let archetype = self.borrow.archetypes.get(self.archetype_index as usize)?;
-                    self.archetype_index += 1;
+                    let archetype = self.archetypes.next()?;
                     unsafe {
-                        self.iter = Q::Fetch::get(archetype, 0).map(|fetch| ChunkIter {
-                            entities: archetype.entities(),
-                            fetch,
-                            len: archetype.len(),
-                        });
+                        self.iter = ChunkIter::new(self.meta, archetype, 0..archetype.len());
                     }
                 }
                 Some(ref mut iter) => match unsafe { iter.next() } {
                     None => {
-                        self.iter = None;
+                        if !unsafe { iter.next_chunk() } {
+                            self.iter = None;
+                        }
                         continue;
                     }
-                    Some((id, components)) => {
-                        return Some((
-                            Entity {
-                                id,
-                                generation: self.borrow.meta[id as usize].generation,
-                            },
-                            components,
-                        ));
-                    }
+                    x => return x,
                 },
             }
         }
     }
+}
+
+impl<'q, Q: Query> Iterator for QueryMut<'q, Q> {
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let (id, components) = self.next_raw()?;
+        Some((
+            Entity {
+                id,
+                generation: self.meta[id as usize].generation,
+            },
+            components,
+        ))
+    }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
         let n = self.len();
@@ -478,33 +1653,49 @@ impl<'q, 'w, Q: Query> Iterator for QueryIter<'q, 'w, Q> {
     }
 }
 
-impl<'q, 'w, Q: Query> ExactSizeIterator for QueryIter<'q, 'w, Q> {
+impl<'q, Q: Query> ExactSizeIterator for QueryMut<'q, Q> {
     fn len(&self) -> usize {
-        self.borrow
-            .archetypes
-            .iter()
-            .filter(|&x| Q::Fetch::access(x).is_some())
-            .map(|x| x.len() as usize)
-            .sum()
+        self.iter.as_ref().map_or(0, |x| x.remaining())
+            + self
+                .archetypes
+                .clone()
+                .map(|x| count::<Q>(self.meta, x))
+                .sum::<usize>()
     }
 }
 
-struct ChunkIter<Q: Query> {
-    entities: NonNull<u32>,
-    fetch: Q::Fetch,
-    len: u32,
-}
+/// Iterator over the results of `Q` in a uniquely borrowed `World`, without the entities they
+/// belong to
+///
+/// Obtained from `QueryMut::into_items`.
+pub struct QueryMutItems<'q, Q: Query>(QueryMut<'q, Q>);
+
+impl<'q, Q: Query> Iterator for QueryMutItems<'q, Q> {
+    type Item = <Q::Fetch as Fetch<'q>>::Item;
 
-impl<Q: Query> ChunkIter<Q> {
     #[inline]
-    unsafe fn next<'a>(&mut self) -> Option<(u32, <Q::Fetch as Fetch<'a>>::Item)> {
-        if self.len == 0 {
-            return None;
-        }
-        self.len -= 1;
-        let entity = self.entities.as_ptr();
-        self.entities = NonNull::new_unchecked(entity.add(1));
-        Some((*entity, self.fetch.next()))
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.0.next_raw()?.1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'q, Q: Query> ExactSizeIterator for QueryMutItems<'q, Q> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Panic if `Q` would alias a component of `archetype` with itself
+///
+/// Needed when dynamic borrow tracking is skipped due to exclusive access to the `World`.
+pub(crate) fn assert_borrow<Q: Query>(archetype: &Archetype) {
+    if Q::Fetch::access(archetype) >= Some(Access::Read) {
+        Q::Fetch::borrow(archetype);
+        Q::Fetch::release(archetype);
     }
 }
 
@@ -513,7 +1704,8 @@ pub struct BatchedIter<'q, 'w, Q: Query> {
     borrow: &'q mut QueryBorrow<'w, Q>,
     archetype_index: u32,
     batch_size: u32,
-    batch: u32,
+    /// Index of the first entity of the next batch in the current archetype
+    offset: u32,
 }
 
 unsafe impl<'q, 'w, Q: Query> Send for BatchedIter<'q, 'w, Q> {}
@@ -525,31 +1717,28 @@ impl<'q, 'w, Q: Query> Iterator for BatchedIter<'q, 'w, Q> {
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             let archetype = self.borrow.archetypes.get(self.archetype_index as usize)?;
-            let offset = self.batch_size * self.batch;
-            if offset >= archetype.len() {
+            if self.offset >= archetype.len() {
                 self.archetype_index += 1;
-                self.batch = 0;
+                self.offset = 0;
                 continue;
             }
-            if let Some(fetch) = unsafe { Q::Fetch::get(archetype, offset as usize) } {
-                self.batch += 1;
+            // Batches never span chunks, which aren't contiguous with each other
+            let end = archetype
+                .chunk_end(self.offset)
+                .min(self.offset.saturating_add(self.batch_size));
+            if let Some(state) =
+                unsafe { ChunkIter::new(self.borrow.meta, archetype, self.offset..end) }
+            {
+                self.offset = end;
                 return Some(Batch {
                     _marker: PhantomData,
                     meta: self.borrow.meta,
-                    state: ChunkIter {
-                        entities: unsafe {
-                            NonNull::new_unchecked(
-                                archetype.entities().as_ptr().add(offset as usize),
-                            )
-                        },
-                        fetch,
-                        len: self.batch_size.min(archetype.len() - offset),
-                    },
+                    state,
                 });
             } else {
                 self.archetype_index += 1;
                 debug_assert_eq!(
-                    self.batch, 0,
+                    self.offset, 0,
                     "query fetch should always reject at the first batch or not at all"
                 );
                 continue;
@@ -583,6 +1772,140 @@ impl<'q, 'w, Q: Query> Iterator for Batch<'q, 'w, Q> {
 unsafe impl<'q, 'w, Q: Query> Send for Batch<'q, 'w, Q> {}
 unsafe impl<'q, 'w, Q: Query> Sync for Batch<'q, 'w, Q> {}
 
+/// Number of entities per batch used by `QueryBorrow::par_iter`
+#[cfg(feature = "rayon")]
+pub const PAR_BATCH_SIZE: u32 = 1024;
+
+/// Parallel iterator over the set of entities with the components in `Q`
+///
+/// Obtained from `QueryBorrow::par_iter`. Archetypes, and the batches of entities within them, are
+/// split as rayon distributes them over its threadpool. Like `Batch`, this is `Send` and `Sync`;
+/// the items themselves are only sent to other threads when they're `Send`, which holds for every
+/// built-in query since components are `Send + Sync`.
+#[cfg(feature = "rayon")]
+pub struct ParIter<'q, 'w, Q: Query> {
+    borrow: &'q mut QueryBorrow<'w, Q>,
+    batch_size: u32,
+}
+
+#[cfg(feature = "rayon")]
+unsafe impl<'q, 'w, Q: Query> Send for ParIter<'q, 'w, Q> {}
+#[cfg(feature = "rayon")]
+unsafe impl<'q, 'w, Q: Query> Sync for ParIter<'q, 'w, Q> {}
+
+#[cfg(feature = "rayon")]
+impl<'q, 'w, Q: Query> rayon::iter::ParallelIterator for ParIter<'q, 'w, Q>
+where
+    <Q::Fetch as Fetch<'q>>::Item: Send,
+{
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: rayon::iter::plumbing::UnindexedConsumer<Self::Item>,
+    {
+        self.borrow.borrow();
+        let archetypes = self.borrow.archetypes;
+        let producer = BatchProducer::<Q> {
+            _marker: PhantomData,
+            meta: self.borrow.meta,
+            archetypes,
+            offset: 0,
+            end: archetypes.last().map_or(0, |x| x.len()),
+            // Empty batches would never make progress
+            batch_size: self.batch_size.max(1),
+        };
+        rayon::iter::plumbing::bridge_unindexed(producer, consumer)
+    }
+}
+
+/// The entities of `ParIter` that remain to be split or processed
+///
+/// Covers `archetypes`, starting from `offset` in the first and stopping at `end` in the last.
+#[cfg(feature = "rayon")]
+struct BatchProducer<'q, 'w, Q: Query> {
+    _marker: PhantomData<&'q Q>,
+    meta: &'w [EntityMeta],
+    archetypes: &'w [Archetype],
+    offset: u32,
+    end: u32,
+    batch_size: u32,
+}
+
+#[cfg(feature = "rayon")]
+unsafe impl<'q, 'w, Q: Query> Send for BatchProducer<'q, 'w, Q> {}
+
+#[cfg(feature = "rayon")]
+impl<'q, 'w, Q: Query> rayon::iter::plumbing::UnindexedProducer for BatchProducer<'q, 'w, Q>
+where
+    <Q::Fetch as Fetch<'q>>::Item: Send,
+{
+    type Item = (Entity, <Q::Fetch as Fetch<'q>>::Item);
+
+    fn split(self) -> (Self, Option<Self>) {
+        match self.archetypes.len() {
+            0 => (self, None),
+            1 => {
+                // Split between whole batches, so no batch is smaller than it must be
+                let batches = (self.end - self.offset).div_ceil(self.batch_size);
+                if batches < 2 {
+                    return (self, None);
+                }
+                let mid = self.offset + batches / 2 * self.batch_size;
+                let right = Self {
+                    offset: mid,
+                    ..self
+                };
+                (Self { end: mid, ..self }, Some(right))
+            }
+            n => {
+                let (left, right) = self.archetypes.split_at(n / 2);
+                let right = Self {
+                    archetypes: right,
+                    offset: 0,
+                    ..self
+                };
+                let left = Self {
+                    archetypes: left,
+                    end: left[left.len() - 1].len(),
+                    ..self
+                };
+                (left, Some(right))
+            }
+        }
+    }
+
+    fn fold_with<F>(self, mut folder: F) -> F
+    where
+        F: rayon::iter::plumbing::Folder<Self::Item>,
+    {
+        let last = self.archetypes.len().wrapping_sub(1);
+        for (i, archetype) in self.archetypes.iter().enumerate() {
+            let mut offset = if i == 0 { self.offset } else { 0 };
+            let end = if i == last { self.end } else { archetype.len() };
+            while offset < end && !folder.full() {
+                // Batches never span chunks, as in `BatchedIter`
+                let batch_end = archetype
+                    .chunk_end(offset)
+                    .min(end)
+                    .min(offset.saturating_add(self.batch_size));
+                let state = match unsafe { ChunkIter::new(self.meta, archetype, offset..batch_end) }
+                {
+                    Some(x) => x,
+                    None => break,
+                };
+                folder = folder.consume_iter(Batch::<Q> {
+                    _marker: PhantomData,
+                    meta: self.meta,
+                    state,
+                });
+                offset = batch_end;
+            }
+        }
+        folder
+    }
+}
+
 macro_rules! tuple_impl {
     ($($name: ident),*) => {
         impl<'a, $($name: Fetch<'a>),*> Fetch<'a> for ($($name,)*) {
@@ -597,35 +1920,187 @@ macro_rules! tuple_impl {
                 Some(access)
             }
 
-            #[allow(unused_variables)]
-            fn borrow(archetype: &Archetype) {
-                $($name::borrow(archetype);)*
+            #[allow(unused_variables, unused_mut, unused_assignments)]
+            fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+                // On failure, release whatever was borrowed before it
+                let mut result = Ok(());
+                let mut borrowed = 0;
+                $(
+                    if result.is_ok() {
+                        result = $name::try_borrow(archetype);
+                        borrowed += result.is_ok() as usize;
+                    }
+                )*
+                if result.is_err() {
+                    $(
+                        if borrowed > 0 {
+                            $name::release(archetype);
+                            borrowed -= 1;
+                        }
+                    )*
+                }
+                result
             }
             #[allow(unused_variables)]
-            unsafe fn get(archetype: &'a Archetype, offset: usize) -> Option<Self> {
-                Some(($($name::get(archetype, offset)?,)*))
+            unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self> {
+                Some(($($name::get(meta, archetype, offset)?,)*))
             }
             #[allow(unused_variables)]
             fn release(archetype: &Archetype) {
                 $($name::release(archetype);)*
             }
 
-            unsafe fn next(&mut self) -> Self::Item {
+            #[allow(unused_variables)]
+            unsafe fn should_skip(&self, n: usize) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                $($name.should_skip(n)||)* false
+            }
+
+            fn may_skip(&self) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                $($name.may_skip()||)* false
+            }
+
+            #[allow(unused_variables)]
+            unsafe fn is_missing(&self, n: usize) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                $($name.is_missing(n)||)* false
+            }
+
+            #[allow(unused_variables, clippy::unused_unit)]
+            unsafe fn fetch(&self, n: usize) -> Self::Item {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = self;
+                ($($name.fetch(n),)*)
+            }
+        }
+
+        impl<'a, $($name: FetchChunk<'a>),*> FetchChunk<'a> for ($($name,)*) {
+            type Chunk = ($($name::Chunk,)*);
+
+            #[allow(unused_variables, clippy::unused_unit)]
+            unsafe fn fetch_chunk(&self, len: usize) -> Self::Chunk {
                 #[allow(non_snake_case)]
                 let ($($name,)*) = self;
-                ($($name.next(),)*)
+                ($($name.fetch_chunk(len),)*)
             }
         }
 
         impl<$($name: Query),*> Query for ($($name,)*) {
             type Fetch = ($($name::Fetch,)*);
         }
+
+        unsafe impl<$($name: QueryShared),*> QueryShared for ($($name,)*) {}
     };
 }
 
 //smaller_tuples_too!(tuple_impl, B, A);
 smaller_tuples_too!(tuple_impl, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
 
+macro_rules! or_impl {
+    ($($name: ident),*) => {
+        impl<'a, $($name: Fetch<'a>),*> Fetch<'a> for FetchOr<($(Option<$name>,)*)> {
+            type Item = ($(Option<$name::Item>,)*);
+
+            #[allow(unused_variables, unused_mut)]
+            fn access(archetype: &Archetype) -> Option<Access> {
+                let mut access = None;
+                $(
+                    access = access.max($name::access(archetype));
+                )*
+                access
+            }
+
+            #[allow(unused_variables, unused_mut, unused_assignments)]
+            fn try_borrow(archetype: &Archetype) -> Result<(), BorrowError> {
+                // On failure, release whatever was borrowed before it
+                let mut result = Ok(());
+                let mut borrowed = 0;
+                $(
+                    if result.is_ok() {
+                        if $name::access(archetype) >= Some(Access::Read) {
+                            result = $name::try_borrow(archetype);
+                        }
+                        borrowed += result.is_ok() as usize;
+                    }
+                )*
+                if result.is_err() {
+                    $(
+                        if borrowed > 0 {
+                            if $name::access(archetype) >= Some(Access::Read) {
+                                $name::release(archetype);
+                            }
+                            borrowed -= 1;
+                        }
+                    )*
+                }
+                result
+            }
+            #[allow(unused_variables)]
+            unsafe fn get(meta: &'a [EntityMeta], archetype: &'a Archetype, offset: usize) -> Option<Self> {
+                let fetch = ($($name::get(meta, archetype, offset),)*);
+                #[allow(non_snake_case)]
+                let ($($name,)*) = &fetch;
+                if true $(&& $name.is_none())* {
+                    return None;
+                }
+                Some(Self(fetch))
+            }
+            #[allow(unused_variables)]
+            fn release(archetype: &Archetype) {
+                $(
+                    if $name::access(archetype) >= Some(Access::Read) {
+                        $name::release(archetype);
+                    }
+                )*
+            }
+
+            #[allow(unused_variables)]
+            unsafe fn should_skip(&self, n: usize) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = &self.0;
+                true $(&& $name.as_ref().map_or(true, |x| x.should_skip(n)))*
+            }
+
+            fn may_skip(&self) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = &self.0;
+                true $(&& $name.as_ref().map_or(true, |x| x.may_skip()))*
+            }
+
+            #[allow(unused_variables)]
+            unsafe fn is_missing(&self, n: usize) -> bool {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = &self.0;
+                true $(&& $name.as_ref().map_or(true, |x| x.is_missing(n)))*
+            }
+
+            #[allow(unused_variables, clippy::unused_unit)]
+            unsafe fn fetch(&self, n: usize) -> Self::Item {
+                #[allow(non_snake_case)]
+                let ($($name,)*) = &self.0;
+                ($(
+                    match $name {
+                        Some(x) if !x.should_skip(n) => Some(x.fetch(n)),
+                        _ => None,
+                    },
+                )*)
+            }
+        }
+
+        impl<$($name: Query),*> Query for Or<($($name,)*)> {
+            type Fetch = FetchOr<($(Option<$name::Fetch>,)*)>;
+        }
+
+        unsafe impl<$($name: QueryShared),*> QueryShared for Or<($($name,)*)> {}
+    };
+}
+
+smaller_tuples_too!(or_impl, O, N, M, L, K, J, I, H, G, F, E, D, C, B, A);
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,10 +1,12 @@
 use core::marker::PhantomData;
 
+use crate::entities::EntityMeta;
 use crate::query::{Fetch, With, Without};
 use crate::{Archetype, Component, Query};
 
 /// A borrow of a `World` sufficient to execute the query `Q` on a single entity
 pub struct QueryOne<'a, Q: Query> {
+    meta: &'a [EntityMeta],
     archetype: &'a Archetype,
     index: u32,
     borrowed: bool,
@@ -16,9 +18,10 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
     ///
     /// # Safety
     ///
-    /// `index` must be in-bounds for `archetype`
-    pub(crate) unsafe fn new(archetype: &'a Archetype, index: u32) -> Self {
+    /// `index` must be in-bounds for `archetype`, or `u32::MAX` for a pending entity
+    pub(crate) unsafe fn new(meta: &'a [EntityMeta], archetype: &'a Archetype, index: u32) -> Self {
         Self {
+            meta,
             archetype,
             index,
             borrowed: false,
@@ -36,11 +39,19 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
         if self.borrowed {
             panic!("called QueryOnce::get twice; construct a new query instead");
         }
+        // Guards against pending entities, which have no defined index
+        if self.index >= self.archetype.len() {
+            return None;
+        }
         unsafe {
-            let mut fetch = Q::Fetch::get(self.archetype, self.index as usize)?;
-            self.borrowed = true;
+            let fetch = Q::Fetch::get(self.meta, self.archetype, self.index as usize)?;
             Q::Fetch::borrow(self.archetype);
-            Some(fetch.next())
+            if fetch.should_skip(0) {
+                Q::Fetch::release(self.archetype);
+                return None;
+            }
+            self.borrowed = true;
+            Some(fetch.fetch(0))
         }
     }
 
@@ -61,6 +72,7 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
     /// Helper to change the type of the query
     fn transform<R: Query>(mut self) -> QueryOne<'a, R> {
         let x = QueryOne {
+            meta: self.meta,
             archetype: self.archetype,
             index: self.index,
             borrowed: self.borrowed,
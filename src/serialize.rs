@@ -0,0 +1,857 @@
+use crate::alloc::boxed::Box;
+use crate::alloc::vec::{self, Vec};
+use core::any::type_name;
+use core::fmt;
+use core::marker::PhantomData;
+
+use hashbrown::HashMap;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq, SerializeTuple};
+use serde::{Deserializer, Serialize, Serializer};
+
+use crate::archetype::{Archetype, ComponentId};
+use crate::entities::EntityMeta;
+use crate::sparse::SparseSet;
+use crate::{Component, Entity, EntityBuilder, World};
+
+/// Maps component types to stable names for serializing and deserializing whole `World`s
+///
+/// Only components of registered types are serialized; others are silently omitted, though the
+/// entities they're attached to are still recorded. Entity handles are preserved exactly, so
+/// `Entity`s stored inside components remain valid after deserialization. Entities that were
+/// despawned before serialization are not tracked, nor are relationships established with
+/// `World::set_parent`.
+///
+/// Two representations are supported:
+/// - rows, via `serialize_rows`, a sequence of `(entity, {name: component})` pairs
+/// - columns, via `serialize_columns`, a sequence of `([entity], {name: [component]})` pairs, one
+///   per archetype, which is more compact and faster to process when many entities share the
+///   same components
+///
+/// Components of types registered with `World::register_sparse` are included in the map of each
+/// entity having one in the row representation, and as a pair of their own per type in the column
+/// representation. Deserialized worlds store them in archetypes unless they're registered here
+/// with `register_sparse`.
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// let mut registry = Registry::new();
+/// registry.register::<i32>("number").register::<bool>("flag");
+///
+/// let mut world = World::new();
+/// let a = world.spawn((123, true));
+/// let b = world.spawn((456,));
+///
+/// let json = serde_json::to_string(&registry.serialize_rows(&world)).unwrap();
+/// let mut deserializer = serde_json::Deserializer::from_str(&json);
+/// let restored = registry.deserialize_rows(&mut deserializer).unwrap();
+/// assert_eq!(*restored.get::<i32>(a).unwrap(), 123);
+/// assert_eq!(*restored.get::<bool>(a).unwrap(), true);
+/// assert_eq!(*restored.get::<i32>(b).unwrap(), 456);
+/// ```
+pub struct Registry {
+    components: Vec<Registration>,
+    by_id: HashMap<ComponentId, usize>,
+    by_name: HashMap<&'static str, usize>,
+    entity_limit: u32,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            components: Vec::new(),
+            by_id: HashMap::default(),
+            by_name: HashMap::default(),
+            entity_limit: 1 << 24,
+        }
+    }
+}
+
+impl Registry {
+    /// Create a registry that doesn't know of any component types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fail to deserialize worlds containing entity IDs of `limit` or more
+    ///
+    /// A deserialized world allocates storage for every ID up to the largest it contains, so this
+    /// bounds the memory that malformed or malicious input can consume. Defaults to 2^24.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut registry = Registry::new();
+    /// registry.register::<i32>("number").entity_limit(100);
+    /// let mut deserializer = serde_json::Deserializer::from_str(r#"[[100, {"number": 1}]]"#);
+    /// assert!(registry.deserialize_rows(&mut deserializer).is_err());
+    /// ```
+    pub fn entity_limit(&mut self, limit: u32) -> &mut Self {
+        self.entity_limit = limit;
+        self
+    }
+
+    /// Fail if `entity` is beyond `entity_limit`
+    fn check_entity<E: de::Error>(&self, entity: Entity) -> Result<(), E> {
+        if entity.id() >= self.entity_limit {
+            return Err(E::custom(format_args!(
+                "entity ID {} exceeds the limit of {}",
+                entity.id(),
+                self.entity_limit
+            )));
+        }
+        Ok(())
+    }
+
+    /// Associate the component type `T` with `name`
+    ///
+    /// Names identify component types in serialized data, so they must remain stable for
+    /// previously serialized worlds to be loadable. Panics if `T` or `name` is already registered.
+    pub fn register<T>(&mut self, name: &'static str) -> &mut Self
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        let index = self.components.len();
+        assert!(
+            self.by_id.insert(ComponentId::of::<T>(), index).is_none(),
+            "{} registered more than once",
+            type_name::<T>()
+        );
+        assert!(
+            self.by_name.insert(name, index).is_none(),
+            "component name `{}` registered more than once",
+            name
+        );
+        self.components.push(Registration {
+            name,
+            id: ComponentId::of::<T>(),
+            size: core::mem::size_of::<T>(),
+            sparse: false,
+            vtable: Box::new(Typed::<T>(PhantomData)),
+        });
+        self
+    }
+
+    /// Like `register`, additionally registering `T` with `World::register_sparse` in every
+    /// deserialized world
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Stunned;
+    /// # impl serde::Serialize for Stunned {
+    /// #     fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    /// #         s.serialize_unit()
+    /// #     }
+    /// # }
+    /// # impl<'de> serde::Deserialize<'de> for Stunned {
+    /// #     fn deserialize<D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+    /// #         <()>::deserialize(d).map(|()| Stunned)
+    /// #     }
+    /// # }
+    ///
+    /// let mut registry = Registry::new();
+    /// registry.register::<i32>("number").register_sparse::<Stunned>("stunned");
+    ///
+    /// let mut world = World::new();
+    /// world.register_sparse::<Stunned>();
+    /// let a = world.spawn((123, Stunned));
+    ///
+    /// let json = serde_json::to_string(&registry.serialize_columns(&world)).unwrap();
+    /// let mut deserializer = serde_json::Deserializer::from_str(&json);
+    /// let mut restored = registry.deserialize_columns(&mut deserializer).unwrap();
+    /// assert!(restored.get::<Stunned>(a).is_ok());
+    /// // Sparse components don't move entities between archetypes
+    /// let generation = restored.archetypes_generation();
+    /// restored.remove_one::<Stunned>(a).unwrap();
+    /// assert_eq!(restored.archetypes_generation(), generation);
+    /// ```
+    pub fn register_sparse<T>(&mut self, name: &'static str) -> &mut Self
+    where
+        T: Component + Serialize + DeserializeOwned,
+    {
+        self.register::<T>(name);
+        self.components.last_mut().unwrap().sparse = true;
+        self
+    }
+
+    /// A world with every type registered with `register_sparse` made sparse
+    fn new_world(&self) -> World {
+        let mut world = World::new();
+        for registration in self.components.iter().filter(|x| x.sparse) {
+            registration.vtable.register_sparse(&mut world);
+        }
+        world
+    }
+
+    /// Serialize `world` one entity at a time
+    ///
+    /// Panics during serialization if a registered component is uniquely borrowed.
+    pub fn serialize_rows<'a>(&'a self, world: &'a World) -> SerializeRows<'a> {
+        SerializeRows {
+            registry: self,
+            world,
+        }
+    }
+
+    /// Serialize `world` one archetype at a time
+    ///
+    /// Panics during serialization if a registered component is uniquely borrowed.
+    pub fn serialize_columns<'a>(&'a self, world: &'a World) -> SerializeColumns<'a> {
+        SerializeColumns {
+            registry: self,
+            world,
+        }
+    }
+
+    /// Reconstruct a `World` from the output of `serialize_rows`
+    pub fn deserialize_rows<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<World, D::Error> {
+        deserializer.deserialize_seq(RowsVisitor(self))
+    }
+
+    /// Reconstruct a `World` from the output of `serialize_columns`
+    pub fn deserialize_columns<'de, D: Deserializer<'de>>(
+        &self,
+        deserializer: D,
+    ) -> Result<World, D::Error> {
+        deserializer.deserialize_seq(ColumnsVisitor(self))
+    }
+}
+
+struct Registration {
+    name: &'static str,
+    id: ComponentId,
+    size: usize,
+    /// Whether deserialized worlds store this type in sparse sets
+    sparse: bool,
+    vtable: Box<dyn ErasedComponent>,
+}
+
+/// Operations on a registered component type
+trait ErasedComponent: Send + Sync {
+    fn borrow(&self, archetype: &Archetype);
+    fn release(&self, archetype: &Archetype);
+    fn register_sparse(&self, world: &mut World);
+    /// `ptr` must refer to a valid component for `'a`
+    unsafe fn as_serialize<'a>(&self, ptr: *const u8) -> &'a dyn erased_serde::Serialize;
+    fn deserialize(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'_>,
+        builder: &mut EntityBuilder,
+    ) -> Result<(), erased_serde::Error>;
+    fn deserialize_column(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'_>,
+    ) -> Result<Box<dyn Column>, erased_serde::Error>;
+}
+
+struct Typed<T>(PhantomData<fn() -> T>);
+
+impl<T: Component + Serialize + DeserializeOwned> ErasedComponent for Typed<T> {
+    fn borrow(&self, archetype: &Archetype) {
+        archetype.borrow::<T>();
+    }
+
+    fn release(&self, archetype: &Archetype) {
+        archetype.release::<T>();
+    }
+
+    fn register_sparse(&self, world: &mut World) {
+        world.register_sparse::<T>();
+    }
+
+    unsafe fn as_serialize<'a>(&self, ptr: *const u8) -> &'a dyn erased_serde::Serialize {
+        &*ptr.cast::<T>()
+    }
+
+    fn deserialize(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'_>,
+        builder: &mut EntityBuilder,
+    ) -> Result<(), erased_serde::Error> {
+        builder.add(erased_serde::deserialize::<T>(deserializer)?);
+        Ok(())
+    }
+
+    fn deserialize_column(
+        &self,
+        deserializer: &mut dyn erased_serde::Deserializer<'_>,
+    ) -> Result<Box<dyn Column>, erased_serde::Error> {
+        Ok(Box::new(
+            erased_serde::deserialize::<Vec<T>>(deserializer)?.into_iter(),
+        ))
+    }
+}
+
+/// Deserialized components of a single type, consumed in entity order
+trait Column {
+    fn remaining(&self) -> usize;
+    /// Move the next component into `builder`
+    fn add_next(&mut self, builder: &mut EntityBuilder);
+}
+
+impl<T: Component> Column for vec::IntoIter<T> {
+    fn remaining(&self) -> usize {
+        self.len()
+    }
+
+    fn add_next(&mut self, builder: &mut EntityBuilder) {
+        builder.add(self.next().unwrap());
+    }
+}
+
+/// Shared borrow of an archetype's registered components, held for the duration of serialization
+struct ArchetypeColumns<'a> {
+    archetype: &'a Archetype,
+    /// Sorted by name for deterministic output
+    columns: Vec<&'a Registration>,
+    /// Registered sparse types, which each entity may or may not have, sorted by name
+    sparse: Vec<&'a Registration>,
+}
+
+impl<'a> ArchetypeColumns<'a> {
+    fn new(registry: &'a Registry, archetype: &'a Archetype) -> Self {
+        let registered = |id: ComponentId| {
+            let &index = registry.by_id.get(&id)?;
+            Some(&registry.components[index])
+        };
+        let mut columns = archetype
+            .types()
+            .iter()
+            .filter_map(|ty| registered(ty.id()))
+            .collect::<Vec<_>>();
+        columns.sort_unstable_by_key(|x| x.name);
+        let mut sparse = archetype
+            .sparse_sets()
+            .filter_map(|set| registered(set.ty().id()))
+            .collect::<Vec<_>>();
+        sparse.sort_unstable_by_key(|x| x.name);
+        for column in columns.iter().chain(&sparse) {
+            column.vtable.borrow(archetype);
+        }
+        Self {
+            archetype,
+            columns,
+            sparse,
+        }
+    }
+
+    /// The component of type `column` of the entity at `index`, if it has one
+    fn get(&self, column: &Registration, index: u32) -> Option<&dyn erased_serde::Serialize> {
+        assert!(index < self.archetype.len());
+        unsafe {
+            let id = self.archetype.entity_id(index);
+            let (ptr, _) = self.archetype.locate(column.id, column.size, id, index)?;
+            Some(column.vtable.as_serialize(ptr.as_ptr()))
+        }
+    }
+
+    fn entity(&self, meta: &[EntityMeta], index: u32) -> Entity {
+        let id = self.archetype.entity_id(index);
+        Entity {
+            id,
+            generation: meta[id as usize].generation,
+        }
+    }
+}
+
+impl Drop for ArchetypeColumns<'_> {
+    fn drop(&mut self) {
+        for column in self.columns.iter().chain(&self.sparse) {
+            column.vtable.release(self.archetype);
+        }
+    }
+}
+
+/// Row-oriented serialization of a `World`, obtained from `Registry::serialize_rows`
+pub struct SerializeRows<'a> {
+    registry: &'a Registry,
+    world: &'a World,
+}
+
+impl Serialize for SerializeRows<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let archetypes = self.world.archetypes_inner();
+        let meta = self.world.entities_meta();
+        let len = archetypes.iter().map(|x| x.len() as usize).sum();
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for archetype in archetypes.iter().filter(|x| !x.is_empty()) {
+            let columns = ArchetypeColumns::new(self.registry, archetype);
+            for index in 0..archetype.len() {
+                let row = Row {
+                    columns: &columns,
+                    index,
+                };
+                seq.serialize_element(&(columns.entity(meta, index), row))?;
+            }
+        }
+        seq.end()
+    }
+}
+
+struct Row<'a> {
+    columns: &'a ArchetypeColumns<'a>,
+    index: u32,
+}
+
+impl Serialize for Row<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let columns = self.columns;
+        let components = || {
+            columns
+                .columns
+                .iter()
+                .chain(&columns.sparse)
+                .filter_map(move |column| Some((column.name, columns.get(column, self.index)?)))
+        };
+        let mut map = serializer.serialize_map(Some(components().count()))?;
+        for (name, component) in components() {
+            map.serialize_entry(name, component)?;
+        }
+        map.end()
+    }
+}
+
+/// Column-oriented serialization of a `World`, obtained from `Registry::serialize_columns`
+pub struct SerializeColumns<'a> {
+    registry: &'a Registry,
+    world: &'a World,
+}
+
+impl Serialize for SerializeColumns<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let archetypes = self.world.archetypes_inner();
+        let meta = self.world.entities_meta();
+        let mut sparse = self
+            .world
+            .sparse_sets()
+            .filter(|set| !set.ids().is_empty())
+            .filter_map(|set| {
+                let &index = self.registry.by_id.get(&set.ty().id())?;
+                Some((&self.registry.components[index], set))
+            })
+            .collect::<Vec<_>>();
+        sparse.sort_unstable_by_key(|x| x.0.name);
+        let len = archetypes.iter().filter(|x| !x.is_empty()).count() + sparse.len();
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for archetype in archetypes.iter().filter(|x| !x.is_empty()) {
+            seq.serialize_element(&ArchetypeData {
+                columns: ArchetypeColumns::new(self.registry, archetype),
+                meta,
+            })?;
+        }
+        for (registration, set) in sparse {
+            seq.serialize_element(&SparseData::new(registration, set, archetypes, meta))?;
+        }
+        seq.end()
+    }
+}
+
+struct ArchetypeData<'a> {
+    columns: ArchetypeColumns<'a>,
+    meta: &'a [EntityMeta],
+}
+
+impl Serialize for ArchetypeData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&ArchetypeEntities(self))?;
+        tuple.serialize_element(&ArchetypeColumnMap(&self.columns))?;
+        tuple.end()
+    }
+}
+
+struct ArchetypeEntities<'a>(&'a ArchetypeData<'a>);
+
+impl Serialize for ArchetypeEntities<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let columns = &self.0.columns;
+        let len = columns.archetype.len();
+        let mut seq = serializer.serialize_seq(Some(len as usize))?;
+        for index in 0..len {
+            seq.serialize_element(&columns.entity(self.0.meta, index))?;
+        }
+        seq.end()
+    }
+}
+
+struct ArchetypeColumnMap<'a>(&'a ArchetypeColumns<'a>);
+
+impl Serialize for ArchetypeColumnMap<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.columns.len()))?;
+        for &column in &self.0.columns {
+            map.serialize_entry(column.name, &ColumnData(self.0, column))?;
+        }
+        map.end()
+    }
+}
+
+struct ColumnData<'a>(&'a ArchetypeColumns<'a>, &'a Registration);
+
+impl Serialize for ColumnData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = self.0.archetype.len();
+        let mut seq = serializer.serialize_seq(Some(len as usize))?;
+        for index in 0..len {
+            seq.serialize_element(self.0.get(self.1, index).unwrap())?;
+        }
+        seq.end()
+    }
+}
+
+/// Shared borrow of every component of a registered sparse type, serialized in the same form as
+/// an archetype having only that type
+struct SparseData<'a> {
+    registration: &'a Registration,
+    set: &'a SparseSet,
+    /// Each of which tracks borrows of the components of its own entities
+    archetypes: &'a [Archetype],
+    meta: &'a [EntityMeta],
+}
+
+impl<'a> SparseData<'a> {
+    fn new(
+        registration: &'a Registration,
+        set: &'a SparseSet,
+        archetypes: &'a [Archetype],
+        meta: &'a [EntityMeta],
+    ) -> Self {
+        for archetype in archetypes {
+            registration.vtable.borrow(archetype);
+        }
+        Self {
+            registration,
+            set,
+            archetypes,
+            meta,
+        }
+    }
+}
+
+impl Drop for SparseData<'_> {
+    fn drop(&mut self) {
+        for archetype in self.archetypes {
+            self.registration.vtable.release(archetype);
+        }
+    }
+}
+
+impl Serialize for SparseData<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&SparseEntities(self))?;
+        tuple.serialize_element(&SparseColumnMap(self))?;
+        tuple.end()
+    }
+}
+
+struct SparseEntities<'a>(&'a SparseData<'a>);
+
+impl Serialize for SparseEntities<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let ids = self.0.set.ids();
+        let mut seq = serializer.serialize_seq(Some(ids.len()))?;
+        for &id in ids {
+            seq.serialize_element(&Entity {
+                id,
+                generation: self.0.meta[id as usize].generation,
+            })?;
+        }
+        seq.end()
+    }
+}
+
+struct SparseColumnMap<'a>(&'a SparseData<'a>);
+
+impl Serialize for SparseColumnMap<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.0.registration.name, &SparseColumn(self.0))?;
+        map.end()
+    }
+}
+
+struct SparseColumn<'a>(&'a SparseData<'a>);
+
+impl Serialize for SparseColumn<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = self.0;
+        let len = data.set.ids().len();
+        let mut seq = serializer.serialize_seq(Some(len))?;
+        for slot in 0..len {
+            let component = unsafe {
+                data.registration
+                    .vtable
+                    .as_serialize(data.set.data().as_ptr().add(slot * data.registration.size))
+            };
+            seq.serialize_element(component)?;
+        }
+        seq.end()
+    }
+}
+
+struct RowsVisitor<'a>(&'a Registry);
+
+impl<'de> Visitor<'de> for RowsVisitor<'_> {
+    type Value = World;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence of entities")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<World, A::Error> {
+        let mut world = self.0.new_world();
+        let mut builder = EntityBuilder::new();
+        while let Some(()) = seq.next_element_seed(RowSeed {
+            registry: self.0,
+            world: &mut world,
+            builder: &mut builder,
+        })? {}
+        Ok(world)
+    }
+}
+
+/// Spawns a single entity
+struct RowSeed<'a> {
+    registry: &'a Registry,
+    world: &'a mut World,
+    builder: &'a mut EntityBuilder,
+}
+
+impl<'de> DeserializeSeed<'de> for RowSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de> Visitor<'de> for RowSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an entity and its components")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let entity = seq
+            .next_element::<Entity>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        self.registry.check_entity(entity)?;
+        let components = ComponentsSeed {
+            registry: self.registry,
+            builder: self.builder,
+        };
+        if seq.next_element_seed(components)?.is_none() {
+            return Err(de::Error::invalid_length(1, &self));
+        }
+        self.world.spawn_at(entity, self.builder.build());
+        Ok(())
+    }
+}
+
+/// Adds a map of named components to an `EntityBuilder`
+struct ComponentsSeed<'a> {
+    registry: &'a Registry,
+    builder: &'a mut EntityBuilder,
+}
+
+impl<'de> DeserializeSeed<'de> for ComponentsSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentsSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of component names to components")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(index) = map.next_key_seed(NameSeed(self.registry))? {
+            map.next_value_seed(ComponentSeed {
+                registration: &self.registry.components[index],
+                builder: self.builder,
+            })?;
+        }
+        Ok(())
+    }
+}
+
+struct ComponentSeed<'a> {
+    registration: &'a Registration,
+    builder: &'a mut EntityBuilder,
+}
+
+impl<'de> DeserializeSeed<'de> for ComponentSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        self.registration
+            .vtable
+            .deserialize(&mut deserializer, self.builder)
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Looks up a component name, yielding its index in `Registry::components`
+struct NameSeed<'a>(&'a Registry);
+
+impl<'de> DeserializeSeed<'de> for NameSeed<'_> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for NameSeed<'_> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a registered component name")
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<usize, E> {
+        self.0
+            .by_name
+            .get(name)
+            .copied()
+            .ok_or_else(|| E::custom(format_args!("unregistered component name `{}`", name)))
+    }
+}
+
+struct ColumnsVisitor<'a>(&'a Registry);
+
+impl<'de> Visitor<'de> for ColumnsVisitor<'_> {
+    type Value = World;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence of archetypes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<World, A::Error> {
+        let mut world = self.0.new_world();
+        let mut builder = EntityBuilder::new();
+        while let Some(()) = seq.next_element_seed(ArchetypeSeed {
+            registry: self.0,
+            world: &mut world,
+            builder: &mut builder,
+        })? {}
+        Ok(world)
+    }
+}
+
+/// Spawns every entity of a single archetype
+struct ArchetypeSeed<'a> {
+    registry: &'a Registry,
+    world: &'a mut World,
+    builder: &'a mut EntityBuilder,
+}
+
+impl<'de> DeserializeSeed<'de> for ArchetypeSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de> Visitor<'de> for ArchetypeSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a sequence of entities and a map of component columns")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let entities = seq
+            .next_element::<Vec<Entity>>()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        for &entity in &entities {
+            self.registry.check_entity(entity)?;
+        }
+        let mut columns = seq
+            .next_element_seed(ColumnsSeed {
+                registry: self.registry,
+                len: entities.len(),
+            })?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        for entity in entities {
+            for column in &mut columns {
+                column.add_next(self.builder);
+            }
+            // Entities having sparse components appear again after their archetype
+            if self.world.is_spawned(entity) {
+                self.world.insert(entity, self.builder.build()).unwrap();
+            } else {
+                self.world.spawn_at(entity, self.builder.build());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes a map of named component columns, each containing exactly `len` components
+struct ColumnsSeed<'a> {
+    registry: &'a Registry,
+    len: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for ColumnsSeed<'_> {
+    type Value = Vec<Box<dyn Column>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ColumnsSeed<'_> {
+    type Value = Vec<Box<dyn Column>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of component names to component columns")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut columns = Vec::new();
+        while let Some(index) = map.next_key_seed(NameSeed(self.registry))? {
+            let registration = &self.registry.components[index];
+            let column = map.next_value_seed(ColumnSeed(registration))?;
+            if column.remaining() != self.len {
+                return Err(de::Error::custom(format_args!(
+                    "column `{}` has {} components, but the archetype has {} entities",
+                    registration.name,
+                    column.remaining(),
+                    self.len
+                )));
+            }
+            columns.push(column);
+        }
+        Ok(columns)
+    }
+}
+
+struct ColumnSeed<'a>(&'a Registration);
+
+impl<'de> DeserializeSeed<'de> for ColumnSeed<'_> {
+    type Value = Box<dyn Column>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let mut deserializer = <dyn erased_serde::Deserializer>::erase(deserializer);
+        self.0
+            .vtable
+            .deserialize_column(&mut deserializer)
+            .map_err(de::Error::custom)
+    }
+}
@@ -0,0 +1,427 @@
+use crate::alloc::alloc::{alloc, dealloc, Layout};
+use crate::alloc::sync::Arc;
+use crate::alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::mem;
+use core::ptr::{self, NonNull};
+
+use hashbrown::HashMap;
+
+use crate::archetype::{align, ComponentId, TypeInfo};
+use crate::clone::{CloneFn, Cloners, NotCloneable};
+
+/// Component types registered through `World::register_sparse`
+///
+/// Each set is shared with every archetype, through which queries and borrows read and write
+/// components in place. Sets are only added to or removed from through `&mut self`, which the world
+/// only lends out while no archetype is borrowed.
+#[derive(Default)]
+pub(crate) struct SparseSets {
+    sets: HashMap<ComponentId, Arc<SparseCell>>,
+}
+
+impl SparseSets {
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn contains(&self, id: ComponentId) -> bool {
+        self.sets.contains_key(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (ComponentId, &Arc<SparseCell>)> + '_ {
+        self.sets.iter().map(|(&id, set)| (id, set))
+    }
+
+    /// Register `ty` as sparse, returning its new set, or `None` if it already was
+    pub fn register(&mut self, ty: TypeInfo) -> Option<Arc<SparseCell>> {
+        if self.contains(ty.id()) {
+            return None;
+        }
+        let set = Arc::new(SparseCell::new(SparseSet::new(ty)));
+        self.sets.insert(ty.id(), set.clone());
+        Some(set)
+    }
+
+    pub fn get(&self, id: ComponentId) -> Option<&SparseSet> {
+        Some(self.sets.get(&id)?.get())
+    }
+
+    pub fn get_mut(&mut self, id: ComponentId) -> Option<&mut SparseSet> {
+        // Exclusive access to `self` rules out live borrows through archetypes
+        Some(unsafe { self.sets.get(&id)?.get_mut() })
+    }
+
+    /// Remove every component of the entity `id`, passing each to `f` to be moved out or dropped
+    pub unsafe fn take(&mut self, id: u32, mut f: impl FnMut(*mut u8, TypeInfo)) {
+        for set in self.sets.values() {
+            let set = set.get_mut();
+            let ty = *set.ty();
+            set.remove(id, |x| f(x, ty));
+        }
+    }
+
+    /// Drop every component of the entity `id`
+    pub fn despawn(&mut self, id: u32) {
+        unsafe {
+            self.take(id, |x, ty| ty.drop(x));
+        }
+    }
+
+    /// Look up the clone function of every component of the entity `id`
+    pub fn cloners_for(
+        &self,
+        id: u32,
+        cloners: &Cloners,
+    ) -> Result<Vec<(ComponentId, CloneFn)>, NotCloneable> {
+        self.sets
+            .iter()
+            .filter(|(_, set)| set.get().contains(id))
+            .map(|(&ty, set)| Ok((ty, cloners.get(set.get().ty())?)))
+            .collect()
+    }
+
+    /// Drop every component
+    pub fn clear(&mut self) {
+        for set in self.sets.values() {
+            unsafe {
+                set.get_mut().clear();
+            }
+        }
+    }
+
+    /// Release storage not needed by the components currently stored
+    pub fn shrink_to_fit(&mut self) {
+        for set in self.sets.values() {
+            unsafe {
+                set.get_mut().shrink_to_fit();
+            }
+        }
+        self.sets.shrink_to_fit();
+    }
+
+    /// Clone every set, preserving change ticks
+    pub fn try_clone(&self, cloners: &Cloners) -> Result<Self, NotCloneable> {
+        let mut sets = HashMap::default();
+        for (&id, set) in &self.sets {
+            let set = set.get();
+            let clone = cloners.get(set.ty())?;
+            let set = unsafe { set.clone_with(clone) };
+            sets.insert(id, Arc::new(SparseCell::new(set)));
+        }
+        Ok(Self { sets })
+    }
+}
+
+/// Components of a single type, stored by entity ID rather than in archetypes
+///
+/// Used for types registered with `World::register_sparse`. Adding or removing a component only
+/// touches the set, so the entity never moves to another archetype. Components are packed densely
+/// in arbitrary order, and located through a table indexed by entity ID.
+pub(crate) struct SparseSet {
+    ty: TypeInfo,
+    /// Slot of each entity's component, indexed by entity ID, or `u32::MAX` if it has none
+    slots: Vec<u32>,
+    /// ID of the entity owning the component in each occupied slot
+    ids: Vec<u32>,
+    /// Storage for `capacity` components, followed by the ticks at which each was added, and then
+    /// by the ticks at which each was last mutated
+    data: NonNull<u8>,
+    capacity: u32,
+}
+
+impl SparseSet {
+    pub fn new(ty: TypeInfo) -> Self {
+        Self {
+            ty,
+            slots: Vec::new(),
+            ids: Vec::new(),
+            data: NonNull::dangling(),
+            capacity: 0,
+        }
+    }
+
+    pub fn ty(&self) -> &TypeInfo {
+        &self.ty
+    }
+
+    /// Slot of the component of the entity with ID `id`, if it has one
+    #[inline]
+    pub fn slot(&self, id: u32) -> Option<u32> {
+        match self.slots.get(id as usize) {
+            Some(&slot) if slot != u32::MAX => Some(slot),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, id: u32) -> bool {
+        self.slot(id).is_some()
+    }
+
+    /// ID of the entity owning the component in each slot
+    #[cfg(feature = "serde")]
+    pub fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    /// The component in slot 0, which that in slot `n` follows by `n` times the type's size
+    pub fn data(&self) -> NonNull<u8> {
+        self.data
+    }
+
+    /// The tick at which the component in slot 0 was added, followed by those of later slots
+    pub fn added(&self) -> NonNull<u32> {
+        unsafe { self.ticks(self.offsets(self.capacity).0) }
+    }
+
+    /// The tick at which the component in slot 0 was last mutated, followed by those of later
+    /// slots
+    pub fn mutated(&self) -> NonNull<u32> {
+        unsafe { self.ticks(self.offsets(self.capacity).1) }
+    }
+
+    unsafe fn ticks(&self, offset: usize) -> NonNull<u32> {
+        NonNull::new_unchecked(self.data.as_ptr().add(offset).cast::<u32>())
+    }
+
+    /// Locate the component of the entity `id`, and the tick at which it was last mutated
+    pub fn get(&self, id: u32) -> Option<(NonNull<u8>, NonNull<u32>)> {
+        let slot = self.slot(id)? as usize;
+        unsafe {
+            Some((
+                NonNull::new_unchecked(self.data.as_ptr().add(slot * self.ty.layout().size())),
+                NonNull::new_unchecked(self.mutated().as_ptr().add(slot)),
+            ))
+        }
+    }
+
+    /// Move `component` into the set for the entity `id`, marking it mutated, and also added unless
+    /// the entity already had one
+    ///
+    /// Any component the entity already had must have been dropped.
+    pub unsafe fn insert(&mut self, id: u32, component: *const u8, tick: u32) {
+        let slot = match self.slot(id) {
+            Some(slot) => {
+                *self.mutated().as_ptr().add(slot as usize) = tick;
+                slot as usize
+            }
+            None => self.push(id, tick),
+        };
+        let size = self.ty.layout().size();
+        ptr::copy_nonoverlapping(component, self.data.as_ptr().add(slot * size), size);
+    }
+
+    /// Clone the component of the entity `from` into the entity `to`, marking it added
+    ///
+    /// `clone` must be the clone function of this set's type, and `to` must not have a component.
+    pub unsafe fn clone_component(&mut self, clone: CloneFn, from: u32, to: u32, tick: u32) {
+        let from = match self.slot(from) {
+            Some(x) => x as usize,
+            None => return,
+        };
+        // Only take the slot once the clone succeeds, so a panic leaves the set unchanged
+        self.reserve_one();
+        let size = self.ty.layout().size();
+        clone(
+            self.data.as_ptr().add(from * size),
+            self.data.as_ptr().add(self.ids.len() * size),
+            1,
+        );
+        self.push(to, tick);
+    }
+
+    fn reserve_one(&mut self) {
+        if self.ids.len() == self.capacity as usize {
+            self.grow((self.capacity * 2).max(64));
+        }
+    }
+
+    /// Allocate an uninitialized slot for the entity `id`, marking it added and mutated
+    unsafe fn push(&mut self, id: u32, tick: u32) -> usize {
+        self.reserve_one();
+        if self.slots.len() <= id as usize {
+            self.slots.resize(id as usize + 1, u32::MAX);
+        }
+        let slot = self.ids.len();
+        self.slots[id as usize] = slot as u32;
+        self.ids.push(id);
+        *self.added().as_ptr().add(slot) = tick;
+        *self.mutated().as_ptr().add(slot) = tick;
+        slot
+    }
+
+    /// Remove the component of the entity `id`, if any, passing it to `f` to be moved out or
+    /// dropped
+    pub unsafe fn remove(&mut self, id: u32, f: impl FnOnce(*mut u8)) -> bool {
+        let slot = match self.slot(id) {
+            Some(x) => x as usize,
+            None => return false,
+        };
+        let size = self.ty.layout().size();
+        let removed = self.data.as_ptr().add(slot * size);
+        f(removed);
+        let last = self.ids.len() - 1;
+        if slot != last {
+            ptr::copy_nonoverlapping(self.data.as_ptr().add(last * size), removed, size);
+            for &ticks in &[self.added(), self.mutated()] {
+                *ticks.as_ptr().add(slot) = *ticks.as_ptr().add(last);
+            }
+            let moved = self.ids[last];
+            self.ids[slot] = moved;
+            self.slots[moved as usize] = slot as u32;
+        }
+        self.ids.pop();
+        self.slots[id as usize] = u32::MAX;
+        true
+    }
+
+    /// Drop every component
+    pub fn clear(&mut self) {
+        let size = self.ty.layout().size();
+        for (slot, &id) in self.ids.iter().enumerate() {
+            unsafe {
+                self.ty.drop(self.data.as_ptr().add(slot * size));
+            }
+            self.slots[id as usize] = u32::MAX;
+        }
+        self.ids.clear();
+    }
+
+    /// Release storage not needed by the components currently stored
+    pub fn shrink_to_fit(&mut self) {
+        let len = self.ids.len() as u32;
+        if len == 0 {
+            self.release();
+        } else if len != self.capacity {
+            self.grow(len);
+        }
+        // Entities after the last to have a component needn't have slots
+        let slots = self
+            .slots
+            .iter()
+            .rposition(|&slot| slot != u32::MAX)
+            .map_or(0, |x| x + 1);
+        self.slots.truncate(slots);
+        self.slots.shrink_to_fit();
+        self.ids.shrink_to_fit();
+    }
+
+    /// Free the storage of an empty set
+    fn release(&mut self) {
+        debug_assert!(self.ids.is_empty());
+        if self.capacity != 0 {
+            unsafe {
+                dealloc(
+                    self.data.as_ptr(),
+                    Layout::from_size_align_unchecked(
+                        self.offsets(self.capacity).2,
+                        self.data_align(),
+                    ),
+                );
+            }
+        }
+        self.data = NonNull::dangling();
+        self.capacity = 0;
+    }
+
+    /// Clone every component with `clone`, preserving change ticks
+    ///
+    /// `clone` must be the clone function of this set's type.
+    pub unsafe fn clone_with(&self, clone: CloneFn) -> Self {
+        let mut new = Self::new(self.ty);
+        if self.ids.is_empty() {
+            return new;
+        }
+        let len = self.ids.len();
+        new.grow(len as u32);
+        clone(self.data.as_ptr(), new.data.as_ptr(), len);
+        for &(from, to) in &[(self.added(), new.added()), (self.mutated(), new.mutated())] {
+            ptr::copy_nonoverlapping(from.as_ptr(), to.as_ptr(), len);
+        }
+        new.slots = self.slots.clone();
+        new.ids = self.ids.clone();
+        new
+    }
+
+    /// Offsets of the added and mutated ticks, and the total size of storage, for `capacity`
+    /// components
+    fn offsets(&self, capacity: u32) -> (usize, usize, usize) {
+        let capacity = capacity as usize;
+        let added = align(self.ty.layout().size() * capacity, mem::align_of::<u32>());
+        let mutated = added + mem::size_of::<u32>() * capacity;
+        (added, mutated, mutated + mem::size_of::<u32>() * capacity)
+    }
+
+    /// Alignment of `data`, suitable for both components and change ticks
+    fn data_align(&self) -> usize {
+        self.ty.layout().align().max(mem::align_of::<u32>())
+    }
+
+    fn grow(&mut self, capacity: u32) {
+        let len = self.ids.len();
+        let (added, mutated, size) = self.offsets(capacity);
+        unsafe {
+            let data = NonNull::new(alloc(
+                Layout::from_size_align(size, self.data_align()).unwrap(),
+            ))
+            .unwrap();
+            if self.capacity != 0 {
+                let (old_added, old_mutated, old_size) = self.offsets(self.capacity);
+                ptr::copy_nonoverlapping(
+                    self.data.as_ptr(),
+                    data.as_ptr(),
+                    self.ty.layout().size() * len,
+                );
+                for &(from, to) in &[(old_added, added), (old_mutated, mutated)] {
+                    ptr::copy_nonoverlapping(
+                        self.data.as_ptr().add(from),
+                        data.as_ptr().add(to),
+                        mem::size_of::<u32>() * len,
+                    );
+                }
+                dealloc(
+                    self.data.as_ptr(),
+                    Layout::from_size_align_unchecked(old_size, self.data_align()),
+                );
+            }
+            self.data = data;
+        }
+        self.capacity = capacity;
+    }
+}
+
+impl Drop for SparseSet {
+    fn drop(&mut self) {
+        self.clear();
+        self.release();
+    }
+}
+
+/// A `SparseSet` shared by a `World` and each of its archetypes
+///
+/// Like the contents of an archetype, components are read and written in place through shared
+/// references, subject to dynamic borrow checking. The set itself is only modified while the world
+/// is uniquely borrowed.
+pub(crate) struct SparseCell(UnsafeCell<SparseSet>);
+
+// Components are `Send + Sync`, and access is synchronized like that of archetypes
+unsafe impl Send for SparseCell {}
+unsafe impl Sync for SparseCell {}
+
+impl SparseCell {
+    pub fn new(set: SparseSet) -> Self {
+        Self(UnsafeCell::new(set))
+    }
+
+    pub fn get(&self) -> &SparseSet {
+        unsafe { &*self.0.get() }
+    }
+
+    /// # Safety
+    ///
+    /// No other reference to the set may be live, including those returned by `get`
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_mut(&self) -> &mut SparseSet {
+        &mut *self.0.get()
+    }
+}
@@ -12,21 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::alloc::vec::Vec;
-use core::any::TypeId;
+use crate::alloc::alloc::GlobalAlloc;
+use crate::alloc::sync::Arc;
+use crate::alloc::{vec, vec::Vec};
 use core::convert::TryFrom;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use core::{fmt, mem, ptr};
 
 #[cfg(feature = "std")]
 use std::error::Error;
 
-use hashbrown::{HashMap, HashSet};
+use hashbrown::HashMap;
 
-use crate::archetype::Archetype;
-use crate::entities::{Entities, Location};
+use crate::archetype::{Archetype, ComponentId, StorageConfig, TypeInfo};
+use crate::clone::{CloneError, Cloners, NotCloneable};
+use crate::entities::{Entities, EntityMeta, Location};
+use crate::hierarchy::{Ancestors, BreadthFirst, DepthFirst, Hierarchy, HierarchyError};
+use crate::observer::Observers;
+use crate::query::{assert_borrow, ChunkIter, Fetch, QueryMut};
+use crate::sparse::SparseSets;
 use crate::{
-    Bundle, DynamicBundle, Entity, EntityRef, MissingComponent, NoSuchEntity, Query, QueryBorrow,
-    QueryOne, Ref, RefMut,
+    BorrowError, Bundle, ColumnBatch, DynamicBundle, DynamicQueryIter, Entity, EntityBuilder,
+    EntityRange, EntityRef, MissingComponent, NoSuchEntity, Query, QueryBorrow, QueryOne, Ref,
+    RefMut, TakenEntity,
 };
 
 /// An unordered collection of entities, each having any number of distinctly typed components
@@ -37,28 +45,88 @@ use crate::{
 /// The components of entities who have the same set of component types are stored in contiguous
 /// runs, allowing for extremely fast, cache-friendly iteration.
 pub struct World {
+    id: u64,
     entities: Entities,
-    index: HashMap<Vec<TypeId>, u32>,
+    index: HashMap<Vec<ComponentId>, u32>,
     archetypes: Vec<Archetype>,
     archetype_generation: u64,
+    /// Number of times archetypes were removed, changing the indices of others
+    archetype_removals: u64,
+    tick: u32,
+    hierarchy: Hierarchy,
+    observers: Observers,
+    cloners: Cloners,
+    sparse: SparseSets,
+    storage: StorageConfig,
 }
 
 impl World {
     /// Create an empty world
     pub fn new() -> Self {
+        Self::with_storage(StorageConfig::default())
+    }
+
+    fn with_storage(storage: StorageConfig) -> Self {
         // `flush` assumes archetype 0 always exists, representing entities with no components.
-        let mut archetypes = Vec::new();
-        archetypes.push(Archetype::new(Vec::new()));
+        let archetypes = vec![Archetype::new(Vec::new(), 0, &storage)];
         let mut index = HashMap::default();
         index.insert(Vec::new(), 0);
+        // AtomicU64 is unsupported on some no_std platforms
+        static ID: AtomicUsize = AtomicUsize::new(1);
+        let id = ID.fetch_add(1, Ordering::Relaxed) as u64;
         Self {
+            id,
             entities: Entities::default(),
             index,
             archetypes,
             archetype_generation: 0,
+            archetype_removals: 0,
+            tick: 0,
+            hierarchy: Hierarchy::default(),
+            observers: Observers::default(),
+            cloners: Cloners::default(),
+            sparse: SparseSets::default(),
+            storage,
         }
     }
 
+    /// Configure how an empty world will store components, see `WorldBuilder`
+    pub fn builder() -> WorldBuilder {
+        WorldBuilder::new()
+    }
+
+    /// Create an empty world that divides the storage of each archetype into chunks of about
+    /// `size` bytes
+    ///
+    /// Shorthand for `World::builder().chunk_size(size).build()`.
+    ///
+    /// By default, the components of an archetype share one buffer, which is reallocated and
+    /// copied in full whenever it runs out of room, causing occasional pauses in large worlds.
+    /// Chunked storage instead grows by adding chunks, so existing components never move, at a
+    /// small cost to random access. Queries visit one chunk at a time, with
+    /// `QueryBorrow::iter_chunks` and `World::query_dynamic` yielding each separately. Every chunk
+    /// has room for at least one entity, so may exceed `size` given very large components.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::with_chunk_size(16 * 1024);
+    /// world.spawn_batch((0..10_000).map(|i| (i, i as f32)));
+    /// assert!(world.query::<&i32>().iter_chunks().count() > 1);
+    /// let sum = world.query_mut::<&i32>().into_items().map(|&i| i64::from(i)).sum::<i64>();
+    /// assert_eq!(sum, 49_995_000);
+    /// ```
+    pub fn with_chunk_size(size: usize) -> Self {
+        Self::builder().chunk_size(size).build()
+    }
+
+    /// Create an empty world whose archetypes store components in memory obtained from `allocator`
+    ///
+    /// Shorthand for `World::builder().allocator(allocator).build()`.
+    pub fn with_allocator(allocator: impl GlobalAlloc + Send + Sync + 'static) -> Self {
+        Self::builder().allocator(allocator).build()
+    }
+
     /// Create an entity with certain components
     ///
     /// Returns the ID of the newly created entity.
@@ -83,29 +151,59 @@ impl World {
         self.flush();
 
         let entity = self.entities.alloc();
-        let archetype_id = components.with_ids(|ids| {
-            self.index.get(ids).copied().unwrap_or_else(|| {
-                let x = self.archetypes.len() as u32;
-                self.archetypes.push(Archetype::new(components.type_info()));
-                self.index.insert(ids.to_vec(), x);
-                self.archetype_generation += 1;
-                x
-            })
-        });
+        self.spawn_inner(entity, components);
+        entity
+    }
+
+    /// Create an entity with certain components and a specific handle
+    ///
+    /// Any existing entity with the same ID is despawned first. Useful for reconstructing a world
+    /// that's been serialized, so that handles stored inside components remain valid. Prefer
+    /// `spawn` otherwise.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let mut other = World::new();
+    /// other.spawn_at(a, (456,));
+    /// assert_eq!(*other.get::<i32>(a).unwrap(), 456);
+    /// ```
+    pub fn spawn_at(&mut self, entity: Entity, components: impl DynamicBundle) {
+        // Ensure all entity allocations are accounted for so `self.entities` can realloc if
+        // necessary
+        self.flush();
+
+        if let Some(previous) = self.entities.occupant(entity.id) {
+            self.despawn(previous).unwrap();
+        }
+        self.entities.alloc_at(entity);
+        self.spawn_inner(entity, components);
+    }
+
+    fn spawn_inner(&mut self, entity: Entity, components: impl DynamicBundle) {
+        let archetype_id =
+            components.with_ids(|ids| self.archetype_for(ids, || components.type_info()));
 
         let archetype = &mut self.archetypes[archetype_id as usize];
+        let sparse = &mut self.sparse;
+        let tick = self.tick;
         unsafe {
             let index = archetype.allocate(entity.id);
             components.put(|ptr, ty, size| {
-                archetype.put_dynamic(ptr, ty, size, index);
+                match sparse.get_mut(ty) {
+                    Some(set) => set.insert(entity.id, ptr, tick),
+                    None => archetype.put_dynamic(ptr, ty, size, index),
+                }
                 true
             });
             self.entities.meta[entity.id as usize].location = Location {
                 archetype: archetype_id,
                 index,
             };
+            self.observers.spawned(entity, archetype, index);
         }
-        entity
     }
 
     /// Efficiently spawn a large number of entities with the same components
@@ -141,7 +239,53 @@ impl World {
             entities: &mut self.entities,
             archetype_id,
             archetype: &mut self.archetypes[archetype_id as usize],
+            observers: &mut self.observers,
+            sparse: &mut self.sparse,
+            tick: self.tick,
+        }
+    }
+
+    /// Spawn one entity per row of `batch`, copying each column into storage in bulk
+    ///
+    /// Faster than `spawn_batch` when components are already laid out in one `Vec` per type. The
+    /// spawned entities have consecutive IDs, and are returned in the same order as the rows of
+    /// `batch`. See `ColumnBatch` for an example.
+    pub fn spawn_column_batch(&mut self, batch: ColumnBatch) -> EntityRange {
+        self.flush();
+        let len = batch.len();
+        let (ids, generation) = self.entities.alloc_many(len);
+        if len == 0 {
+            return EntityRange::new(ids, generation);
+        }
+
+        let mut columns = batch.into_columns();
+        let elements = columns.iter().map(|x| x.0.id()).collect::<Vec<_>>();
+        let archetype_id = self.archetype_for(&elements, || columns.iter().map(|x| x.0).collect());
+        let archetype = &mut self.archetypes[archetype_id as usize];
+        unsafe {
+            let first = archetype.allocate_many(ids.clone());
+            for (ty, components) in &mut columns {
+                match self.sparse.get_mut(ty.id()) {
+                    Some(set) => {
+                        for (i, id) in ids.clone().enumerate() {
+                            let component = components.as_ptr().add(i * ty.layout().size());
+                            set.insert(id, component, self.tick);
+                        }
+                    }
+                    None => archetype.put_column(ty, components.as_ptr(), first, len),
+                }
+                components.forget_elements();
+            }
+            for (index, id) in (first..).zip(ids.clone()) {
+                self.entities.meta[id as usize].location = Location {
+                    archetype: archetype_id,
+                    index,
+                };
+                let entity = Entity { generation, id };
+                self.observers.spawned(entity, archetype, index);
+            }
         }
+        EntityRange::new(ids, generation)
     }
 
     /// Allocate an entity ID concurrently
@@ -159,15 +303,345 @@ impl World {
     }
 
     /// Destroy an entity and all its components
+    ///
+    /// The entity is detached from its parent, and its children are left without a parent. See
+    /// `despawn_recursive` to also destroy the children.
     pub fn despawn(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
         self.flush();
-        let loc = self.entities.free(entity)?;
-        if let Some(moved) = unsafe { self.archetypes[loc.archetype as usize].remove(loc.index) } {
-            self.entities.meta[moved as usize].location.index = loc.index;
+        let loc = *self.entities.get_mut(entity)?;
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        unsafe {
+            self.observers.despawned(entity, archetype, loc.index);
+        }
+        self.entities.free(entity).unwrap();
+        self.hierarchy.remove(entity.id);
+        self.sparse.despawn(entity.id);
+        unsafe {
+            if let Some(moved) = archetype.remove(loc.index) {
+                self.entities.meta[moved as usize].location.index = loc.index;
+            }
         }
         Ok(())
     }
 
+    /// Destroy every entity in `entities`
+    ///
+    /// Faster than calling `despawn` repeatedly, since each archetype is compacted only once.
+    /// Entities that were already despawned are ignored. As with `despawn`, each entity is detached
+    /// from the hierarchy.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let entities = world.spawn_batch((0..10).map(|i| (i,))).collect::<Vec<_>>();
+    /// world.despawn_batch(entities.iter().copied().filter(|e| e.id() % 2 == 0));
+    /// assert_eq!(world.iter().count(), 5);
+    /// ```
+    pub fn despawn_batch(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        self.flush();
+        let mut locations = Vec::new();
+        for entity in entities {
+            if let Ok(loc) = self.entities.get_mut(entity) {
+                locations.push((loc.archetype, loc.index, entity));
+            }
+        }
+        locations.sort_unstable_by_key(|&(archetype, index, _)| (archetype, index));
+        locations.dedup_by_key(|&mut (archetype, index, _)| (archetype, index));
+        for &(archetype, index, entity) in &locations {
+            unsafe {
+                self.observers
+                    .despawned(entity, &self.archetypes[archetype as usize], index);
+            }
+        }
+        for &(_, _, entity) in &locations {
+            self.entities.free(entity).unwrap();
+            self.hierarchy.remove(entity.id);
+            self.sparse.despawn(entity.id);
+        }
+
+        let mut remove = Vec::new();
+        for group in locations.chunk_by(|x, y| x.0 == y.0) {
+            let archetype = &mut self.archetypes[group[0].0 as usize];
+            remove.clear();
+            remove.resize(archetype.len() as usize, false);
+            for &(_, index, _) in group {
+                remove[index as usize] = true;
+            }
+            let meta = &mut self.entities.meta;
+            unsafe {
+                archetype.remove_many(&remove, |id, index| {
+                    meta[id as usize].location.index = index;
+                });
+            }
+        }
+    }
+
+    /// Despawn every entity matching `Q` for which `f` returns `false`
+    ///
+    /// Entities that don't match `Q` are kept. Each archetype is compacted at most once, so this is
+    /// faster than collecting entities to `despawn`. As with `despawn`, each destroyed entity is
+    /// detached from the hierarchy.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((1, true));
+    /// let b = world.spawn((2, false));
+    /// let c = world.spawn(("abc",));
+    /// world.retain::<(&i32, &bool), _>(|_, (_, &keep)| keep);
+    /// assert!(world.contains(a));
+    /// assert!(!world.contains(b));
+    /// assert!(world.contains(c));
+    /// ```
+    pub fn retain<Q: Query, F>(&mut self, mut f: F)
+    where
+        F: for<'a> FnMut(Entity, <Q::Fetch as Fetch<'a>>::Item) -> bool,
+    {
+        self.flush();
+        let mut remove = Vec::new();
+        for archetype in &mut self.archetypes {
+            assert_borrow::<Q>(archetype);
+            let meta = &self.entities.meta;
+            let mut iter = match unsafe { ChunkIter::<Q>::new(meta, archetype, 0..archetype.len()) }
+            {
+                Some(x) => x,
+                None => continue,
+            };
+            remove.clear();
+            remove.resize(archetype.len() as usize, false);
+            let mut any = false;
+            loop {
+                while let Some((id, item)) = unsafe { iter.next() } {
+                    let meta = &meta[id as usize];
+                    let entity = Entity {
+                        id,
+                        generation: meta.generation,
+                    };
+                    if !f(entity, item) {
+                        any = true;
+                        remove[meta.location.index as usize] = true;
+                    }
+                }
+                if !unsafe { iter.next_chunk() } {
+                    break;
+                }
+            }
+            if !any {
+                continue;
+            }
+
+            let entities = &self.entities;
+            let removed = (0..archetype.len())
+                .filter(|&index| remove[index as usize])
+                .map(|index| {
+                    let id = archetype.entity_id(index);
+                    let entity = Entity {
+                        id,
+                        generation: entities.meta[id as usize].generation,
+                    };
+                    (index, entity)
+                })
+                .collect::<Vec<_>>();
+            for &(index, entity) in &removed {
+                unsafe {
+                    self.observers.despawned(entity, archetype, index);
+                }
+            }
+            for (_, entity) in removed {
+                self.entities.free(entity).unwrap();
+                self.hierarchy.remove(entity.id);
+                self.sparse.despawn(entity.id);
+            }
+            let meta = &mut self.entities.meta;
+            unsafe {
+                archetype.remove_many(&remove, |id, index| {
+                    meta[id as usize].location.index = index;
+                });
+            }
+        }
+    }
+
+    /// Destroy an entity, moving all its components into a bundle
+    ///
+    /// The returned bundle can be spawned into this or any other `World` without knowing which
+    /// components the entity had. As with `despawn`, the entity is detached from the hierarchy.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut staging = World::new();
+    /// let mut world = World::new();
+    /// let a = staging.spawn((123, "abc"));
+    /// let b = world.spawn(staging.take(a).unwrap());
+    /// assert!(!staging.contains(a));
+    /// assert_eq!(*world.get::<i32>(b).unwrap(), 123);
+    /// assert_eq!(*world.get::<&str>(b).unwrap(), "abc");
+    /// ```
+    pub fn take(&mut self, entity: Entity) -> Result<TakenEntity, NoSuchEntity> {
+        self.flush();
+        let loc = *self.entities.get_mut(entity)?;
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        unsafe {
+            self.observers.despawned(entity, archetype, loc.index);
+        }
+        self.entities.free(entity).unwrap();
+        self.hierarchy.remove(entity.id);
+        let mut builder = EntityBuilder::new();
+        unsafe {
+            let types = archetype.types().to_vec();
+            if let Some(moved) = archetype.move_to(loc.index, |ptr, ty, _| {
+                let ty = *types.iter().find(|x| x.id() == ty).unwrap();
+                builder.add_dynamic(ptr, ty);
+            }) {
+                self.entities.meta[moved as usize].location.index = loc.index;
+            }
+            self.sparse.take(entity.id, |ptr, ty| {
+                builder.add_dynamic(ptr, ty);
+            });
+        }
+        Ok(TakenEntity::new(builder))
+    }
+
+    /// Allow components of type `T` to be cloned by `clone_entity` and `try_clone`
+    pub fn register_clone<T: Component + Clone>(&mut self) {
+        self.cloners.register::<T>();
+    }
+
+    /// Store components of type `T` outside of archetypes, so that adding or removing one doesn't
+    /// move its entity to another archetype
+    ///
+    /// Suits components that are inserted and removed often, like markers. Inserting or removing a
+    /// sparse component takes constant time regardless of how many components the entity has, and
+    /// never creates archetypes. Queries, including `With` and `Without`, work as usual, but must
+    /// check each entity individually, so iterating over sparse components is slower. Sparse
+    /// components can't be fetched by `QueryBorrow::iter_chunks`, and are ignored by
+    /// `query_dynamic`. See `Registry::register_sparse` to preserve sparseness through
+    /// serialization.
+    ///
+    /// Has no effect if `T` is already sparse. Panics if an archetype containing `T` exists, i.e.
+    /// if any entity has ever had a `T`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Stunned;
+    ///
+    /// let mut world = World::new();
+    /// world.register_sparse::<Stunned>();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456, true));
+    /// let generation = world.archetypes_generation();
+    /// world.insert_one(a, Stunned).unwrap();
+    /// assert_eq!(world.archetypes_generation(), generation);
+    /// assert_eq!(world.query::<With<Stunned, &i32>>().iter().count(), 1);
+    /// assert_eq!(world.query::<Without<Stunned, &i32>>().iter().next().unwrap().0, b);
+    /// world.remove_one::<Stunned>(a).unwrap();
+    /// assert!(world.get::<Stunned>(a).is_err());
+    /// ```
+    pub fn register_sparse<T: Component>(&mut self) {
+        let ty = TypeInfo::of::<T>();
+        assert!(
+            !self.archetypes.iter().any(|x| x.has::<T>()),
+            "{} must be registered as sparse before any entity has one",
+            ty.name()
+        );
+        let set = match self.sparse.register(ty) {
+            Some(x) => x,
+            None => return,
+        };
+        for archetype in &mut self.archetypes {
+            archetype.add_sparse(ty.id(), set.clone());
+        }
+        // Queries involving `T` may now match existing archetypes
+        self.archetype_generation += 1;
+    }
+
+    /// Spawn a copy of `entity`, cloning each of its components
+    ///
+    /// Every component type of `entity` must have been registered with `register_clone`. The copy
+    /// has no parent or children.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.register_clone::<i32>();
+    /// let a = world.spawn((123,));
+    /// let b = world.clone_entity(a).unwrap();
+    /// assert_eq!(*world.get::<i32>(b).unwrap(), 123);
+    /// let c = world.spawn((456, "abc"));
+    /// assert!(world.clone_entity(c).is_err());
+    /// ```
+    pub fn clone_entity(&mut self, entity: Entity) -> Result<Entity, CloneError> {
+        self.flush();
+        let loc = self.entities.get(entity)?;
+        let archetype = &mut self.archetypes[loc.archetype as usize];
+        let fns = self.cloners.get_all(archetype.types())?;
+        let sparse_fns = self.sparse.cloners_for(entity.id, &self.cloners)?;
+        let clone = unsafe {
+            // Nothing is committed until the components stored in the archetype are cloned, so a
+            // panicking `Clone` of one leaves the world unchanged. Sparse components are cloned
+            // into an entity that's already complete.
+            let index = archetype.clone_row(&fns, loc.index);
+            let clone = self.entities.alloc();
+            archetype.push_cloned(clone.id);
+            self.entities.meta[clone.id as usize].location = Location {
+                archetype: loc.archetype,
+                index,
+            };
+            for (ty, f) in sparse_fns {
+                let set = self.sparse.get_mut(ty).unwrap();
+                set.clone_component(f, entity.id, clone.id, self.tick);
+            }
+            self.observers.spawned(clone, archetype, index);
+            clone
+        };
+        Ok(clone)
+    }
+
+    /// Copy every entity into a new `World`, preserving entity IDs, change ticks, and the hierarchy
+    ///
+    /// Every component type in the world must have been registered with `register_clone`, and the
+    /// registrations carry over to the copy. Observers do not. Components are cloned a whole
+    /// archetype column at a time.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.register_clone::<i32>();
+    /// let a = world.spawn((123,));
+    /// let snapshot = world.try_clone().unwrap();
+    /// *world.get_mut::<i32>(a).unwrap() = 456;
+    /// assert_eq!(*snapshot.get::<i32>(a).unwrap(), 123);
+    /// ```
+    pub fn try_clone(&mut self) -> Result<World, NotCloneable> {
+        self.flush();
+        let mut archetypes = self
+            .archetypes
+            .iter()
+            .map(|x| x.try_clone(&self.cloners))
+            .collect::<Result<Vec<_>, _>>()?;
+        let sparse = self.sparse.try_clone(&self.cloners)?;
+        for archetype in &mut archetypes {
+            for (id, set) in sparse.iter() {
+                archetype.add_sparse(id, set.clone());
+            }
+        }
+        let mut world = World::with_storage(self.storage.clone());
+        world.entities = self.entities.clone();
+        world.index = self.index.clone();
+        world.archetypes = archetypes;
+        world.archetype_generation = self.archetype_generation;
+        world.tick = self.tick;
+        world.hierarchy = self.hierarchy.clone();
+        world.cloners = self.cloners.clone();
+        world.sparse = sparse;
+        Ok(world)
+    }
+
     /// Ensure `additional` entities with exact components `T` can be spawned without reallocating
     pub fn reserve<T: Bundle>(&mut self, additional: u32) {
         self.reserve_inner::<T>(additional);
@@ -177,15 +651,7 @@ impl World {
         self.flush();
         self.entities.reserve(additional);
 
-        let archetype_id = T::with_static_ids(|ids| {
-            self.index.get(ids).copied().unwrap_or_else(|| {
-                let x = self.archetypes.len() as u32;
-                self.archetypes.push(Archetype::new(T::static_type_info()));
-                self.index.insert(ids.to_vec(), x);
-                self.archetype_generation += 1;
-                x
-            })
-        });
+        let archetype_id = T::with_static_ids(|ids| self.archetype_for(ids, T::static_type_info));
 
         self.archetypes[archetype_id as usize].reserve(additional);
         archetype_id
@@ -195,10 +661,250 @@ impl World {
     ///
     /// Preserves allocated storage for reuse.
     pub fn clear(&mut self) {
+        if !self.observers.ignores_despawn() {
+            for x in &self.archetypes {
+                for index in 0..x.len() {
+                    let id = x.entity_id(index);
+                    let entity = Entity {
+                        id,
+                        generation: self.entities.meta[id as usize].generation,
+                    };
+                    unsafe {
+                        self.observers.despawned(entity, x, index);
+                    }
+                }
+            }
+        }
         for x in &mut self.archetypes {
             x.clear();
         }
+        self.sparse.clear();
         self.entities.clear();
+        self.hierarchy.clear();
+    }
+
+    /// Release memory not needed to store the current entities
+    ///
+    /// Storage is retained as entities are despawned or moved between archetypes, so that it can be
+    /// reused without reallocating. This frees whatever is unused, e.g. after a mass despawn, at the
+    /// cost of reallocating if the world grows again. Archetypes themselves remain, even if empty;
+    /// see `remove_empty_archetypes`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let entities = world.spawn_batch((0..1000).map(|i| (i,))).collect::<Vec<_>>();
+    /// world.despawn_batch(entities[1..].iter().copied());
+    /// world.shrink_to_fit();
+    /// assert_eq!(*world.get::<i32>(entities[0]).unwrap(), 0);
+    /// ```
+    pub fn shrink_to_fit(&mut self) {
+        self.flush();
+        for archetype in &mut self.archetypes {
+            archetype.shrink_to_fit();
+        }
+        self.archetypes.shrink_to_fit();
+        self.index.shrink_to_fit();
+        self.sparse.shrink_to_fit();
+        self.hierarchy.shrink_to_fit();
+    }
+
+    /// Destroy every archetype that contains no entities
+    ///
+    /// An archetype exists for each combination of component types any entity has ever had, so a
+    /// world whose entities pass through many short-lived combinations accumulates them without
+    /// bound. Removing them frees their storage and speeds up queries, which must inspect every
+    /// archetype, at the cost of recreating any that are needed again.
+    ///
+    /// Changes `archetypes_generation` if any archetype was removed, as the others may then be
+    /// visited by `archetypes` in a different order.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// world.insert_one(a, true).unwrap();
+    /// world.remove_one::<bool>(a).unwrap();
+    /// let archetypes = world.archetypes().len();
+    /// let generation = world.archetypes_generation();
+    /// world.remove_empty_archetypes();
+    /// assert!(world.archetypes().len() < archetypes);
+    /// assert_ne!(world.archetypes_generation(), generation);
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 123);
+    /// ```
+    pub fn remove_empty_archetypes(&mut self) {
+        self.flush();
+        // New index of each archetype, or `u32::MAX` if it's removed. Archetype 0 is always kept,
+        // as `flush` relies on it.
+        let mut remap = Vec::with_capacity(self.archetypes.len());
+        let mut kept = 0;
+        for (index, archetype) in self.archetypes.iter().enumerate() {
+            if index == 0 || !archetype.is_empty() {
+                remap.push(kept);
+                kept += 1;
+            } else {
+                remap.push(u32::MAX);
+            }
+        }
+        if kept as usize == self.archetypes.len() {
+            return;
+        }
+
+        let mut index = 0;
+        self.archetypes.retain(|_| {
+            index += 1;
+            remap[index - 1] != u32::MAX
+        });
+        self.index.retain(|_, x| {
+            *x = remap[*x as usize];
+            *x != u32::MAX
+        });
+        for (index, archetype) in self.archetypes.iter_mut().enumerate() {
+            archetype.remap_edges(&remap);
+            for i in 0..archetype.len() {
+                let id = archetype.entity_id(i);
+                self.entities.meta[id as usize].location.archetype = index as u32;
+            }
+        }
+        self.archetype_generation += 1;
+        self.archetype_removals += 1;
+    }
+
+    /// Destroy an entity, its children, their children, and so on
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// let b = world.spawn(());
+    /// let c = world.spawn(());
+    /// world.set_parent(b, a).unwrap();
+    /// world.set_parent(c, b).unwrap();
+    /// world.despawn_recursive(b).unwrap();
+    /// assert!(world.contains(a));
+    /// assert!(!world.contains(b));
+    /// assert!(!world.contains(c));
+    /// assert!(world.children_of(a).unwrap().is_empty());
+    /// ```
+    pub fn despawn_recursive(&mut self, entity: Entity) -> Result<(), NoSuchEntity> {
+        self.flush();
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        let doomed = core::iter::once(entity)
+            .chain(self.hierarchy.depth_first(entity.id))
+            .collect::<Vec<_>>();
+        for x in doomed {
+            self.despawn(x).unwrap();
+        }
+        Ok(())
+    }
+
+    /// Make `parent` the parent of `child`, replacing any previous parent
+    ///
+    /// Parent-child relationships are tracked by the world rather than by components, so they
+    /// remain consistent when entities are despawned: see `despawn` and `despawn_recursive`.
+    /// Fails if `parent` is `child` or one of its descendants.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn(());
+    /// let b = world.spawn(());
+    /// world.set_parent(b, a).unwrap();
+    /// assert_eq!(world.parent_of(b).unwrap(), Some(a));
+    /// assert_eq!(world.children_of(a).unwrap(), &[b]);
+    /// assert_eq!(world.set_parent(a, b), Err(HierarchyError::Cycle));
+    /// ```
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), HierarchyError> {
+        self.flush();
+        if !self.contains(child) || !self.contains(parent) {
+            return Err(HierarchyError::NoSuchEntity);
+        }
+        self.hierarchy.set_parent(child, parent)
+    }
+
+    /// Detach `child` from its parent, returning the former parent, if any
+    pub fn remove_parent(&mut self, child: Entity) -> Result<Option<Entity>, NoSuchEntity> {
+        self.flush();
+        if !self.contains(child) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.hierarchy.remove_parent(child.id))
+    }
+
+    /// The parent of `entity`, if any
+    pub fn parent_of(&self, entity: Entity) -> Result<Option<Entity>, NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.hierarchy.parent(entity.id))
+    }
+
+    /// The children of `entity`, in the order they were attached
+    pub fn children_of(&self, entity: Entity) -> Result<&[Entity], NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.hierarchy.children(entity.id))
+    }
+
+    /// Iterate over the parent of `entity`, its parent, and so on
+    pub fn ancestors(&self, entity: Entity) -> Result<Ancestors<'_>, NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.hierarchy.ancestors(entity.id))
+    }
+
+    /// Iterate over the descendants of `entity`, not including `entity` itself, in depth-first
+    /// pre-order
+    ///
+    /// Every entity is visited after its parent, making this suitable for propagating state such
+    /// as transforms down the hierarchy.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// struct Local(i32);
+    /// struct Global(i32);
+    ///
+    /// let mut world = World::new();
+    /// let root = world.spawn((Local(1), Global(1)));
+    /// let a = world.spawn((Local(10), Global(0)));
+    /// let b = world.spawn((Local(100), Global(0)));
+    /// world.set_parent(a, root).unwrap();
+    /// world.set_parent(b, a).unwrap();
+    /// for entity in world.descendants_depth_first(root).unwrap() {
+    ///     let parent = world.parent_of(entity).unwrap().unwrap();
+    ///     let parent_global = world.get::<Global>(parent).unwrap().0;
+    ///     world.get_mut::<Global>(entity).unwrap().0 = parent_global + world.get::<Local>(entity).unwrap().0;
+    /// }
+    /// assert_eq!(world.get::<Global>(b).unwrap().0, 111);
+    /// ```
+    pub fn descendants_depth_first(&self, entity: Entity) -> Result<DepthFirst<'_>, NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.hierarchy.depth_first(entity.id))
+    }
+
+    /// Iterate over the descendants of `entity`, not including `entity` itself, in order of
+    /// increasing depth
+    ///
+    /// Like `descendants_depth_first`, every entity is visited after its parent.
+    pub fn descendants_breadth_first(
+        &self,
+        entity: Entity,
+    ) -> Result<BreadthFirst<'_>, NoSuchEntity> {
+        if !self.contains(entity) {
+            return Err(NoSuchEntity);
+        }
+        Ok(self.hierarchy.breadth_first(entity.id))
     }
 
     /// Whether `entity` still exists
@@ -270,7 +976,98 @@ impl World {
     /// ```
     pub fn query_one<Q: Query>(&self, entity: Entity) -> Result<QueryOne<'_, Q>, NoSuchEntity> {
         let loc = self.entities.get(entity)?;
-        Ok(unsafe { QueryOne::new(&self.archetypes[loc.archetype as usize], loc.index) })
+        Ok(unsafe {
+            QueryOne::new(
+                &self.entities.meta,
+                &self.archetypes[loc.archetype as usize],
+                loc.index,
+            )
+        })
+    }
+
+    /// Query a uniquely borrowed world
+    ///
+    /// Like `query`, but faster because dynamic borrow checks can be skipped. Panics if `Q` would
+    /// alias a component with itself, like `(&mut T, &T)`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((456,));
+    /// for (_, x) in world.query_mut::<&mut i32>() {
+    ///     *x += 1;
+    /// }
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 124);
+    /// assert_eq!(*world.get::<i32>(b).unwrap(), 457);
+    /// ```
+    pub fn query_mut<Q: Query>(&mut self) -> QueryMut<'_, Q> {
+        QueryMut::new(&self.entities.meta, &mut self.archetypes)
+    }
+
+    /// Iterate over the entities having every component type in `ids`, a chunk at a time
+    ///
+    /// Intended for component types defined at runtime, whose columns can then be borrowed as bytes
+    /// from each chunk. Columns are borrowed and released individually, and borrowing a column
+    /// that's already uniquely borrowed panics.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// # use std::alloc::Layout;
+    /// unsafe fn drop_nothing(_: *mut u8) {}
+    /// let ty = unsafe { TypeInfo::dynamic(0, Layout::new::<[u8; 2]>(), drop_nothing, "pair") };
+    /// let mut world = World::new();
+    /// let mut builder = EntityBuilder::new();
+    /// builder.add(true).add_bytes(ty, &[1, 2]);
+    /// let e = world.spawn(builder.build());
+    /// for chunk in world.query_dynamic(&[ty.id()]) {
+    ///     assert_eq!(chunk.entities().collect::<Vec<_>>(), [e]);
+    ///     chunk.get_mut(ty.id()).unwrap()[1] = 3;
+    /// }
+    /// let chunk = world.query_dynamic(&[ty.id()]).next().unwrap();
+    /// assert_eq!(&*chunk.get(ty.id()).unwrap(), &[1, 3]);
+    /// ```
+    pub fn query_dynamic<'q>(&self, ids: &'q [ComponentId]) -> DynamicQueryIter<'_, 'q> {
+        DynamicQueryIter::new(&self.entities.meta, &self.archetypes, ids)
+    }
+
+    /// Query a single entity in a uniquely borrowed world
+    ///
+    /// Like `query_one`, but faster because dynamic borrow checks can be skipped, and no `QueryOne`
+    /// needs to be kept alive while the results are in use. Panics if `Q` would alias a component
+    /// with itself, like `(&mut T, &T)`.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123, true));
+    /// let (number, flag) = world.query_one_mut::<(&mut i32, &bool)>(a).unwrap();
+    /// if *flag { *number *= 2; }
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 246);
+    /// assert_eq!(world.query_one_mut::<&&str>(a).err(), Some(QueryOneError::Unsatisfied));
+    /// ```
+    pub fn query_one_mut<Q: Query>(
+        &mut self,
+        entity: Entity,
+    ) -> Result<<Q::Fetch as Fetch<'_>>::Item, QueryOneError> {
+        let loc = self.entities.get(entity)?;
+        let archetype = &self.archetypes[loc.archetype as usize];
+        // Guards against pending entities, which have no defined index
+        if loc.index >= archetype.len() {
+            return Err(QueryOneError::Unsatisfied);
+        }
+        unsafe {
+            let fetch = Q::Fetch::get(&self.entities.meta, archetype, loc.index as usize)
+                .ok_or(QueryOneError::Unsatisfied)?;
+            assert_borrow::<Q>(archetype);
+            if fetch.should_skip(0) {
+                return Err(QueryOneError::Unsatisfied);
+            }
+            Ok(fetch.fetch(0))
+        }
     }
 
     /// Borrow the `T` component of `entity`
@@ -279,10 +1076,13 @@ impl World {
     /// components.
     pub fn get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError> {
         let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
-        }
-        Ok(unsafe { Ref::new(&self.archetypes[loc.archetype as usize], loc.index)? })
+        Ok(unsafe {
+            Ref::new(
+                &self.archetypes[loc.archetype as usize],
+                entity.id,
+                loc.index,
+            )?
+        })
     }
 
     /// Uniquely borrow the `T` component of `entity`
@@ -290,19 +1090,70 @@ impl World {
     /// Panics if the component is already borrowed from another entity with the same components.
     pub fn get_mut<T: Component>(&self, entity: Entity) -> Result<RefMut<'_, T>, ComponentError> {
         let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
+        Ok(unsafe {
+            RefMut::new(
+                &self.archetypes[loc.archetype as usize],
+                entity.id,
+                loc.index,
+            )?
+        })
+    }
+
+    /// Like `get`, but returns an error instead of panicking if the component is already borrowed
+    /// uniquely
+    pub fn try_get<T: Component>(&self, entity: Entity) -> Result<Ref<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        unsafe {
+            Ref::try_new(
+                &self.archetypes[loc.archetype as usize],
+                entity.id,
+                loc.index,
+            )
+        }
+    }
+
+    /// Like `get_mut`, but returns an error instead of panicking if the component is already
+    /// borrowed
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// let a = world.spawn((123,));
+    /// let x = world.try_get::<i32>(a).unwrap();
+    /// assert_eq!(
+    ///     world.try_get_mut::<i32>(a).err(),
+    ///     Some(ComponentError::Borrow(BorrowError::Shared("i32")))
+    /// );
+    /// drop(x);
+    /// *world.try_get_mut::<i32>(a).unwrap() += 1;
+    /// assert_eq!(*world.get::<i32>(a).unwrap(), 124);
+    /// ```
+    pub fn try_get_mut<T: Component>(
+        &self,
+        entity: Entity,
+    ) -> Result<RefMut<'_, T>, ComponentError> {
+        let loc = self.entities.get(entity)?;
+        unsafe {
+            RefMut::try_new(
+                &self.archetypes[loc.archetype as usize],
+                entity.id,
+                loc.index,
+            )
         }
-        Ok(unsafe { RefMut::new(&self.archetypes[loc.archetype as usize], loc.index)? })
     }
 
     /// Access an entity regardless of its component types
     ///
     /// Does not immediately borrow any component.
     pub fn entity(&self, entity: Entity) -> Result<EntityRef<'_>, NoSuchEntity> {
-        Ok(match self.entities.get(entity)? {
-            Location { archetype: 0, .. } => EntityRef::empty(),
-            loc => unsafe { EntityRef::new(&self.archetypes[loc.archetype as usize], loc.index) },
+        let loc = self.entities.get(entity)?;
+        Ok(unsafe {
+            EntityRef::new(
+                &self.archetypes[loc.archetype as usize],
+                entity.id,
+                loc.index,
+            )
         })
     }
 
@@ -347,41 +1198,47 @@ impl World {
         entity: Entity,
         components: impl DynamicBundle,
     ) -> Result<(), NoSuchEntity> {
-        use hashbrown::hash_map::Entry;
-
         self.flush();
-        let loc = self.entities.get_mut(entity)?;
+        let loc = *self.entities.get_mut(entity)?;
+        let target = self.insert_target(loc.archetype, &components);
         unsafe {
-            // Assemble Vec<TypeInfo> for the final entity
-            let arch = &mut self.archetypes[loc.archetype as usize];
-            let mut info = arch.types().to_vec();
-            for ty in components.type_info() {
-                if let Some(ptr) = arch.get_dynamic(ty.id(), ty.layout().size(), loc.index) {
-                    ty.drop(ptr.as_ptr());
-                } else {
-                    info.push(ty);
+            // Drop any components being replaced
+            let source_arch = &self.archetypes[loc.archetype as usize];
+            let observers = &mut self.observers;
+            let sparse = &mut self.sparse;
+            components.with_ids(|ids| {
+                for ty in source_arch.types() {
+                    if ids.contains(&ty.id()) {
+                        let ptr = source_arch
+                            .get_dynamic(ty.id(), ty.layout().size(), loc.index)
+                            .unwrap();
+                        observers.removed(entity, source_arch, loc.index, ty);
+                        ty.drop(ptr.as_ptr());
+                    }
                 }
-            }
-            info.sort();
-
-            // Find the archetype it'll live in
-            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
-            let target = match self.index.entry(elements) {
-                Entry::Occupied(x) => *x.get(),
-                Entry::Vacant(x) => {
-                    let index = self.archetypes.len() as u32;
-                    self.archetypes.push(Archetype::new(info));
-                    x.insert(index);
-                    self.archetype_generation += 1;
-                    index
+                for &id in ids {
+                    let ty = match sparse.get(id) {
+                        Some(set) if set.contains(entity.id) => *set.ty(),
+                        _ => continue,
+                    };
+                    observers.removed(entity, source_arch, loc.index, &ty);
+                    let (ptr, _) = sparse.get(id).unwrap().get(entity.id).unwrap();
+                    ty.drop(ptr.as_ptr());
                 }
-            };
+            });
 
             if target == loc.archetype {
                 // Update components in the current archetype
                 let arch = &mut self.archetypes[loc.archetype as usize];
+                let observers = &mut self.observers;
+                let sparse = &mut self.sparse;
+                let tick = self.tick;
                 components.put(|ptr, ty, size| {
-                    arch.put_dynamic(ptr, ty, size, loc.index);
+                    match sparse.get_mut(ty) {
+                        Some(set) => set.insert(entity.id, ptr, tick),
+                        None => arch.replace_dynamic(ptr, ty, size, loc.index),
+                    }
+                    observers.added(entity, arch, loc.index, ty, size);
                     true
                 });
                 return Ok(());
@@ -394,15 +1251,29 @@ impl World {
                 target as usize,
             );
             let target_index = target_arch.allocate(entity.id);
-            loc.archetype = target;
-            let old_index = mem::replace(&mut loc.index, target_index);
-            if let Some(moved) = source_arch.move_to(old_index, |ptr, ty, size| {
-                target_arch.put_dynamic(ptr, ty, size, target_index);
+            target_arch.copy_ticks_from(source_arch, loc.index, target_index);
+            if let Some(moved) = source_arch.move_to(loc.index, |src, ty, size| {
+                let dst = target_arch.get_dynamic(ty, size, target_index).unwrap();
+                ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
             }) {
-                self.entities.meta[moved as usize].location.index = old_index;
+                self.entities.meta[moved as usize].location.index = loc.index;
             }
+            self.entities.meta[entity.id as usize].location = Location {
+                archetype: target,
+                index: target_index,
+            };
+            let observers = &mut self.observers;
+            let sparse = &mut self.sparse;
+            let tick = self.tick;
             components.put(|ptr, ty, size| {
-                target_arch.put_dynamic(ptr, ty, size, target_index);
+                if let Some(set) = sparse.get_mut(ty) {
+                    set.insert(entity.id, ptr, tick);
+                } else if source_arch.has_dynamic(ty) {
+                    target_arch.replace_dynamic(ptr, ty, size, target_index);
+                } else {
+                    target_arch.put_dynamic(ptr, ty, size, target_index);
+                }
+                observers.added(entity, target_arch, target_index, ty, size);
                 true
             });
         }
@@ -440,48 +1311,62 @@ impl World {
     /// assert_eq!(*world.get::<bool>(e).unwrap(), true);
     /// ```
     pub fn remove<T: Bundle>(&mut self, entity: Entity) -> Result<T, ComponentError> {
-        use hashbrown::hash_map::Entry;
-
         self.flush();
-        let loc = self.entities.get_mut(entity)?;
+        let loc = *self.entities.get_mut(entity)?;
+        let target = self.remove_target::<T>(loc.archetype);
         unsafe {
-            let removed = T::with_static_ids(|ids| ids.iter().copied().collect::<HashSet<_>>());
-            let info = self.archetypes[loc.archetype as usize]
-                .types()
-                .iter()
-                .cloned()
-                .filter(|x| !removed.contains(&x.id()))
-                .collect::<Vec<_>>();
-            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
-            let target = match self.index.entry(elements) {
-                Entry::Occupied(x) => *x.get(),
-                Entry::Vacant(x) => {
-                    self.archetypes.push(Archetype::new(info));
-                    let index = (self.archetypes.len() - 1) as u32;
-                    x.insert(index);
-                    self.archetype_generation += 1;
-                    index
-                }
-            };
-            let old_index = loc.index;
             let source_arch = &self.archetypes[loc.archetype as usize];
-            let bundle = T::get(|ty, size| source_arch.get_dynamic(ty, size, old_index))?;
+            // Until the world forgets the components, the bundle must not drop them
+            let bundle = mem::ManuallyDrop::new(T::get(|ty, size| {
+                Some(source_arch.locate(ty, size, entity.id, loc.index)?.0)
+            })?);
+            let observers = &mut self.observers;
+            let sparse = &mut self.sparse;
+            T::with_static_ids(|ids| {
+                for set in ids.iter().filter_map(|&id| sparse.get(id)) {
+                    observers.removed(entity, source_arch, loc.index, set.ty());
+                }
+            });
+            let target_arch = &self.archetypes[target as usize];
+            if target != loc.archetype {
+                for ty in source_arch.types() {
+                    if !target_arch.has_dynamic(ty.id()) {
+                        self.observers.removed(entity, source_arch, loc.index, ty);
+                    }
+                }
+            }
+            // Sparse components are removed in place
+            T::with_static_ids(|ids| {
+                for &id in ids {
+                    if let Some(set) = sparse.get_mut(id) {
+                        set.remove(entity.id, |_| {});
+                    }
+                }
+            });
+            let bundle = mem::ManuallyDrop::into_inner(bundle);
+            if target == loc.archetype {
+                // Nothing else to remove
+                return Ok(bundle);
+            }
             let (source_arch, target_arch) = index2(
                 &mut self.archetypes,
                 loc.archetype as usize,
                 target as usize,
             );
             let target_index = target_arch.allocate(entity.id);
-            loc.archetype = target;
-            loc.index = target_index;
-            if let Some(moved) = source_arch.move_to(old_index, |src, ty, size| {
+            target_arch.copy_ticks_from(source_arch, loc.index, target_index);
+            if let Some(moved) = source_arch.move_to(loc.index, |src, ty, size| {
                 // Only move the components present in the target archetype, i.e. the non-removed ones.
                 if let Some(dst) = target_arch.get_dynamic(ty, size, target_index) {
                     ptr::copy_nonoverlapping(src, dst.as_ptr(), size);
                 }
             }) {
-                self.entities.meta[moved as usize].location.index = old_index;
+                self.entities.meta[moved as usize].location.index = loc.index;
             }
+            self.entities.meta[entity.id as usize].location = Location {
+                archetype: target,
+                index: target_index,
+            };
             Ok(bundle)
         }
     }
@@ -493,6 +1378,86 @@ impl World {
         self.remove::<(T,)>(entity).map(|(x,)| x)
     }
 
+    /// Find or create the archetype with exactly the sorted component types `ids`
+    ///
+    /// Sparse types are left out of the archetype, though `ids` may include them.
+    fn archetype_for(&mut self, ids: &[ComponentId], info: impl FnOnce() -> Vec<TypeInfo>) -> u32 {
+        if let Some(&x) = self.index.get(ids) {
+            return x;
+        }
+        if !ids.iter().any(|&id| self.sparse.contains(id)) {
+            return self.add_archetype(ids, info());
+        }
+        let dense = ids
+            .iter()
+            .copied()
+            .filter(|&id| !self.sparse.contains(id))
+            .collect::<Vec<_>>();
+        let x = match self.index.get(&dense) {
+            Some(&x) => x,
+            None => {
+                let mut info = info();
+                info.retain(|x| !self.sparse.contains(x.id()));
+                self.add_archetype(&dense, info)
+            }
+        };
+        // Also index the types including sparse ones, which are looked up again on every spawn
+        self.index.insert(ids.to_vec(), x);
+        x
+    }
+
+    fn add_archetype(&mut self, ids: &[ComponentId], info: Vec<TypeInfo>) -> u32 {
+        let x = self.archetypes.len() as u32;
+        let mut archetype = Archetype::new(info, self.tick, &self.storage);
+        for (id, set) in self.sparse.iter() {
+            archetype.add_sparse(id, set.clone());
+        }
+        self.archetypes.push(archetype);
+        self.index.insert(ids.to_vec(), x);
+        self.archetype_generation += 1;
+        x
+    }
+
+    /// Archetype of an entity in `source` after `components` are inserted into it
+    fn insert_target(&mut self, source: u32, components: &impl DynamicBundle) -> u32 {
+        components.with_ids(|ids| {
+            if let Some(x) = self.archetypes[source as usize].insert_edge(ids) {
+                return x;
+            }
+            let source_arch = &self.archetypes[source as usize];
+            let mut info = source_arch.types().to_vec();
+            for ty in components.type_info() {
+                if !source_arch.has_dynamic(ty.id()) && !self.sparse.contains(ty.id()) {
+                    info.push(ty);
+                }
+            }
+            info.sort();
+            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
+            let target = self.archetype_for(&elements, || info);
+            self.archetypes[source as usize].set_insert_edge(ids, target);
+            target
+        })
+    }
+
+    /// Archetype of an entity in `source` after the components in `T` are removed from it
+    fn remove_target<T: Bundle>(&mut self, source: u32) -> u32 {
+        T::with_static_ids(|ids| {
+            if let Some(x) = self.archetypes[source as usize].remove_edge(ids) {
+                return x;
+            }
+            let info = self.archetypes[source as usize]
+                .types()
+                .iter()
+                .filter(|x| !ids.contains(&x.id()))
+                .cloned()
+                .collect::<Vec<_>>();
+            let elements = info.iter().map(|x| x.id()).collect::<Vec<_>>();
+            let target = self.archetype_for(&elements, || info);
+            self.archetypes[source as usize].set_remove_edge(ids, target);
+            target
+        })
+    }
+
     /// Borrow the `T` component of `entity` without safety checks
     ///
     /// Should only be used as a building block for safe abstractions.
@@ -503,14 +1468,17 @@ impl World {
     /// same component of `entity` may be live simultaneous to the returned reference.
     pub unsafe fn get_unchecked<T: Component>(&self, entity: Entity) -> Result<&T, ComponentError> {
         let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
-        }
         Ok(&*self.archetypes[loc.archetype as usize]
-            .get::<T>()
+            .locate(
+                ComponentId::of::<T>(),
+                mem::size_of::<T>(),
+                entity.id,
+                loc.index,
+            )
             .ok_or_else(MissingComponent::new::<T>)?
-            .as_ptr()
-            .add(loc.index as usize))
+            .0
+            .cast::<T>()
+            .as_ptr())
     }
 
     /// Uniquely borrow the `T` component of `entity` without safety checks
@@ -521,19 +1489,23 @@ impl World {
     ///
     /// `entity` must have been previously obtained from this `World`, and no borrow of the same
     /// component of `entity` may be live simultaneous to the returned reference.
+    #[allow(clippy::mut_from_ref)]
     pub unsafe fn get_unchecked_mut<T: Component>(
         &self,
         entity: Entity,
     ) -> Result<&mut T, ComponentError> {
         let loc = self.entities.get(entity)?;
-        if loc.archetype == 0 {
-            return Err(MissingComponent::new::<T>().into());
-        }
         Ok(&mut *self.archetypes[loc.archetype as usize]
-            .get::<T>()
+            .locate(
+                ComponentId::of::<T>(),
+                mem::size_of::<T>(),
+                entity.id,
+                loc.index,
+            )
             .ok_or_else(MissingComponent::new::<T>)?
-            .as_ptr()
-            .add(loc.index as usize))
+            .0
+            .cast::<T>()
+            .as_ptr())
     }
 
     /// Convert all reserved entities into empty entities that can be iterated and accessed
@@ -551,6 +1523,87 @@ impl World {
         self.entities.clear_reserved();
     }
 
+    /// Begin a new change tracking period
+    ///
+    /// `Added`, `Mutated`, and `Changed` queries only match components that were added or mutated
+    /// since the most recent call to this method. Typically called once per frame, after every
+    /// interested system has run. Does not touch the components themselves, so its cost is
+    /// proportional only to the number of archetypes.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// let mut world = World::new();
+    /// world.spawn((123,));
+    /// assert_eq!(world.query::<Added<i32>>().iter().count(), 1);
+    /// world.clear_trackers();
+    /// assert_eq!(world.query::<Added<i32>>().iter().count(), 0);
+    /// ```
+    pub fn clear_trackers(&mut self) {
+        self.tick = self.tick.wrapping_add(1);
+        for x in &mut self.archetypes {
+            x.set_tick(self.tick);
+        }
+    }
+
+    /// Call `f` whenever a `T` component is added to an entity
+    ///
+    /// Invoked by `spawn`, `spawn_batch`, and `insert` after the component is in place. Replacing
+    /// an existing component with `insert` counts as removing the old value and adding the new
+    /// one. `f` cannot access the `World`, but may e.g. forward events through a channel or update
+    /// external state.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let added = Arc::new(Mutex::new(Vec::new()));
+    /// let mut world = World::new();
+    /// let log = added.clone();
+    /// world.on_add::<i32>(move |entity, &x| log.lock().unwrap().push((entity, x)));
+    /// let a = world.spawn((123, true));
+    /// let b = world.spawn((true,));
+    /// world.insert_one(b, 456).unwrap();
+    /// assert_eq!(*added.lock().unwrap(), [(a, 123), (b, 456)]);
+    /// ```
+    pub fn on_add<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.observers.on_add(f);
+    }
+
+    /// Call `f` whenever a `T` component is about to be removed from an entity
+    ///
+    /// Invoked by `insert` when replacing a component, by `remove`, and for every component of
+    /// entities destroyed by `despawn`, `despawn_batch`, `retain`, `take`, `despawn_recursive`,
+    /// `spawn_at`, or `clear`. `f` observes the component before it is dropped or returned to the
+    /// caller.
+    ///
+    /// # Example
+    /// ```
+    /// # use hecs::*;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let removed = Arc::new(Mutex::new(Vec::new()));
+    /// let mut world = World::new();
+    /// let log = removed.clone();
+    /// world.on_remove::<String>(move |_, x| log.lock().unwrap().push(x.clone()));
+    /// let a = world.spawn(("abc".to_string(),));
+    /// world.insert_one(a, "def".to_string()).unwrap();
+    /// world.despawn(a).unwrap();
+    /// assert_eq!(*removed.lock().unwrap(), ["abc", "def"]);
+    /// ```
+    pub fn on_remove<T: Component>(&mut self, f: impl FnMut(Entity, &T) + Send + Sync + 'static) {
+        self.observers.on_remove(f);
+    }
+
+    /// Call `f` whenever an entity is destroyed by `despawn`, `despawn_batch`, `retain`, `take`,
+    /// `despawn_recursive`, `spawn_at`, or `clear`
+    ///
+    /// Invoked after any `on_remove` observers for the entity's components.
+    pub fn on_despawn(&mut self, f: impl FnMut(Entity) + Send + Sync + 'static) {
+        self.observers.on_despawn(f);
+    }
+
     /// Inspect the archetypes that entities are organized into
     ///
     /// Useful for dynamically scheduling concurrent queries by checking borrows in advance. Does
@@ -559,6 +1612,50 @@ impl World {
         self.archetypes.iter()
     }
 
+    pub(crate) fn entities_meta(&self) -> &[EntityMeta] {
+        &self.entities.meta
+    }
+
+    pub(crate) fn archetypes_inner(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    /// Number of types registered with `register_sparse`
+    pub(crate) fn sparse_types(&self) -> usize {
+        self.sparse.len()
+    }
+
+    /// Whether `entity` was spawned and not since despawned, excluding reserved entities unlike
+    /// `contains`
+    #[cfg(feature = "serde")]
+    pub(crate) fn is_spawned(&self, entity: Entity) -> bool {
+        let meta = match self.entities.meta.get(entity.id as usize) {
+            Some(x) if x.generation == entity.generation => x,
+            _ => return false,
+        };
+        // The location of a free ID is stale, so check it leads back to the entity
+        let loc = meta.location;
+        self.archetypes
+            .get(loc.archetype as usize)
+            .is_some_and(|x| loc.index < x.len() && x.entity_id(loc.index) == entity.id)
+    }
+
+    #[cfg(feature = "serde")]
+    pub(crate) fn sparse_sets(&self) -> impl Iterator<Item = &crate::sparse::SparseSet> + '_ {
+        self.sparse.iter().map(|(_, set)| set.get())
+    }
+
+    /// Number of times `remove_empty_archetypes` removed any archetype, changing the indices of
+    /// the rest
+    pub(crate) fn archetype_removals(&self) -> u64 {
+        self.archetype_removals
+    }
+
+    /// A value distinct from that of every other `World` in this process
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
     /// Returns a distinct value after `archetypes` is changed
     ///
     /// Store the current value after deriving information from `archetypes`, then check whether the
@@ -567,7 +1664,7 @@ impl World {
     /// correct.
     ///
     /// The generation may be, but is not necessarily, changed as a result of adding or removing any
-    /// entity or component.
+    /// entity or component. It's always changed by `remove_empty_archetypes` removing any archetype.
     ///
     /// # Example
     /// ```
@@ -585,6 +1682,77 @@ impl World {
 unsafe impl Send for World {}
 unsafe impl Sync for World {}
 
+/// Configuration for a new `World`, from `World::builder`
+///
+/// # Example
+/// ```
+/// # use hecs::*;
+/// // Start every column on its own cache line
+/// let mut world = World::builder().column_align(64).build();
+/// world.spawn_batch((0..100).map(|i| (i, i as f32)));
+/// for (_, (ints, floats)) in world.query::<(&i32, &f32)>().iter_chunks() {
+///     assert_eq!(ints.as_ptr() as usize % 64, 0);
+///     assert_eq!(floats.as_ptr() as usize % 64, 0);
+/// }
+/// ```
+pub struct WorldBuilder {
+    storage: StorageConfig,
+}
+
+impl WorldBuilder {
+    /// Start from the defaults used by `World::new`
+    pub fn new() -> Self {
+        Self {
+            storage: StorageConfig::default(),
+        }
+    }
+
+    /// Divide the storage of each archetype into chunks of about `size` bytes
+    ///
+    /// See `World::with_chunk_size`. Panics if `size` is zero.
+    pub fn chunk_size(&mut self, size: usize) -> &mut Self {
+        assert!(size > 0, "chunk size must be nonzero");
+        self.storage.chunk_size = Some(size);
+        self
+    }
+
+    /// Start each column of components at an address that's a multiple of `align` bytes
+    ///
+    /// Columns are always aligned suitably for their component type. Over-aligning them, for
+    /// example to 64-byte cache lines or 32-byte AVX registers, can speed up code that processes
+    /// the slices yielded by `QueryBorrow::iter_chunks` at the cost of some padding. Panics if
+    /// `align` isn't a power of two.
+    pub fn column_align(&mut self, align: usize) -> &mut Self {
+        assert!(
+            align.is_power_of_two(),
+            "column alignment must be a power of two"
+        );
+        self.storage.column_align = align;
+        self
+    }
+
+    /// Allocate the storage of each archetype from `allocator` rather than the global allocator
+    ///
+    /// `allocator` is kept alive, and receives every buffer back, until the world and any
+    /// `World::try_clone` of it are dropped. Sparse components and other bookkeeping, such as the
+    /// entity index, still use the global allocator.
+    pub fn allocator(&mut self, allocator: impl GlobalAlloc + Send + Sync + 'static) -> &mut Self {
+        self.storage.allocator = Arc::new(allocator);
+        self
+    }
+
+    /// Create an empty world with this configuration
+    pub fn build(&self) -> World {
+        World::with_storage(self.storage.clone())
+    }
+}
+
+impl Default for WorldBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Default for World {
     fn default() -> Self {
         Self::new()
@@ -614,6 +1782,8 @@ pub enum ComponentError {
     NoSuchEntity,
     /// The entity did not have a requested component
     MissingComponent(MissingComponent),
+    /// The requested component was already borrowed incompatibly, from a `try_` method
+    Borrow(BorrowError),
 }
 
 #[cfg(feature = "std")]
@@ -625,6 +1795,7 @@ impl fmt::Display for ComponentError {
         match *self {
             NoSuchEntity => f.write_str("no such entity"),
             MissingComponent(ref x) => x.fmt(f),
+            Borrow(ref x) => x.fmt(f),
         }
     }
 }
@@ -641,6 +1812,40 @@ impl From<MissingComponent> for ComponentError {
     }
 }
 
+impl From<BorrowError> for ComponentError {
+    fn from(x: BorrowError) -> Self {
+        ComponentError::Borrow(x)
+    }
+}
+
+/// Errors that arise when querying a single entity
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum QueryOneError {
+    /// The entity was already despawned
+    NoSuchEntity,
+    /// The entity exists but does not satisfy the query
+    Unsatisfied,
+}
+
+#[cfg(feature = "std")]
+impl Error for QueryOneError {}
+
+impl fmt::Display for QueryOneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use QueryOneError::*;
+        match *self {
+            NoSuchEntity => f.write_str("no such entity"),
+            Unsatisfied => f.write_str("unsatisfied"),
+        }
+    }
+}
+
+impl From<NoSuchEntity> for QueryOneError {
+    fn from(NoSuchEntity: NoSuchEntity) -> Self {
+        QueryOneError::NoSuchEntity
+    }
+}
+
 /// Types that can be components, implemented automatically for all `Send + Sync + 'static` types
 ///
 /// This is just a convenient shorthand for `Send + Sync + 'static`, and never needs to be
@@ -680,7 +1885,7 @@ impl<'a> Iterator for Iter<'a> {
                     self.index = 0;
                 }
                 Some(current) => {
-                    if self.index == current.len() as u32 {
+                    if self.index == current.len() {
                         self.current = None;
                         continue;
                     }
@@ -692,7 +1897,7 @@ impl<'a> Iterator for Iter<'a> {
                             id,
                             generation: self.entities.meta[id as usize].generation,
                         },
-                        unsafe { EntityRef::new(current, index) },
+                        unsafe { EntityRef::new(current, id, index) },
                     ));
                 }
             }
@@ -737,6 +1942,9 @@ where
     entities: &'a mut Entities,
     archetype_id: u32,
     archetype: &'a mut Archetype,
+    observers: &'a mut Observers,
+    sparse: &'a mut SparseSets,
+    tick: u32,
 }
 
 impl<I> Drop for SpawnBatchIter<'_, I>
@@ -762,13 +1970,17 @@ where
         unsafe {
             let index = self.archetype.allocate(entity.id);
             components.put(|ptr, ty, size| {
-                self.archetype.put_dynamic(ptr, ty, size, index);
+                match self.sparse.get_mut(ty) {
+                    Some(set) => set.insert(entity.id, ptr, self.tick),
+                    None => self.archetype.put_dynamic(ptr, ty, size, index),
+                }
                 true
             });
             self.entities.meta[entity.id as usize].location = Location {
                 archetype: self.archetype_id,
                 index,
             };
+            self.observers.spawned(entity, self.archetype, index);
         }
         Some(entity)
     }
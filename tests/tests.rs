@@ -210,6 +210,52 @@ fn illegal_random_access() {
     world.get::<i32>(e).unwrap();
 }
 
+#[test]
+fn try_borrow() {
+    let mut world = World::new();
+    let a = world.spawn(("abc", 123, true));
+    let b = world.spawn((456, true));
+    {
+        let _x = world.get_mut::<i32>(b).unwrap();
+        assert_eq!(
+            world.try_get::<i32>(b).err(),
+            Some(ComponentError::Borrow(BorrowError::Unique("i32")))
+        );
+        // Borrows are tracked per archetype
+        assert!(world.try_get_mut::<i32>(a).is_ok());
+        assert!(world.try_get::<bool>(b).is_ok());
+        assert_eq!(
+            world.try_get::<char>(a).err(),
+            Some(ComponentError::MissingComponent(MissingComponent::new::<
+                char,
+            >()))
+        );
+        assert_eq!(
+            world.entity(b).unwrap().try_get::<i32>().err(),
+            Some(BorrowError::Unique("i32"))
+        );
+
+        // Borrows already acquired from `a`'s archetype and of `bool` from `b`'s are released
+        let mut query = world.query::<(&bool, &i32)>();
+        let err = query.try_iter().err().unwrap();
+        assert_eq!(err.component(), "i32");
+        assert_eq!(err.to_string(), "i32 already borrowed uniquely");
+        assert!(world.try_get_mut::<i32>(a).is_ok());
+        assert!(world.try_get_mut::<bool>(a).is_ok());
+        assert!(world.try_get_mut::<bool>(b).is_ok());
+    }
+    {
+        let _x = world.get::<bool>(a).unwrap();
+        assert_eq!(
+            world.try_get_mut::<bool>(a).err(),
+            Some(ComponentError::Borrow(BorrowError::Shared("bool")))
+        );
+    }
+    let mut query = world.query::<&mut i32>();
+    assert_eq!(query.try_iter().unwrap().count(), 2);
+    assert_eq!(query.try_iter().unwrap().count(), 2);
+}
+
 #[test]
 #[cfg(feature = "macros")]
 fn derived_bundle() {
@@ -225,6 +271,166 @@ fn derived_bundle() {
     assert_eq!(*world.get::<f64>(e).unwrap(), 1.0);
 }
 
+#[test]
+#[cfg(feature = "macros")]
+fn derived_query() {
+    #[derive(Query, Debug, PartialEq)]
+    struct Body<'a> {
+        entity: Entity,
+        position: &'a mut i32,
+        velocity: &'a u8,
+        mass: Option<&'a f32>,
+    }
+
+    #[derive(Query, Debug, PartialEq)]
+    #[query(with(bool), without(char))]
+    struct Labeled<'a> {
+        body: Body<'a>,
+        label: &'a &'static str,
+    }
+
+    let mut world = World::new();
+    let a = world.spawn((1, 2u8));
+    let b = world.spawn((3, 4u8, 5.0f32, "b", true));
+    let c = world.spawn((6, 7u8, "c", true, 'x'));
+    world.spawn((8, "d", true));
+
+    let mut bodies = world.query_mut::<Body>().into_items().collect::<Vec<_>>();
+    bodies.sort_by_key(|x| *x.position);
+    assert_eq!(bodies.len(), 3);
+    assert_eq!(
+        bodies[0],
+        Body {
+            entity: a,
+            position: &mut 1,
+            velocity: &2,
+            mass: None,
+        }
+    );
+    assert_eq!(bodies[1].mass, Some(&5.0));
+    assert_eq!(bodies[2].entity, c);
+    for body in bodies {
+        *body.position += i32::from(*body.velocity);
+    }
+
+    let labeled = world
+        .query::<Labeled>()
+        .iter()
+        .map(|(e, x)| (e, *x.label, *x.body.position))
+        .collect::<Vec<_>>();
+    assert_eq!(labeled, [(b, "b", 7)]);
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn derived_bundle_generic_nested() {
+    use std::sync::Arc;
+
+    #[derive(Bundle, Debug, PartialEq)]
+    struct Physics(i32, f64);
+
+    #[derive(Bundle, Debug, PartialEq)]
+    struct Unit<T> {
+        faction: T,
+        #[bundle]
+        physics: Physics,
+        #[bundle(skip)]
+        label: String,
+    }
+
+    #[derive(Bundle)]
+    struct Owner(Arc<()>);
+
+    #[derive(Bundle)]
+    struct Owned {
+        #[bundle]
+        owner: Owner,
+        #[bundle]
+        physics: Physics,
+    }
+
+    let mut world = World::new();
+    let e = world.spawn(Unit {
+        faction: 'a',
+        physics: Physics(1, 2.0),
+        label: "ignored".into(),
+    });
+    assert_eq!(*world.get::<char>(e).unwrap(), 'a');
+    assert_eq!(*world.get::<i32>(e).unwrap(), 1);
+    assert!(world.get::<String>(e).is_err());
+    let f = world.spawn(Unit {
+        faction: 7u8,
+        physics: Physics(3, 4.0),
+        label: String::new(),
+    });
+    assert_eq!(*world.get::<u8>(f).unwrap(), 7);
+
+    // Each instantiation caches its own IDs
+    let ids = |x: &[ComponentId]| (x.as_ptr(), x.to_vec());
+    let chars = <Unit<char> as Bundle>::with_static_ids(ids);
+    let bytes = <Unit<u8> as Bundle>::with_static_ids(ids);
+    assert_ne!(chars.1, bytes.1);
+    assert_eq!(<Unit<char> as Bundle>::with_static_ids(ids), chars);
+    assert_eq!(<Unit<u8> as Bundle>::with_static_ids(ids), bytes);
+
+    // Bundles with lifetime parameters can't be cached, but still work
+    #[derive(Bundle)]
+    struct Borrowing<'a> {
+        faction: char,
+        #[bundle(skip)]
+        _marker: std::marker::PhantomData<&'a ()>,
+    }
+    let g = world.spawn(Borrowing {
+        faction: 'b',
+        _marker: std::marker::PhantomData,
+    });
+    assert_eq!(*world.get::<char>(g).unwrap(), 'b');
+
+    assert_eq!(
+        world.remove::<Unit<char>>(e),
+        Ok(Unit {
+            faction: 'a',
+            physics: Physics(1, 2.0),
+            label: String::new(),
+        })
+    );
+    assert!(world.get::<i32>(e).is_err());
+
+    // `Owner` is found but `Physics` isn't, so the `Arc` read for `Owner` must not be dropped
+    let rc = Arc::new(());
+    world.insert(e, Owner(rc.clone())).unwrap();
+    assert!(world.remove::<Owned>(e).is_err());
+    assert_eq!(Arc::strong_count(&rc), 2);
+    world.despawn(e).unwrap();
+    assert_eq!(Arc::strong_count(&rc), 1);
+
+    // Skipped fields are defaulted before anything is moved out of the world
+    struct Panicky;
+    impl Default for Panicky {
+        fn default() -> Self {
+            panic!("no default");
+        }
+    }
+
+    #[derive(Bundle)]
+    struct Labeled {
+        #[bundle]
+        owner: Owner,
+        #[bundle(skip)]
+        #[allow(dead_code)]
+        label: Panicky,
+    }
+
+    let g = world.spawn(Owner(rc.clone()));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        world.remove::<Labeled>(g).ok();
+    }));
+    assert!(result.is_err());
+    assert_eq!(Arc::strong_count(&rc), 2);
+    world.despawn(g).unwrap();
+    assert_eq!(Arc::strong_count(&rc), 1);
+}
+
 #[test]
 #[cfg(feature = "macros")]
 #[should_panic(expected = "each type must occur at most once")]
@@ -277,6 +483,28 @@ fn remove_missing() {
     assert!(world.remove_one::<bool>(e).is_err());
 }
 
+#[test]
+fn insert_remove_cached() {
+    let mut world = World::new();
+    let a = world.spawn(("abc", 123));
+    let b = world.spawn(("def", 456));
+    for i in 0..3 {
+        world.insert(a, (true, i)).unwrap();
+        world.insert(b, (false, i)).unwrap();
+        assert_eq!(*world.get::<i32>(a).unwrap(), i);
+        assert!(!*world.get::<bool>(b).unwrap());
+        assert_eq!(world.remove_one::<bool>(a), Ok(true));
+        assert_eq!(world.remove::<(bool, i32)>(b), Ok((false, i)));
+        assert!(world.get::<bool>(a).is_err());
+        assert!(world.get::<i32>(b).is_err());
+        world.insert_one(b, 789).unwrap();
+    }
+    assert_eq!(world.remove::<()>(a), Ok(()));
+    assert_eq!(*world.get::<&str>(a).unwrap(), "abc");
+    assert_eq!(*world.get::<&str>(b).unwrap(), "def");
+    assert_eq!(world.query::<(&&str, &i32)>().iter().count(), 2);
+}
+
 #[test]
 fn reserve() {
     let mut world = World::new();
@@ -306,21 +534,15 @@ fn query_batched() {
     let c = world.spawn((42,));
     assert_eq!(world.query::<()>().iter_batched(1).count(), 3);
     assert_eq!(world.query::<()>().iter_batched(2).count(), 2);
-    assert_eq!(
-        world.query::<()>().iter_batched(2).flat_map(|x| x).count(),
-        3
-    );
+    assert_eq!(world.query::<()>().iter_batched(2).flatten().count(), 3);
     // different archetypes are always in different batches
     assert_eq!(world.query::<()>().iter_batched(3).count(), 2);
-    assert_eq!(
-        world.query::<()>().iter_batched(3).flat_map(|x| x).count(),
-        3
-    );
+    assert_eq!(world.query::<()>().iter_batched(3).flatten().count(), 3);
     assert_eq!(world.query::<()>().iter_batched(4).count(), 2);
     let entities = world
         .query::<()>()
         .iter_batched(1)
-        .flat_map(|x| x)
+        .flatten()
         .map(|(e, ())| e)
         .collect::<Vec<_>>();
     dbg!(&entities);
@@ -342,6 +564,222 @@ fn spawn_batch() {
     assert_eq!(entities.len(), 100);
 }
 
+#[test]
+fn spawn_column_batch() {
+    use std::sync::Arc;
+
+    let rc = Arc::new(());
+    let mut world = World::new();
+    let a = world.spawn((0, "zero"));
+    let dead = world.spawn((true,));
+    world.despawn(dead).unwrap();
+
+    let mut batch = ColumnBatch::new();
+    batch
+        .add(vec![1, 2, 3])
+        .add_slice(&["one", "two", "three"])
+        .add(vec![rc.clone(), rc.clone(), rc.clone()]);
+    assert_eq!(batch.len(), 3);
+    let entities = world.spawn_column_batch(batch);
+    assert_eq!(entities.len(), 3);
+    assert_eq!(Arc::strong_count(&rc), 4);
+    let entities = entities.collect::<Vec<_>>();
+    assert_eq!(entities[1].id(), entities[0].id() + 1);
+    assert_eq!(entities[2].id(), entities[0].id() + 2);
+    for (&e, (i, s)) in entities.iter().zip(&[(1, "one"), (2, "two"), (3, "three")]) {
+        assert_eq!(*world.get::<i32>(e).unwrap(), *i);
+        assert_eq!(*world.get::<&str>(e).unwrap(), *s);
+    }
+
+    // Fills an existing archetype alongside other entities
+    let mut batch = ColumnBatch::new();
+    batch.add(vec!["four"]).add(vec![4]);
+    let b = world.spawn_column_batch(batch).next().unwrap();
+    let mut ids = world
+        .query::<(&i32, &&str)>()
+        .iter()
+        .map(|(_, (&i, _))| i)
+        .collect::<Vec<_>>();
+    ids.sort_unstable();
+    assert_eq!(ids, [0, 1, 2, 3, 4]);
+    assert_eq!(*world.get::<&str>(a).unwrap(), "zero");
+    assert_eq!(*world.get::<&str>(b).unwrap(), "four");
+
+    // Later allocations don't reuse batch IDs
+    let c = world.spawn((5,));
+    assert!(!entities.contains(&c) && c != b);
+    assert_eq!(world.spawn_column_batch(ColumnBatch::new()).len(), 0);
+
+    for e in entities {
+        world.despawn(e).unwrap();
+    }
+    assert_eq!(Arc::strong_count(&rc), 1);
+}
+
+#[test]
+fn spawn_column_batch_many() {
+    let mut world = World::new();
+    let mut entities = Vec::new();
+    for i in 0..2000 {
+        let mut batch = ColumnBatch::new();
+        batch.add(vec![i; 3]);
+        entities.extend(world.spawn_column_batch(batch));
+        entities.push(world.spawn((i,)));
+    }
+    let mut ids = entities.iter().map(|e| e.id()).collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+    assert_eq!(ids.len(), 8000);
+    for (n, &e) in entities.iter().enumerate() {
+        assert_eq!(*world.get::<i32>(e).unwrap(), n as i32 / 4);
+    }
+}
+
+#[test]
+#[should_panic(expected = "column has the wrong length")]
+fn spawn_column_batch_mismatch() {
+    let mut batch = ColumnBatch::new();
+    batch.add(vec![1, 2]).add(vec![true]);
+}
+
+#[test]
+fn despawn_batch() {
+    use std::sync::Arc;
+
+    let mut world = World::new();
+    let rc = Arc::new(());
+    let entities = (0..10)
+        .map(|i| world.spawn((i, rc.clone())))
+        .collect::<Vec<_>>();
+    let other = world.spawn(("abc", rc.clone()));
+    world.despawn(entities[9]).unwrap();
+    world.despawn_batch(
+        entities
+            .iter()
+            .copied()
+            .filter(|e| e.id() % 3 == 0)
+            .chain(Some(other)),
+    );
+    assert_eq!(Arc::strong_count(&rc), 7);
+    for (i, &e) in entities.iter().enumerate() {
+        if i % 3 == 0 {
+            assert!(!world.contains(e));
+        } else {
+            assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+        }
+    }
+    assert!(!world.contains(other));
+    let e = world.spawn((42, rc.clone()));
+    assert_eq!(*world.get::<i32>(e).unwrap(), 42);
+}
+
+#[test]
+fn retain() {
+    let mut world = World::new();
+    let root = world.spawn(());
+    let entities = (0..10)
+        .map(|i| world.spawn((i, i % 2 == 0)))
+        .collect::<Vec<_>>();
+    world.set_parent(entities[4], root).unwrap();
+    world.set_parent(entities[5], root).unwrap();
+    let unmatched = world.spawn((3,));
+    let mut seen = Vec::new();
+    world.retain::<(&mut i32, &bool), _>(|e, (x, &even)| {
+        seen.push(e);
+        *x *= 10;
+        even
+    });
+    assert_eq!(seen, entities);
+    for (i, &e) in entities.iter().enumerate() {
+        if i % 2 == 0 {
+            assert_eq!(*world.get::<i32>(e).unwrap(), i as i32 * 10);
+        } else {
+            assert!(!world.contains(e));
+        }
+    }
+    assert_eq!(*world.get::<i32>(unmatched).unwrap(), 3);
+    assert_eq!(world.children_of(root).unwrap(), [entities[4]]);
+}
+
+#[test]
+fn query_entity() {
+    let mut world = World::new();
+    let a = world.spawn((123,));
+    let b = world.spawn((456, true));
+    world.despawn(a).unwrap();
+    let a = world.spawn((789,));
+    let mut entities = world
+        .query::<(Entity, &i32)>()
+        .iter()
+        .map(|(e, (e2, &i))| {
+            assert_eq!(e, e2);
+            (e, i)
+        })
+        .collect::<Vec<_>>();
+    entities.sort_by_key(|x| x.1);
+    assert_eq!(entities, [(b, 456), (a, 789)]);
+    assert_eq!(world.query_one::<Entity>(b).unwrap().get(), Some(b));
+    assert_eq!(
+        world.query_one_mut::<(Entity, &bool)>(b).unwrap(),
+        (b, &true)
+    );
+    let mut query = world.query::<Entity>();
+    let view = query.view();
+    assert_eq!(view.get(a), Some(a));
+    drop(query);
+
+    let mut items = world.query_mut::<Entity>().into_items().collect::<Vec<_>>();
+    items.sort();
+    let mut expected = [a, b];
+    expected.sort();
+    assert_eq!(items, expected);
+    assert_eq!(
+        world.query::<&bool>().iter_items().collect::<Vec<_>>(),
+        [&true]
+    );
+}
+
+#[test]
+fn query_chunks() {
+    let mut world = World::new();
+    let a = world.spawn((1, 'a'));
+    let b = world.spawn((2, 'b', true));
+    let c = world.spawn((3, true));
+    world.spawn(("abc",));
+    world.clear_trackers();
+
+    let mut chunks = world
+        .query::<(&mut i32, Option<&char>)>()
+        .with::<bool>()
+        .iter_chunks()
+        .map(|(ids, (xs, chars))| {
+            for x in xs.iter_mut() {
+                *x *= 10;
+            }
+            (ids.to_vec(), xs.to_vec(), chars.map(|x| x.to_vec()))
+        })
+        .collect::<Vec<_>>();
+    chunks.sort();
+    assert_eq!(
+        chunks,
+        [
+            (vec![b.id()], vec![20], Some(vec!['b'])),
+            (vec![c.id()], vec![30], None),
+        ]
+    );
+
+    let mut mutated = world
+        .query::<Mutated<i32>>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    mutated.sort();
+    let mut expected = vec![b, c];
+    expected.sort();
+    assert_eq!(mutated, expected);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+}
+
 #[test]
 fn query_one() {
     let mut world = World::new();
@@ -358,3 +796,1525 @@ fn query_one() {
     world.despawn(a).unwrap();
     assert!(world.query_one::<&i32>(a).is_err());
 }
+
+#[test]
+fn command_buffer() {
+    let mut world = World::new();
+    let a = world.spawn(("abc", 123));
+    let b = world.spawn(("def", 456, true));
+    let reserved = world.reserve_entity();
+
+    let mut cmd = CommandBuffer::new();
+    let mut builder = EntityBuilder::new();
+    builder.add(789).add(false);
+    cmd.spawn(builder.build());
+    cmd.insert(reserved, (1.0f32, "ghi"));
+    cmd.insert_one(a, true);
+    cmd.remove_one::<&str>(a);
+    cmd.despawn(b);
+    // Commands targeting dead entities are skipped
+    cmd.insert_one(b, 0u8);
+    cmd.run_on(&mut world);
+
+    assert!(!world.contains(b));
+    assert!(world.get::<&str>(a).is_err());
+    assert!(*world.get::<bool>(a).unwrap());
+    assert_eq!(*world.get::<f32>(reserved).unwrap(), 1.0);
+    assert_eq!(*world.get::<&str>(reserved).unwrap(), "ghi");
+    let spawned = world
+        .query::<(&i32, &bool)>()
+        .iter()
+        .map(|(_, (&i, &b))| (i, b))
+        .collect::<Vec<_>>();
+    assert_eq!(spawned.len(), 2);
+    assert!(spawned.contains(&(789, false)));
+    assert!(spawned.contains(&(123, true)));
+
+    // The buffer is reusable
+    cmd.insert_one(a, 42);
+    cmd.run_on(&mut world);
+    assert_eq!(*world.get::<i32>(a).unwrap(), 42);
+}
+
+#[test]
+fn command_buffer_drops_unapplied() {
+    use std::sync::Arc;
+
+    let rc = Arc::new(());
+    let mut cmd = CommandBuffer::new();
+    cmd.spawn((rc.clone(), 123));
+    cmd.insert_one(Entity::from_bits(0), rc.clone());
+    assert_eq!(Arc::strong_count(&rc), 3);
+    drop(cmd);
+    assert_eq!(Arc::strong_count(&rc), 1);
+}
+
+#[test]
+fn change_tracking() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    let b = world.spawn((456,));
+    world.clear_trackers();
+    assert_eq!(world.query::<Changed<i32>>().iter().count(), 0);
+
+    // Moving between archetypes preserves ticks; new components are added, replaced ones mutated
+    world.insert(b, ("abc", 789)).unwrap();
+    assert_eq!(world.query::<Added<i32>>().iter().count(), 0);
+    assert_eq!(
+        world
+            .query::<Mutated<i32>>()
+            .iter()
+            .map(|(e, &i)| (e, i))
+            .collect::<Vec<_>>(),
+        &[(b, 789)]
+    );
+    assert_eq!(
+        world
+            .query::<Added<&str>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        &[b]
+    );
+    world.remove_one::<bool>(a).unwrap();
+    assert_eq!(world.query::<Changed<i32>>().iter().count(), 1);
+
+    world.clear_trackers();
+    for (_, x) in world.query::<&mut i32>().with::<&str>().iter() {
+        *x += 1;
+    }
+    assert!(world.query_one::<Mutated<i32>>(a).unwrap().get().is_none());
+    assert_eq!(
+        world.query_one::<Mutated<i32>>(b).unwrap().get(),
+        Some(&790)
+    );
+    assert_eq!(
+        world
+            .query::<(&i32, Option<Mutated<i32>>)>()
+            .iter()
+            .map(|(_, (_, x))| x.copied())
+            .collect::<Vec<_>>()
+            .len(),
+        2
+    );
+    assert_eq!(
+        world
+            .query::<Mutated<i32>>()
+            .iter_batched(1)
+            .flatten()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        &[b]
+    );
+    assert_eq!(world.query::<Mutated<i32>>().iter().len(), 1);
+}
+
+#[test]
+fn query_or() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    let b = world.spawn((456,));
+    world.spawn(("abc",));
+    world.clear_trackers();
+    let c = world.spawn((false,));
+    for (_, (i, b)) in world.query::<Or<(&mut i32, &bool)>>().iter() {
+        if let (Some(i), Some(&b)) = (i, b) {
+            *i = if b { 1 } else { 0 };
+        }
+    }
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert_eq!(world.query::<Or<(&i32, &bool)>>().iter().len(), 3);
+
+    // Only members matching each archetype are borrowed
+    let _borrow = world.get_mut::<i32>(b).unwrap();
+    assert_eq!(
+        world
+            .query::<Or<(Added<bool>, With<bool, &i32>)>>()
+            .iter()
+            .map(|(e, (added, _))| (e, added.is_some()))
+            .collect::<Vec<_>>(),
+        &[(a, false), (c, true)]
+    );
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn query_or_alias() {
+    let mut world = World::new();
+    world.spawn((123,));
+    world.query::<Or<(&mut i32, &i32)>>().iter();
+}
+
+#[test]
+fn query_satisfies() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    let b = world.spawn((456,));
+    // Doesn't borrow `bool`
+    let _borrow = world.get_mut::<bool>(a).unwrap();
+    let mut entities = world
+        .query::<(&i32, Satisfies<&bool>)>()
+        .iter()
+        .map(|(e, (&i, b))| (e, i, b))
+        .collect::<Vec<_>>();
+    entities.sort();
+    assert_eq!(entities, &[(a, 123, true), (b, 456, false)]);
+}
+
+#[test]
+fn prepared_query() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    let mut query = PreparedQuery::<(&mut i32, &bool)>::new();
+    assert_eq!(query.query(&world).iter().len(), 1);
+
+    // Archetypes created after the first execution are picked up
+    let b = world.spawn((456, false, "abc"));
+    world.spawn((789,));
+    for (_, (x, &flag)) in query.query_mut(&mut world) {
+        if flag {
+            *x *= 2;
+        }
+    }
+    assert_eq!(*world.get::<i32>(a).unwrap(), 246);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 456);
+
+    let mut borrow = query.query(&world);
+    let mut iter = borrow.iter();
+    assert_eq!(iter.len(), 2);
+    iter.next();
+    assert_eq!(iter.len(), 1);
+}
+
+#[test]
+#[should_panic(expected = "different world")]
+fn prepared_query_wrong_world() {
+    let mut query = PreparedQuery::<&i32>::new();
+    query.query(&World::new());
+    query.query(&World::new());
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn prepared_query_mut_alias() {
+    let mut world = World::new();
+    world.spawn((123,));
+    PreparedQuery::<(&mut i32, &i32)>::new().query_mut(&mut world);
+}
+
+#[test]
+fn query_mut() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    let b = world.spawn((456,));
+    // Outstanding reserved entities don't disturb exclusive queries
+    world.reserve_entity();
+    let mut iter = world.query_mut::<(&mut i32, Option<&bool>)>();
+    assert_eq!(iter.len(), 2);
+    for (_, (x, flag)) in &mut iter {
+        if flag.is_some() {
+            *x = 0;
+        }
+    }
+    assert_eq!(*world.get::<i32>(a).unwrap(), 0);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 456);
+
+    *world.query_one_mut::<&mut i32>(b).unwrap() = 42;
+    assert_eq!(*world.get::<i32>(b).unwrap(), 42);
+    assert_eq!(
+        world.query_one_mut::<&bool>(b).err(),
+        Some(QueryOneError::Unsatisfied)
+    );
+    world.despawn(b).unwrap();
+    assert_eq!(
+        world.query_one_mut::<&i32>(b).err(),
+        Some(QueryOneError::NoSuchEntity)
+    );
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn query_mut_alias() {
+    let mut world = World::new();
+    world.spawn((123,));
+    world.query_mut::<(&mut i32, &i32)>();
+}
+
+#[test]
+fn query_view() {
+    let mut world = World::new();
+    let a = world.spawn((123, true));
+    let b = world.spawn((456,));
+    let c = world.spawn(("abc",));
+    let reserved = world.reserve_entity();
+    world.despawn(c).unwrap();
+
+    {
+        let mut query = world.query::<(&i32, Option<&bool>)>();
+        let view = query.view();
+        assert_eq!(view.get(a), Some((&123, Some(&true))));
+        assert_eq!(view.get(b), Some((&456, None)));
+        assert_eq!(view.get(c), None);
+        assert_eq!(view.get(reserved), None);
+    }
+
+    let mut query = world.query::<&mut i32>().with::<bool>();
+    let mut view = query.view();
+    assert_eq!(view.get_many_mut([a, b]), [Some(&mut 123), None]);
+}
+
+#[test]
+#[should_panic(expected = "occurs more than once")]
+fn query_view_duplicate() {
+    let mut world = World::new();
+    let a = world.spawn((123,));
+    world.query::<&mut i32>().view().get_many_mut([a, a]);
+}
+
+#[test]
+#[should_panic(expected = "already borrowed")]
+fn query_view_conflict() {
+    let mut world = World::new();
+    world.spawn((123,));
+    let mut q = world.query::<&i32>();
+    let _v = q.view();
+    world.query::<&mut i32>().view();
+}
+
+#[test]
+#[cfg(feature = "rayon")]
+fn par_iter() {
+    use rayon::iter::ParallelIterator;
+
+    let mut world = World::new();
+    let mut entities = world
+        .spawn_batch((0..5000).map(|i| (i, true)))
+        .collect::<Vec<_>>();
+    entities.extend(world.spawn_batch((5000..7500).map(|i| (i,))));
+    world.spawn_batch((0..100).map(|_| (false,)));
+
+    let mut visited = world
+        .query::<&i32>()
+        .par_iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    visited.sort_unstable_by_key(|e| e.id());
+    assert_eq!(visited, entities);
+
+    world
+        .query::<(&mut i32, Option<&bool>)>()
+        .par_for_each(100, |_, (i, flag)| {
+            if flag.is_some() {
+                *i = -*i;
+            }
+        });
+    for (i, &e) in entities.iter().enumerate() {
+        let expected = if i < 5000 { -(i as i32) } else { i as i32 };
+        assert_eq!(*world.get::<i32>(e).unwrap(), expected);
+    }
+
+    // Batches are split within archetypes, never across chunks of storage
+    let mut world = World::with_chunk_size(256);
+    world.spawn_batch((0..1000).map(|i| (i, true)));
+    world.spawn_batch((1000..1003).map(|i| (i,)));
+    world.query::<&mut i32>().par_for_each(100, |_, i| *i += 1);
+    let sum: i32 = world.query::<&i32>().par_iter().map(|(_, &i)| i).sum();
+    assert_eq!(sum, (1..=1003).sum::<i32>());
+    assert!(world.query::<&i32>().par_iter().any(|(_, &i)| i == 1003));
+}
+
+#[test]
+#[should_panic(expected = "too many entities")]
+fn spawn_at_max_id() {
+    let mut world = World::new();
+    world.spawn((1,));
+    let entity = Entity::from_bits(u64::from(u32::MAX));
+    world.spawn_at(entity, (2,));
+}
+
+#[test]
+fn spawn_at() {
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    world.despawn(a).unwrap();
+    let b = world.spawn((2,));
+    assert_eq!(a.id(), b.id());
+
+    let mut other = World::new();
+    other.spawn_at(b, (3, true));
+    assert!(!other.contains(a));
+    assert_eq!(*other.get::<i32>(b).unwrap(), 3);
+    // Allocation skips the occupied ID
+    let c = other.spawn((4,));
+    assert_ne!(c.id(), b.id());
+    // Replaces the existing entity
+    other.spawn_at(b, ("abc",));
+    assert!(other.get::<i32>(b).is_err());
+    assert_eq!(*other.get::<&str>(b).unwrap(), "abc");
+    assert_eq!(*other.get::<i32>(c).unwrap(), 4);
+    assert_eq!(other.iter().count(), 2);
+}
+
+#[test]
+fn take() {
+    use std::sync::Arc;
+
+    let rc = Arc::new(());
+    let mut world = World::new();
+    let a = world.spawn((1, "abc", rc.clone()));
+    let b = world.spawn((2, "def", rc.clone()));
+    let c = world.spawn((true,));
+    world.set_parent(c, a).unwrap();
+
+    let taken = world.take(a).unwrap();
+    assert!(!world.contains(a));
+    assert_eq!(world.take(a).err(), Some(NoSuchEntity));
+    assert_eq!(world.parent_of(c), Ok(None));
+    // The entity moved into the vacated slot is still reachable
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+    assert_eq!(Arc::strong_count(&rc), 3);
+
+    let mut other = World::new();
+    let d = other.spawn(taken);
+    assert_eq!(*other.get::<i32>(d).unwrap(), 1);
+    assert_eq!(*other.get::<&str>(d).unwrap(), "abc");
+    assert_eq!(Arc::strong_count(&rc), 3);
+
+    // Unspawned components are dropped
+    drop(world.take(b).unwrap());
+    assert_eq!(Arc::strong_count(&rc), 2);
+}
+
+#[test]
+fn clone_entity() {
+    use std::sync::Arc;
+
+    let rc = Arc::new(());
+    let mut world = World::new();
+    world.register_clone::<i32>();
+    world.register_clone::<Arc<()>>();
+    let a = world.spawn((1, rc.clone()));
+    let b = world.clone_entity(a).unwrap();
+    assert_ne!(a, b);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 1);
+    assert_eq!(Arc::strong_count(&rc), 3);
+    *world.get_mut::<i32>(a).unwrap() = 2;
+    assert_eq!(*world.get::<i32>(b).unwrap(), 1);
+
+    let c = world.spawn((3, "abc"));
+    let err = world.clone_entity(c).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "&str component is not registered as cloneable"
+    );
+    assert_eq!(world.iter().count(), 3);
+    world.despawn(c).unwrap();
+    assert_eq!(world.clone_entity(c), Err(CloneError::NoSuchEntity));
+
+    world.despawn(a).unwrap();
+    world.despawn(b).unwrap();
+    assert_eq!(Arc::strong_count(&rc), 1);
+}
+
+#[test]
+fn clone_entity_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::Arc;
+
+    struct Panicky;
+    impl Clone for Panicky {
+        fn clone(&self) -> Self {
+            panic!("clone failed");
+        }
+    }
+    #[derive(Clone)]
+    struct Stunned(Panicky);
+
+    let rc = Arc::new(());
+    let mut world = World::new();
+    world.register_clone::<Arc<()>>();
+    world.register_clone::<Panicky>();
+    world.register_clone::<Stunned>();
+    world.register_sparse::<Stunned>();
+    let a = world.spawn((rc.clone(), Panicky));
+    assert!(catch_unwind(AssertUnwindSafe(|| world.clone_entity(a))).is_err());
+    assert_eq!(world.iter().count(), 1);
+    assert_eq!(world.query::<&Panicky>().iter().count(), 1);
+    assert_eq!(Arc::strong_count(&rc), 2);
+
+    // Sparse components are cloned into an entity that's otherwise complete
+    let b = world.spawn((rc.clone(),));
+    world.insert_one(b, Stunned(Panicky)).unwrap();
+    assert!(catch_unwind(AssertUnwindSafe(|| world.clone_entity(b))).is_err());
+    assert_eq!(world.query::<&Arc<()>>().iter().count(), 3);
+    assert_eq!(world.query::<&Stunned>().iter().count(), 1);
+    assert_eq!(Arc::strong_count(&rc), 4);
+    drop(world);
+    assert_eq!(Arc::strong_count(&rc), 1);
+}
+
+#[test]
+fn try_clone() {
+    let mut world = World::new();
+    world.register_clone::<i32>();
+    world.register_clone::<bool>();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2,));
+    let c = world.spawn((3,));
+    world.despawn(c).unwrap();
+    world.set_parent(b, a).unwrap();
+    world.clear_trackers();
+    *world.get_mut::<i32>(b).unwrap() = 4;
+
+    let mut clone = world.try_clone().unwrap();
+    assert_eq!(*clone.get::<i32>(a).unwrap(), 1);
+    assert!(*clone.get::<bool>(a).unwrap());
+    assert_eq!(*clone.get::<i32>(b).unwrap(), 4);
+    assert!(!clone.contains(c));
+    assert_eq!(clone.parent_of(b), Ok(Some(a)));
+    assert_eq!(
+        clone
+            .query::<Mutated<i32>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [b]
+    );
+
+    // The copy is independent and allocates the same IDs
+    *clone.get_mut::<i32>(a).unwrap() = 5;
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert_eq!(world.spawn((6,)), clone.spawn((6,)));
+
+    world.spawn((7, "abc"));
+    assert_eq!(
+        world.try_clone().err().unwrap().to_string(),
+        "&str component is not registered as cloneable"
+    );
+}
+
+#[test]
+fn runtime_defined_components() {
+    use std::alloc::Layout;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+    unsafe fn count_drop(_: *mut u8) {
+        DROPS.fetch_add(1, Ordering::Relaxed);
+    }
+    let health = unsafe { TypeInfo::dynamic(0, Layout::new::<u32>(), count_drop, "health") };
+    let tag = unsafe { TypeInfo::dynamic(1, Layout::new::<()>(), count_drop, "tag") };
+
+    let mut world = World::new();
+    let mut builder = EntityBuilder::new();
+    builder.add(1i32).add_bytes(health, &10u32.to_ne_bytes());
+    let a = world.spawn(builder.build());
+    builder
+        .add_bytes(health, &20u32.to_ne_bytes())
+        .add_bytes(tag, &[]);
+    let b = world.spawn(builder.build());
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+
+    let mut seen = Vec::new();
+    for chunk in world.query_dynamic(&[health.id()]) {
+        let mut column = chunk.get_mut(health.id()).unwrap();
+        for (entity, bytes) in chunk.entities().zip(column.chunks_exact_mut(4)) {
+            let mut value = [0; 4];
+            value.copy_from_slice(bytes);
+            let value = u32::from_ne_bytes(value);
+            seen.push((entity, value));
+            bytes.copy_from_slice(&(value + 1).to_ne_bytes());
+        }
+        // Rust types aren't exposed as bytes
+        assert!(chunk.get(ComponentId::of::<i32>()).is_none());
+    }
+    seen.sort();
+    assert_eq!(seen, [(a, 10), (b, 20)]);
+
+    let chunks = world
+        .query_dynamic(&[health.id(), tag.id()])
+        .collect::<Vec<_>>();
+    assert_eq!(chunks.len(), 1);
+    assert_eq!(chunks[0].entities().collect::<Vec<_>>(), [b]);
+    assert_eq!(&*chunks[0].get(health.id()).unwrap(), &21u32.to_ne_bytes());
+    drop(chunks);
+
+    assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+    world.despawn(b).unwrap();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+    world.clear();
+    assert_eq!(DROPS.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+#[should_panic(expected = "health already borrowed")]
+fn runtime_defined_column_conflict() {
+    use std::alloc::Layout;
+
+    unsafe fn drop_nothing(_: *mut u8) {}
+    let health = unsafe { TypeInfo::dynamic(0, Layout::new::<u32>(), drop_nothing, "health") };
+    let mut world = World::new();
+    let mut builder = EntityBuilder::new();
+    builder.add_bytes(health, &[0; 4]);
+    world.spawn(builder.build());
+    let ids = [health.id()];
+    let chunk = world.query_dynamic(&ids).next().unwrap();
+    let _a = chunk.get(health.id()).unwrap();
+    let _b = chunk.get_mut(health.id()).unwrap();
+}
+
+#[cfg(feature = "serde")]
+mod serde_world {
+    use hecs::*;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Position(f32, f32);
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Target(Entity);
+
+    struct Transient;
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry
+            .register::<Position>("position")
+            .register::<Target>("target")
+            .register::<String>("name");
+        registry
+    }
+
+    fn world() -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        let dead = world.spawn((Position(0.0, 0.0),));
+        world.despawn(dead).unwrap();
+        let a = world.spawn((Position(1.0, 2.0), "a".to_string()));
+        let b = world.spawn((Position(3.0, 4.0), Target(a), Transient));
+        let c = world.spawn(());
+        let d = world.spawn((Target(b),));
+        (world, vec![a, b, c, d])
+    }
+
+    fn check(world: &World, entities: &[Entity]) {
+        let [a, b, c, d] = [entities[0], entities[1], entities[2], entities[3]];
+        assert_ne!(
+            a.to_bits(),
+            u64::from(a.id()),
+            "generation should be nonzero"
+        );
+        assert_eq!(world.iter().count(), 4);
+        assert_eq!(*world.get::<Position>(a).unwrap(), Position(1.0, 2.0));
+        assert_eq!(*world.get::<String>(a).unwrap(), "a");
+        assert_eq!(*world.get::<Position>(b).unwrap(), Position(3.0, 4.0));
+        assert_eq!(*world.get::<Target>(b).unwrap(), Target(a));
+        assert!(world.get::<Transient>(b).is_err());
+        assert!(world.contains(c));
+        assert!(world.get::<Position>(c).is_err());
+        assert_eq!(*world.get::<Target>(d).unwrap(), Target(b));
+    }
+
+    #[test]
+    fn rows() {
+        let (world, entities) = world();
+        let registry = registry();
+        let json = serde_json::to_string(&registry.serialize_rows(&world)).unwrap();
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let restored = registry.deserialize_rows(&mut deserializer).unwrap();
+        check(&restored, &entities);
+    }
+
+    #[test]
+    fn columns() {
+        let (world, entities) = world();
+        let registry = registry();
+        let json = serde_json::to_string(&registry.serialize_columns(&world)).unwrap();
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let restored = registry.deserialize_columns(&mut deserializer).unwrap();
+        check(&restored, &entities);
+    }
+
+    #[test]
+    fn sparse() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Stunned(u32);
+
+        let mut world = World::new();
+        world.register_sparse::<Stunned>();
+        let a = world.spawn((Position(1.0, 2.0), Stunned(3)));
+        let b = world.spawn((Position(3.0, 4.0),));
+        let c = world.spawn((Stunned(5),));
+
+        for &sparse in &[false, true] {
+            let mut registry = registry();
+            if sparse {
+                registry.register_sparse::<Stunned>("stunned");
+            } else {
+                registry.register::<Stunned>("stunned");
+            }
+            let rows = serde_json::to_string(&registry.serialize_rows(&world)).unwrap();
+            let columns = serde_json::to_string(&registry.serialize_columns(&world)).unwrap();
+            let restored = [
+                registry.deserialize_rows(&mut serde_json::Deserializer::from_str(&rows)),
+                registry.deserialize_columns(&mut serde_json::Deserializer::from_str(&columns)),
+            ];
+            for restored in restored {
+                let restored = restored.unwrap();
+                assert_eq!(restored.iter().count(), 3);
+                assert_eq!(*restored.get::<Position>(a).unwrap(), Position(1.0, 2.0));
+                assert_eq!(*restored.get::<Stunned>(a).unwrap(), Stunned(3));
+                assert_eq!(*restored.get::<Position>(b).unwrap(), Position(3.0, 4.0));
+                assert!(restored.get::<Stunned>(b).is_err());
+                assert_eq!(*restored.get::<Stunned>(c).unwrap(), Stunned(5));
+                // The empty archetype and `(Position,)`, plus those including `Stunned` if dense
+                assert_eq!(restored.archetypes().len(), if sparse { 2 } else { 4 });
+            }
+        }
+    }
+
+    #[test]
+    fn entity_limit() {
+        let mut registry = registry();
+        registry.entity_limit(1000);
+        let columns = r#"[[[5, 1000], {"position": [[1.0, 2.0], [3.0, 4.0]]}]]"#;
+        let err = registry
+            .deserialize_columns(&mut serde_json::Deserializer::from_str(columns))
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("entity ID 1000 exceeds the limit of 1000"));
+        let rows = r#"[[999, {"position": [1.0, 2.0]}]]"#;
+        let world = registry
+            .deserialize_rows(&mut serde_json::Deserializer::from_str(rows))
+            .unwrap();
+        assert_eq!(world.iter().count(), 1);
+    }
+
+    #[test]
+    fn unregistered_name() {
+        let mut deserializer = serde_json::Deserializer::from_str(r#"[[0, {"velocity": 1}]]"#);
+        let err = registry()
+            .deserialize_rows(&mut deserializer)
+            .err()
+            .unwrap();
+        assert!(err
+            .to_string()
+            .contains("unregistered component name `velocity`"));
+    }
+}
+
+#[test]
+fn hierarchy() {
+    let mut world = World::new();
+    let root = world.spawn(());
+    let a = world.spawn(());
+    let b = world.spawn(());
+    let c = world.spawn(());
+    let d = world.spawn(());
+    world.set_parent(a, root).unwrap();
+    world.set_parent(b, root).unwrap();
+    world.set_parent(c, a).unwrap();
+    world.set_parent(d, c).unwrap();
+
+    assert_eq!(world.children_of(root).unwrap(), &[a, b]);
+    assert_eq!(world.parent_of(root).unwrap(), None);
+    assert_eq!(
+        world.ancestors(d).unwrap().collect::<Vec<_>>(),
+        [c, a, root]
+    );
+    assert_eq!(
+        world
+            .descendants_depth_first(root)
+            .unwrap()
+            .collect::<Vec<_>>(),
+        [a, c, d, b]
+    );
+    assert_eq!(
+        world
+            .descendants_breadth_first(root)
+            .unwrap()
+            .collect::<Vec<_>>(),
+        [a, b, c, d]
+    );
+    assert_eq!(world.set_parent(root, d), Err(HierarchyError::Cycle));
+    assert_eq!(world.set_parent(a, a), Err(HierarchyError::Cycle));
+
+    // Reparenting
+    world.set_parent(c, b).unwrap();
+    assert_eq!(world.children_of(a).unwrap(), &[]);
+    assert_eq!(world.children_of(b).unwrap(), &[c]);
+    assert_eq!(world.remove_parent(c), Ok(Some(b)));
+    assert_eq!(world.remove_parent(c), Ok(None));
+    assert_eq!(world.children_of(b).unwrap(), &[]);
+}
+
+#[test]
+fn hierarchy_despawn() {
+    let mut world = World::new();
+    let root = world.spawn(());
+    let a = world.spawn(());
+    let b = world.spawn(());
+    let c = world.spawn(());
+    world.set_parent(a, root).unwrap();
+    world.set_parent(b, a).unwrap();
+    world.set_parent(c, b).unwrap();
+
+    world.despawn(b).unwrap();
+    assert_eq!(world.children_of(a).unwrap(), &[]);
+    assert_eq!(world.parent_of(c).unwrap(), None);
+    assert_eq!(world.children_of(b), Err(NoSuchEntity));
+    // A recycled ID doesn't inherit relationships
+    let e = world.spawn(());
+    assert_eq!(e.id(), b.id());
+    assert_eq!(world.parent_of(e).unwrap(), None);
+    assert_eq!(world.set_parent(c, b), Err(HierarchyError::NoSuchEntity));
+
+    world.set_parent(c, a).unwrap();
+    world.despawn_recursive(a).unwrap();
+    assert!(world.contains(root));
+    assert!(!world.contains(a));
+    assert!(!world.contains(c));
+    assert_eq!(world.children_of(root).unwrap(), &[]);
+
+    world.set_parent(e, root).unwrap();
+    world.clear();
+    let x = world.spawn(());
+    let y = world.spawn(());
+    assert!(world.children_of(x).unwrap().is_empty());
+    assert!(world.children_of(y).unwrap().is_empty());
+    assert_eq!(world.parent_of(x).unwrap(), None);
+    assert_eq!(world.parent_of(y).unwrap(), None);
+}
+
+#[test]
+fn observers() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Event {
+        Add(Entity, i32),
+        Remove(Entity, i32),
+        Despawn(Entity),
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    let log = events.clone();
+    world.on_add::<i32>(move |e, &x| log.lock().unwrap().push(Event::Add(e, x)));
+    let log = events.clone();
+    world.on_remove::<i32>(move |e, &x| log.lock().unwrap().push(Event::Remove(e, x)));
+    let log = events.clone();
+    world.on_despawn(move |e| log.lock().unwrap().push(Event::Despawn(e)));
+    let take = || std::mem::take(&mut *events.lock().unwrap());
+
+    let a = world.spawn((1, true));
+    let b = world.spawn((false,));
+    assert_eq!(take(), [Event::Add(a, 1)]);
+    let batch = world.spawn_batch(vec![(2,), (3,)]).collect::<Vec<_>>();
+    assert_eq!(take(), [Event::Add(batch[0], 2), Event::Add(batch[1], 3)]);
+
+    world.insert(b, (4, "abc")).unwrap();
+    assert_eq!(take(), [Event::Add(b, 4)]);
+    world.insert_one(b, 5).unwrap();
+    assert_eq!(take(), [Event::Remove(b, 4), Event::Add(b, 5)]);
+    world.insert_one(b, 'x').unwrap();
+    assert_eq!(take(), []);
+    assert_eq!(world.remove_one::<i32>(b), Ok(5));
+    assert_eq!(take(), [Event::Remove(b, 5)]);
+    assert!(world.remove_one::<i32>(b).is_err());
+    assert_eq!(take(), []);
+
+    world.despawn(a).unwrap();
+    assert_eq!(take(), [Event::Remove(a, 1), Event::Despawn(a)]);
+    world.despawn(b).unwrap();
+    assert_eq!(take(), [Event::Despawn(b)]);
+
+    world.clear();
+    let mut events = take();
+    events.sort_by_key(|x| match *x {
+        Event::Despawn(e) => (e, 1),
+        Event::Add(e, _) | Event::Remove(e, _) => (e, 0),
+    });
+    assert_eq!(
+        events,
+        [
+            Event::Remove(batch[0], 2),
+            Event::Despawn(batch[0]),
+            Event::Remove(batch[1], 3),
+            Event::Despawn(batch[1]),
+        ]
+    );
+}
+
+#[test]
+fn despawn_observer_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let panicky = Arc::new(AtomicBool::new(true));
+    let mut world = World::new();
+    let flag = panicky.clone();
+    world.on_despawn(move |_| assert!(!flag.load(Ordering::Relaxed)));
+    let a = world.spawn((1,));
+    let b = world.spawn((2,));
+    let c = world.spawn((3,));
+
+    let check = |world: &mut World| {
+        for &(e, x) in &[(a, 1), (b, 2), (c, 3)] {
+            assert_eq!(*world.get::<i32>(e).unwrap(), x);
+        }
+        assert_eq!(world.query_mut::<&i32>().count(), 3);
+    };
+    let mut attempt = |f: &mut dyn FnMut(&mut World)| {
+        assert!(catch_unwind(AssertUnwindSafe(|| f(&mut world))).is_err());
+        check(&mut world);
+    };
+    attempt(&mut |world| world.despawn(b).unwrap());
+    attempt(&mut |world| world.despawn_batch([c, a]));
+    attempt(&mut |world| world.retain::<&i32, _>(|_, &x| x != 2));
+    attempt(&mut |world| drop(world.take(c)));
+    attempt(&mut |world| world.spawn_at(a, (5,)));
+
+    panicky.store(false, Ordering::Relaxed);
+    world.despawn_batch([c, a]);
+    assert!(!world.contains(a) && world.contains(b) && !world.contains(c));
+    let d = world.spawn((6,));
+    assert_eq!(world.query_mut::<&i32>().count(), 2);
+    assert_eq!(*world.get::<i32>(d).unwrap(), 6);
+}
+
+#[test]
+fn clear_observer_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut world = World::new();
+    let a = world.spawn((1,));
+    let b = world.spawn((2, true));
+    world.on_despawn(move |e| assert_ne!(e, b));
+    assert!(catch_unwind(AssertUnwindSafe(|| world.clear())).is_err());
+    assert_eq!(*world.get::<i32>(a).unwrap(), 1);
+    assert_eq!(*world.get::<i32>(b).unwrap(), 2);
+    assert_eq!(world.query_mut::<&i32>().count(), 2);
+}
+
+#[test]
+fn remove_observer_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Tracked(Arc<AtomicUsize>);
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+    struct Sparse(Tracked);
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let panicky = Arc::new(AtomicBool::new(true));
+    let mut world = World::new();
+    world.register_sparse::<Sparse>();
+    let flag = panicky.clone();
+    world.on_remove::<Tracked>(move |_, _| assert!(!flag.load(Ordering::Relaxed)));
+    let flag = panicky.clone();
+    world.on_remove::<Sparse>(move |_, _| assert!(!flag.load(Ordering::Relaxed)));
+    let e = world.spawn((1, Tracked(drops.clone()), Sparse(Tracked(drops.clone()))));
+
+    for remove in [
+        |world: &mut World, e| drop(world.remove_one::<Tracked>(e)),
+        |world: &mut World, e| drop(world.remove_one::<Sparse>(e)),
+        |world: &mut World, e| drop(world.remove::<(i32, Tracked, Sparse)>(e)),
+    ] {
+        assert!(catch_unwind(AssertUnwindSafe(|| remove(&mut world, e))).is_err());
+        assert_eq!(drops.load(Ordering::Relaxed), 0);
+        assert!(world.get::<Tracked>(e).is_ok());
+        assert!(Arc::ptr_eq(&world.get::<Sparse>(e).unwrap().0 .0, &drops));
+    }
+    drop(world);
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn observe_before_drop() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    struct Tracked(Arc<AtomicUsize>);
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let drops = Arc::new(AtomicUsize::new(0));
+    let observed = Arc::new(AtomicUsize::new(0));
+    let mut world = World::new();
+    let counter = observed.clone();
+    world.on_remove::<Tracked>(move |_, x| {
+        // The value hasn't been dropped yet
+        counter.fetch_add(1, Ordering::Relaxed);
+        assert_eq!(
+            x.0.load(Ordering::Relaxed),
+            counter.load(Ordering::Relaxed) - 1
+        );
+    });
+    let e = world.spawn((Tracked(drops.clone()),));
+    world.insert_one(e, Tracked(drops.clone())).unwrap();
+    world.despawn(e).unwrap();
+    assert_eq!(observed.load(Ordering::Relaxed), 2);
+    assert_eq!(drops.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn sparse_components() {
+    #[derive(Debug, PartialEq)]
+    struct Stunned(u32);
+    struct Selected;
+
+    let mut world = World::new();
+    world.register_sparse::<Stunned>();
+    world.register_sparse::<Selected>();
+    let a = world.spawn((1, true));
+    let b = world.spawn((2, true, Stunned(5)));
+    let c = world.spawn((Stunned(6),));
+    let generation = world.archetypes_generation();
+
+    world.insert_one(a, Stunned(3)).unwrap();
+    world.insert(a, (Selected, false)).unwrap();
+    world.insert_one(b, Selected).unwrap();
+    assert_eq!(world.archetypes_generation(), generation);
+    assert_eq!(*world.get::<Stunned>(a).unwrap(), Stunned(3));
+    assert!(!*world.get::<bool>(a).unwrap());
+    assert_eq!(*world.get::<Stunned>(c).unwrap(), Stunned(6));
+    assert!(world.get::<Selected>(c).is_err());
+    assert!(world.query_one::<&Selected>(b).unwrap().get().is_some());
+
+    let mut stunned = world
+        .query::<(&Stunned, Option<&i32>)>()
+        .iter()
+        .map(|(e, (s, i))| (e, s.0, i.copied()))
+        .collect::<Vec<_>>();
+    stunned.sort();
+    assert_eq!(stunned, [(a, 3, Some(1)), (b, 5, Some(2)), (c, 6, None)]);
+    assert_eq!(world.query::<With<Selected, &i32>>().iter().count(), 2);
+    assert_eq!(world.query::<Without<Selected, &Stunned>>().iter().len(), 1);
+    assert_eq!(world.query::<(&bool, Option<&Selected>)>().iter().len(), 2);
+    world.remove_one::<Selected>(b).unwrap();
+    assert_eq!(world.query::<(&bool, &Selected)>().iter().len(), 1);
+    world.insert_one(b, Selected).unwrap();
+    let mut satisfied = world
+        .query::<(&i32, Satisfies<&Selected>)>()
+        .iter()
+        .map(|(_, (&i, s))| (i, s))
+        .collect::<Vec<_>>();
+    satisfied.sort();
+    assert_eq!(satisfied, [(1, true), (2, true)]);
+
+    for (_, s) in world.query_mut::<&mut Stunned>() {
+        s.0 *= 10;
+    }
+    assert_eq!(world.get::<Stunned>(b).unwrap().0, 50);
+    let stun = world.get_mut::<Stunned>(a).unwrap();
+    assert!(world.try_get::<Stunned>(b).is_err());
+    assert!(world.try_get::<Stunned>(c).is_ok());
+    drop(stun);
+
+    world.clear_trackers();
+    world.insert_one(a, Stunned(4)).unwrap();
+    world.insert_one(b, 7).unwrap();
+    assert_eq!(
+        world.query::<Added<Stunned>>().iter().count(),
+        0,
+        "replacing a sparse component doesn't add it"
+    );
+    let mut changed = world
+        .query::<Changed<Stunned>>()
+        .iter()
+        .map(|(e, _)| e)
+        .collect::<Vec<_>>();
+    changed.sort();
+    assert_eq!(changed, [a]);
+
+    assert_eq!(world.remove_one::<Stunned>(a), Ok(Stunned(4)));
+    assert!(world.remove_one::<Stunned>(a).is_err());
+    assert_eq!(world.remove::<(Stunned, i32)>(b), Ok((Stunned(50), 7)));
+    assert!(world.get::<Stunned>(b).is_err());
+    assert!(world.get::<Selected>(b).is_ok());
+    assert_eq!(world.query::<&Stunned>().iter().count(), 1);
+
+    let mut query = PreparedQuery::<&Stunned>::new();
+    assert_eq!(query.query(&world).iter().count(), 1);
+    let d = world.spawn(("abc", Stunned(8)));
+    assert_eq!(query.query(&world).iter().count(), 2);
+    world.despawn(c).unwrap();
+    assert_eq!(
+        query
+            .query_mut(&mut world)
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [d]
+    );
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn sparse_derived_query() {
+    #[derive(Debug, PartialEq)]
+    struct Stunned(u32);
+    struct Selected;
+
+    #[derive(Query, Debug, PartialEq)]
+    #[query(with(Selected))]
+    struct SelectedNumber<'a> {
+        number: &'a i32,
+        stunned: Option<&'a Stunned>,
+    }
+
+    let mut world = World::new();
+    world.register_sparse::<Stunned>();
+    world.register_sparse::<Selected>();
+    let a = world.spawn((1, Selected));
+    let b = world.spawn((2, Selected, Stunned(3)));
+    world.spawn((4, Stunned(5)));
+    assert_eq!(
+        world.query_one_mut::<SelectedNumber>(b),
+        Ok(SelectedNumber {
+            number: &2,
+            stunned: Some(&Stunned(3)),
+        })
+    );
+    let mut selected = world
+        .query_mut::<SelectedNumber>()
+        .map(|(e, x)| (e, *x.number, x.stunned.map(|s| s.0)))
+        .collect::<Vec<_>>();
+    selected.sort();
+    assert_eq!(selected, [(a, 1, None), (b, 2, Some(3))]);
+}
+
+#[test]
+#[should_panic(expected = "before any entity has one")]
+fn sparse_after_use() {
+    let mut world = World::new();
+    world.spawn((123,));
+    world.register_sparse::<i32>();
+}
+
+#[test]
+#[should_panic(expected = "is sparse")]
+fn sparse_chunks() {
+    let mut world = World::new();
+    world.register_sparse::<bool>();
+    world.spawn((123, true));
+    world.query::<With<bool, &i32>>().iter_chunks().count();
+}
+
+#[test]
+fn sparse_lifecycle() {
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct Tracked(Arc<Mutex<Vec<&'static str>>>, &'static str);
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            self.0.lock().unwrap().push(self.1);
+        }
+    }
+
+    let drops = Arc::new(Mutex::new(Vec::new()));
+    let tracked = |name| Tracked(drops.clone(), name);
+    let take_drops = || {
+        let mut x = std::mem::take(&mut *drops.lock().unwrap());
+        x.sort();
+        x
+    };
+    let observed = Arc::new(Mutex::new(Vec::new()));
+    let mut world = World::new();
+    world.register_sparse::<Tracked>();
+    world.register_clone::<Tracked>();
+    world.register_clone::<i32>();
+    let log = observed.clone();
+    world.on_add::<Tracked>(move |_, x| log.lock().unwrap().push(("add", x.1)));
+    let log = observed.clone();
+    world.on_remove::<Tracked>(move |_, x| log.lock().unwrap().push(("remove", x.1)));
+    let take_observed = || std::mem::take(&mut *observed.lock().unwrap());
+
+    let a = world.spawn((1, tracked("a")));
+    let batch = world
+        .spawn_batch(vec![(2, tracked("b")), (3, tracked("c"))])
+        .collect::<Vec<_>>();
+    assert_eq!(take_observed(), [("add", "a"), ("add", "b"), ("add", "c")]);
+    world.insert_one(a, tracked("d")).unwrap();
+    assert_eq!(take_drops(), ["a"]);
+    assert_eq!(take_observed(), [("remove", "a"), ("add", "d")]);
+
+    let clone = world.clone_entity(a).unwrap();
+    assert_eq!(world.get::<Tracked>(clone).unwrap().1, "d");
+    assert_eq!(take_observed(), [("add", "d")]);
+    let snapshot = world.try_clone().unwrap();
+    assert_eq!(snapshot.get::<Tracked>(batch[1]).unwrap().1, "c");
+    drop(snapshot);
+    assert_eq!(take_drops(), ["b", "c", "d", "d"]);
+
+    world.despawn(clone).unwrap();
+    assert_eq!(take_drops(), ["d"]);
+    assert_eq!(take_observed(), [("remove", "d")]);
+    let taken = world.take(a).unwrap();
+    assert_eq!(take_observed(), [("remove", "d")]);
+    let e = world.spawn(taken);
+    assert_eq!(world.get::<Tracked>(e).unwrap().1, "d");
+    assert_eq!(*world.get::<i32>(e).unwrap(), 1);
+    assert_eq!(take_drops(), Vec::<&str>::new());
+
+    world.despawn_batch(vec![batch[0]]);
+    assert_eq!(take_drops(), ["b"]);
+    world.retain::<&i32, _>(|_, &i| i != 3);
+    assert_eq!(take_drops(), ["c"]);
+    world.spawn((tracked("f"),));
+    take_observed();
+    world.clear();
+    assert_eq!(take_drops(), ["d", "f"]);
+    let mut observed = take_observed();
+    observed.sort();
+    assert_eq!(observed, [("remove", "d"), ("remove", "f")]);
+    let e = world.spawn((4,));
+    assert!(world.get::<Tracked>(e).is_err());
+}
+
+#[test]
+fn chunked_storage() {
+    // Room for several entities of (i64, u32) per chunk, but far fewer than 100
+    let mut world = World::with_chunk_size(256);
+    let entities = (0..100)
+        .map(|i| world.spawn((i as i64, i as u32)))
+        .collect::<Vec<_>>();
+    let first = world.query_one_mut::<&i64>(entities[0]).unwrap() as *const i64;
+    world.spawn_batch((100..200).map(|i| (i as i64, i as u32)));
+    world.reserve::<(i64, u32)>(1000);
+    // Growing never moves existing components
+    assert_eq!(
+        world.query_one_mut::<&i64>(entities[0]).unwrap() as *const i64,
+        first
+    );
+
+    let mut lens = Vec::new();
+    for (ids, (a, b)) in world.query::<(&i64, &mut u32)>().iter_chunks() {
+        assert_eq!(ids.len(), a.len());
+        for (&a, b) in a.iter().zip(b) {
+            assert_eq!(a as u32, *b);
+            *b += 1;
+        }
+        lens.push(ids.len());
+    }
+    assert!(lens.len() > 2);
+    assert_eq!(lens.iter().sum::<usize>(), 200);
+    // Batches stop at the end of each chunk
+    let batches = world
+        .query::<&i64>()
+        .iter_batched(1000)
+        .map(|x| x.count())
+        .collect::<Vec<_>>();
+    assert_eq!(batches, lens);
+    assert_eq!(world.query::<&u32>().iter().len(), 200);
+    assert_eq!(
+        world.query::<&u32>().iter().map(|(_, &x)| x).sum::<u32>(),
+        (1..=200).sum::<u32>()
+    );
+    let mut query = PreparedQuery::<&u32>::new();
+    assert_eq!(query.query_mut(&mut world).len(), 200);
+
+    // Removal fills gaps with entities from the last chunk
+    world.despawn(entities[3]).unwrap();
+    world.remove_one::<u32>(entities[10]).unwrap();
+    world.clear_trackers();
+    *world.get_mut::<u32>(entities[50]).unwrap() = 0;
+    assert_eq!(
+        world
+            .query::<Mutated<u32>>()
+            .iter()
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>(),
+        [entities[50]]
+    );
+    let mut query = world.query::<&i64>();
+    let view = query.view();
+    for (i, &e) in entities.iter().enumerate().filter(|&(i, _)| i != 3) {
+        assert_eq!(*view.get(e).unwrap(), i as i64);
+    }
+    drop(query);
+    world.retain::<&i64, _>(|_, &x| x % 2 == 0);
+    assert_eq!(world.query::<&i64>().iter().len(), 100);
+    assert!(world.query::<&i64>().iter().all(|(_, &x)| x % 2 == 0));
+
+    world.register_clone::<i64>();
+    world.register_clone::<u32>();
+    let mut snapshot = world.try_clone().unwrap();
+    snapshot.spawn_batch((0..100).map(|i| (i as i64, i as u32)));
+    assert_eq!(snapshot.query::<&i64>().iter().len(), 200);
+    assert_eq!(
+        world.query::<&i64>().iter().map(|(_, &x)| x).sum::<i64>(),
+        snapshot
+            .query::<&i64>()
+            .iter()
+            .map(|(_, &x)| x)
+            .sum::<i64>()
+            - (0..100).sum::<i64>()
+    );
+}
+
+#[test]
+fn chunked_columns() {
+    use std::alloc::Layout;
+
+    unsafe fn drop_nothing(_: *mut u8) {}
+    let health = unsafe { TypeInfo::dynamic(0, Layout::new::<u32>(), drop_nothing, "health") };
+    let mut world = World::with_chunk_size(64);
+    let mut batch = ColumnBatch::new();
+    batch.add((0..50u64).collect::<Vec<_>>());
+    let entities = world.spawn_column_batch(batch).collect::<Vec<_>>();
+    for (i, &e) in entities.iter().enumerate() {
+        assert_eq!(*world.get::<u64>(e).unwrap(), i as u64);
+    }
+
+    for i in 0..50u32 {
+        let mut builder = EntityBuilder::new();
+        builder.add_bytes(health, &i.to_ne_bytes());
+        world.spawn(builder.build());
+    }
+    let mut total = 0;
+    let mut chunks = 0;
+    for chunk in world.query_dynamic(&[health.id()]) {
+        let column = chunk.get_mut(health.id()).unwrap();
+        assert_eq!(column.len(), 4 * chunk.len() as usize);
+        for bytes in column.chunks_exact(4) {
+            let mut value = [0; 4];
+            value.copy_from_slice(bytes);
+            total += u32::from_ne_bytes(value);
+        }
+        chunks += 1;
+    }
+    assert!(chunks > 1);
+    assert_eq!(total, (0..50).sum::<u32>());
+}
+
+#[test]
+#[should_panic(expected = "split into chunks")]
+fn chunked_whole_column() {
+    use std::alloc::Layout;
+
+    unsafe fn drop_nothing(_: *mut u8) {}
+    let health = unsafe { TypeInfo::dynamic(0, Layout::new::<u32>(), drop_nothing, "health") };
+    let mut world = World::with_chunk_size(64);
+    for _ in 0..10 {
+        let mut builder = EntityBuilder::new();
+        builder.add_bytes(health, &[0; 4]);
+        world.spawn(builder.build());
+    }
+    let archetype = world.archetypes().find(|x| !x.is_empty()).unwrap();
+    archetype.get_column(health.id());
+}
+
+#[test]
+fn over_aligned_columns() {
+    #[repr(align(32))]
+    struct Wide(u8);
+
+    let mut world = World::builder().column_align(64).chunk_size(1024).build();
+    let entities = (0..100u8)
+        .map(|i| world.spawn((i, Wide(i), i as u16)))
+        .collect::<Vec<_>>();
+    for (i, &e) in entities.iter().enumerate() {
+        let wide = world.get::<Wide>(e).unwrap();
+        assert_eq!(&*wide as *const Wide as usize % 32, 0);
+        assert_eq!(wide.0, i as u8);
+    }
+    let mut chunks = 0;
+    for (_, (bytes, wides, shorts)) in world.query::<(&u8, &Wide, &u16)>().iter_chunks() {
+        assert_eq!(bytes.as_ptr() as usize % 64, 0);
+        assert_eq!(wides.as_ptr() as usize % 64, 0);
+        assert_eq!(shorts.as_ptr() as usize % 64, 0);
+        chunks += 1;
+    }
+    assert!(chunks > 1);
+}
+
+#[test]
+fn custom_allocator() {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::sync::atomic::{AtomicIsize, Ordering};
+    use std::sync::Arc;
+
+    struct Counting(Arc<AtomicIsize>);
+
+    unsafe impl GlobalAlloc for Counting {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    let live = Arc::new(AtomicIsize::new(0));
+    let mut world = World::with_allocator(Counting(live.clone()));
+    world.spawn_batch((0..1000).map(|i| (i, i as f32)));
+    let e = world.spawn(("abc",));
+    world.insert_one(e, 42).unwrap();
+    assert!(live.load(Ordering::Relaxed) > 0);
+    world.register_clone::<i32>();
+    world.register_clone::<f32>();
+    world.register_clone::<&str>();
+    let snapshot = world.try_clone().unwrap();
+    drop(world);
+    assert!(live.load(Ordering::Relaxed) > 0);
+    assert_eq!(snapshot.query::<&i32>().iter().count(), 1001);
+    drop(snapshot);
+    assert_eq!(live.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn shrink_to_fit() {
+    struct Stunned(u32);
+
+    for mut world in [World::new(), World::with_chunk_size(256)] {
+        world.register_sparse::<Stunned>();
+        let entities = world
+            .spawn_batch((0..1000).map(|i| (i, i as f32)))
+            .collect::<Vec<_>>();
+        for &e in &entities[..10] {
+            world.insert_one(e, Stunned(e.id())).unwrap();
+        }
+        world.set_parent(entities[1], entities[0]).unwrap();
+        world.despawn_batch(entities[2..].iter().copied());
+        world.shrink_to_fit();
+        assert_eq!(world.query::<&i32>().iter().count(), 2);
+        assert_eq!(*world.get::<f32>(entities[1]).unwrap(), 1.0);
+        assert_eq!(
+            world.get::<Stunned>(entities[1]).unwrap().0,
+            entities[1].id()
+        );
+        assert_eq!(world.parent_of(entities[1]).unwrap(), Some(entities[0]));
+
+        world.despawn_batch(entities[..2].iter().copied());
+        world.shrink_to_fit();
+        let entities = world
+            .spawn_batch((0..100).map(|i| (i, i as f32)))
+            .collect::<Vec<_>>();
+        world.insert_one(entities[99], Stunned(7)).unwrap();
+        for (i, &e) in entities.iter().enumerate() {
+            assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+        }
+        assert_eq!(world.get::<Stunned>(entities[99]).unwrap().0, 7);
+    }
+}
+
+#[test]
+fn shrink_emptied_archetype() {
+    for mut world in [World::new(), World::with_chunk_size(256)] {
+        let e = world.spawn((1i32,));
+        world.despawn(e).unwrap();
+        world.shrink_to_fit();
+        assert_eq!(world.query::<&i32>().iter().count(), 0);
+        assert_eq!(world.query_mut::<&i32>().count(), 0);
+        let entities = world
+            .spawn_batch((0..100).map(|i| (i,)))
+            .collect::<Vec<_>>();
+        for (i, &e) in entities.iter().enumerate() {
+            assert_eq!(*world.get::<i32>(e).unwrap(), i as i32);
+        }
+        assert_eq!(world.query::<&i32>().iter().count(), 100);
+    }
+}
+
+#[test]
+fn shrink_emptied_archetype_prepared() {
+    let mut world = World::new();
+    let e = world.spawn((1, true));
+    world.despawn(e).unwrap();
+    world.shrink_to_fit();
+    let mut prepared = PreparedQuery::<&bool>::new();
+    assert_eq!(prepared.query(&world).iter().count(), 0);
+    world.spawn((2, true));
+    assert_eq!(prepared.query(&world).iter().count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "must be registered as sparse before any entity has one")]
+fn shrink_emptied_archetype_register_sparse() {
+    let mut world = World::new();
+    let e = world.spawn((true,));
+    world.despawn(e).unwrap();
+    world.shrink_to_fit();
+    world.register_sparse::<bool>();
+}
+
+#[test]
+fn remove_empty_archetypes() {
+    struct Stunned;
+
+    let mut world = World::new();
+    world.register_sparse::<Stunned>();
+    let mut prepared = PreparedQuery::<(&i32, Option<&bool>)>::new();
+    let a = world.spawn((1, 'a'));
+    let b = world.spawn((2, true));
+    let transient = world.spawn((3, "abc", 1.0f32));
+    let c = world.spawn((4, 5u8));
+    world.set_parent(c, a).unwrap();
+    world.insert_one(b, Stunned).unwrap();
+    assert_eq!(prepared.query(&world).iter().count(), 4);
+
+    world.despawn(transient).unwrap();
+    world.insert_one(a, 1.0f32).unwrap();
+    world.remove_one::<f32>(a).unwrap();
+    let archetypes = world.archetypes().len();
+    let generation = world.archetypes_generation();
+    world.remove_empty_archetypes();
+    assert_eq!(world.archetypes().len(), archetypes - 2);
+    assert!(world.archetypes().skip(1).all(|x| !x.is_empty()));
+    assert_ne!(world.archetypes_generation(), generation);
+    let generation = world.archetypes_generation();
+    world.remove_empty_archetypes();
+    assert_eq!(world.archetypes_generation(), generation);
+
+    let mut results = prepared
+        .query(&world)
+        .iter()
+        .map(|(e, (&i, flag))| (e, i, flag.copied()))
+        .collect::<Vec<_>>();
+    results.sort_by_key(|x| x.1);
+    assert_eq!(results, [(a, 1, None), (b, 2, Some(true)), (c, 4, None)]);
+    assert_eq!(
+        world
+            .query::<With<Stunned, &i32>>()
+            .iter()
+            .next()
+            .unwrap()
+            .0,
+        b
+    );
+    assert_eq!(world.parent_of(c).unwrap(), Some(a));
+
+    // Cached transitions lead to the right archetypes, whether they were kept or recreated
+    world.insert_one(a, 2.0f32).unwrap();
+    assert_eq!(*world.get::<char>(a).unwrap(), 'a');
+    assert_eq!(*world.get::<f32>(a).unwrap(), 2.0);
+    world.remove_one::<f32>(a).unwrap();
+    world.remove_one::<u8>(c).unwrap();
+    world.insert_one(c, 6u8).unwrap();
+    assert_eq!(*world.get::<u8>(c).unwrap(), 6);
+    let d = world.spawn((5, "def", 2.0f32));
+    assert_eq!(prepared.query(&world).iter().count(), 4);
+    assert_eq!(*world.get::<&str>(d).unwrap(), "def");
+    let results = prepared
+        .query_mut(&mut world)
+        .map(|(e, (&i, _))| (e, i))
+        .collect::<Vec<_>>();
+    for (e, i) in results {
+        assert_eq!(*world.get::<i32>(e).unwrap(), i);
+    }
+}